@@ -0,0 +1,87 @@
+// The `json` ACL category, so all the commands can be granted at once:
+//
+//   ACL SETUSER alice on >password ~* +@json
+//
+// Redis has module ACL categories since 7.2. The API is looked up when the module is loaded,
+// and nothing is registered on older servers.
+
+use crate::commands::module_api;
+use redis_module::{raw, Context, Status};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int, c_void};
+
+pub const CATEGORY: &str = "json";
+
+type AddACLCategory = unsafe extern "C" fn(*mut raw::RedisModuleCtx, *const c_char) -> c_int;
+type GetCommand = unsafe extern "C" fn(*mut raw::RedisModuleCtx, *const c_char) -> *mut c_void;
+type SetCommandACLCategories = unsafe extern "C" fn(*mut c_void, *const c_char) -> c_int;
+
+/// The categories of a command with `flags`, as a space separated list: the `json` category and
+/// the ones the flags place the command in, which are given as well so setting the categories
+/// does not take it out of them
+fn categories(flags: &str) -> String {
+    let mut categories = vec![CATEGORY];
+    for flag in flags.split_whitespace() {
+        match flag {
+            "write" => categories.push("write"),
+            "readonly" => categories.push("read"),
+            "admin" => categories.extend(&["admin", "dangerous"]),
+            _ => {}
+        }
+    }
+    categories.join(" ")
+}
+
+/// Adds the `json` category and puts the `commands`, given with their flags, in it. It can
+/// only be done while the module is loaded, once the commands are created
+pub fn register(ctx: &Context, commands: &[(&str, &str)]) -> Status {
+    let (add_category, get_command, set_categories) = unsafe {
+        match (
+            module_api::<AddACLCategory>(ctx, b"RedisModule_AddACLCategory\0"),
+            module_api::<GetCommand>(ctx, b"RedisModule_GetCommand\0"),
+            module_api::<SetCommandACLCategories>(ctx, b"RedisModule_SetCommandACLCategories\0"),
+        ) {
+            (Some(add_category), Some(get_command), Some(set_categories)) => {
+                (add_category, get_command, set_categories)
+            }
+            _ => return Status::Ok,
+        }
+    };
+    let category = CString::new(CATEGORY).unwrap();
+    if unsafe { add_category(ctx.ctx, category.as_ptr()) } != raw::REDISMODULE_OK as c_int {
+        ctx.log_warning(&format!("Could not add the '{}' ACL category", CATEGORY));
+        return Status::Err;
+    }
+    for (name, flags) in commands {
+        let command_name = CString::new(*name).unwrap();
+        let command_categories = CString::new(categories(flags)).unwrap();
+        let status = unsafe {
+            let command = get_command(ctx.ctx, command_name.as_ptr());
+            if command.is_null() {
+                raw::REDISMODULE_ERR as c_int
+            } else {
+                set_categories(command, command_categories.as_ptr())
+            }
+        };
+        if status != raw::REDISMODULE_OK as c_int {
+            ctx.log_warning(&format!(
+                "Could not add the command '{}' to the '{}' ACL category",
+                name, CATEGORY
+            ));
+            return Status::Err;
+        }
+    }
+    Status::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_categories() {
+        assert_eq!(categories("write deny-oom"), "json write");
+        assert_eq!(categories("readonly"), "json read");
+        assert_eq!(categories("admin"), "json admin dangerous");
+    }
+}
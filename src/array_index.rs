@@ -1,5 +1,6 @@
 pub(crate) trait ArrayIndex {
     fn normalize(self, len: i64) -> usize;
+    fn normalize_bound(self, len: i64) -> usize;
 }
 
 impl ArrayIndex for i64 {
@@ -11,6 +12,16 @@ impl ArrayIndex for i64 {
         };
         index as usize
     }
+
+    // Like normalize but for an exclusive bound, which may be one past the last element
+    fn normalize_bound(self, len: i64) -> usize {
+        let index = if self < 0 {
+            len - len.min(-self)
+        } else {
+            len.min(self)
+        };
+        index as usize
+    }
 }
 
 #[cfg(test)]
@@ -30,4 +41,17 @@ mod tests {
         assert_eq!(5.normalize(5), 4);
         assert_eq!(6.normalize(5), 4);
     }
+
+    #[test]
+    fn test_bound() {
+        // [0,1,2,3,4]
+        assert_eq!((-6).normalize_bound(5), 0);
+        assert_eq!((-5).normalize_bound(5), 0);
+        assert_eq!((-1).normalize_bound(5), 4);
+        assert_eq!(0.normalize_bound(5), 0);
+        assert_eq!(4.normalize_bound(5), 4);
+        assert_eq!(5.normalize_bound(5), 5);
+        assert_eq!(6.normalize_bound(5), 5);
+        assert_eq!(0.normalize_bound(0), 0);
+    }
 }
@@ -18,46 +18,55 @@ enum NodeType {
     // N_BINARY = 0x200
 }
 
-impl From<u64> for NodeType {
-    fn from(n: u64) -> Self {
-        match n {
-            0x1u64 => NodeType::Null,
-            0x2u64 => NodeType::String,
-            0x4u64 => NodeType::Number,
-            0x8u64 => NodeType::Integer,
-            0x10u64 => NodeType::Boolean,
-            0x20u64 => NodeType::Dict,
-            0x40u64 => NodeType::Array,
-            0x80u64 => NodeType::KeyVal,
-            _ => panic!("Can't load old RedisJSON RDB1"),
+impl NodeType {
+    fn load(rdb: *mut raw::RedisModuleIO) -> Result<Self, String> {
+        match raw::load_unsigned(rdb) {
+            0x1u64 => Ok(NodeType::Null),
+            0x2u64 => Ok(NodeType::String),
+            0x4u64 => Ok(NodeType::Number),
+            0x8u64 => Ok(NodeType::Integer),
+            0x10u64 => Ok(NodeType::Boolean),
+            0x20u64 => Ok(NodeType::Dict),
+            0x40u64 => Ok(NodeType::Array),
+            0x80u64 => Ok(NodeType::KeyVal),
+            n => Err(format!("unknown RedisJSON 1.x node type {}", n)),
         }
     }
 }
 
-pub fn json_rdb_load(rdb: *mut raw::RedisModuleIO) -> Value {
-    let node_type = raw::load_unsigned(rdb).into();
-    match node_type {
+fn load_str(rdb: *mut raw::RedisModuleIO) -> Result<String, String> {
+    raw::load_string_buffer(rdb)
+        .to_string()
+        .map_err(|_| "invalid UTF-8 string in RedisJSON 1.x value".to_string())
+}
+
+/// Loads a value saved by RedisJSON 1.x (the C module, encoding version 0), which stored the
+/// tree node by node rather than as a JSON string
+pub fn json_rdb_load(rdb: *mut raw::RedisModuleIO) -> Result<Value, String> {
+    let value = match NodeType::load(rdb)? {
         NodeType::Null => Value::Null,
         NodeType::Boolean => {
             let buffer = raw::load_string_buffer(rdb);
-            Value::Bool(buffer.as_ref()[0] == b'1')
+            Value::Bool(buffer.as_ref().first() == Some(&b'1'))
         }
         NodeType::Integer => Value::Number(raw::load_signed(rdb).into()),
-        NodeType::Number => Value::Number(Number::from_f64(raw::load_double(rdb)).unwrap()),
-        NodeType::String => {
-            let buffer = raw::load_string_buffer(rdb);
-            Value::String(buffer.to_string().unwrap())
+        NodeType::Number => {
+            let n = raw::load_double(rdb);
+            Value::Number(
+                Number::from_f64(n)
+                    .ok_or_else(|| format!("invalid number {} in RedisJSON 1.x value", n))?,
+            )
         }
+        NodeType::String => Value::String(load_str(rdb)?),
         NodeType::Dict => {
             let len = raw::load_unsigned(rdb);
             let mut m = Map::with_capacity(len as usize);
             for _ in 0..len {
-                let t: NodeType = raw::load_unsigned(rdb).into();
-                if t != NodeType::KeyVal {
-                    panic!("Can't load old RedisJSON RDB");
+                if NodeType::load(rdb)? != NodeType::KeyVal {
+                    return Err("expected a key in RedisJSON 1.x object".to_string());
                 }
-                let buffer = raw::load_string_buffer(rdb);
-                m.insert(buffer.to_string().unwrap(), json_rdb_load(rdb));
+                let key = load_str(rdb)?;
+                m.insert(key, json_rdb_load(rdb)?);
             }
             Value::Object(m)
         }
@@ -65,10 +74,11 @@ pub fn json_rdb_load(rdb: *mut raw::RedisModuleIO) -> Value {
             let len = raw::load_unsigned(rdb);
             let mut v = Vec::with_capacity(len as usize);
             for _ in 0..len {
-                v.push(json_rdb_load(rdb))
+                v.push(json_rdb_load(rdb)?)
             }
             Value::Array(v)
         }
-        NodeType::KeyVal => panic!("Can't load old RedisJSON RDB"),
-    }
+        NodeType::KeyVal => return Err("unexpected key in RedisJSON 1.x value".to_string()),
+    };
+    Ok(value)
 }
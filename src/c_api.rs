@@ -7,6 +7,7 @@ use std::{
     os::raw::{c_char, c_void},
 };
 
+use crate::cache;
 use crate::commands::KeyValue;
 use jsonpath_lib::select::select_value::{SelectValue, SelectValueType};
 use jsonpath_lib::select::Selector;
@@ -238,17 +239,111 @@ pub fn json_api_free_iter<M: Manager>(_: M, iter: *mut c_void) {
     }
 }
 
-pub fn json_api_get<M: Manager>(_: M, val: *const c_void, path: *const c_char) -> *const c_void {
-    let v = unsafe { &*(val as *const M::V) };
+/// The values matching `path` in `v`, `None` when the path is not valid
+fn select_values<'a, V: SelectValue>(v: &'a V, path: *const c_char) -> Option<Vec<&'a V>> {
+    let path = unsafe { CStr::from_ptr(path).to_str().ok()? };
+    let node = cache::compile(path).ok()?;
     let mut selector = Selector::new();
+    selector.compiled_path(&node);
     selector.value(v);
-    let path = unsafe { CStr::from_ptr(path).to_str().unwrap() };
-    if selector.str_path(path).is_err() {
-        return null();
+    selector.select().ok()
+}
+
+pub fn json_api_get<M: Manager>(_: M, val: *const c_void, path: *const c_char) -> *const c_void {
+    let v = unsafe { &*(val as *const M::V) };
+    match select_values(v, path) {
+        Some(s) => Box::into_raw(Box::new(ResultsIterator { results: s, pos: 0 })) as *mut c_void,
+        None => null(),
+    }
+}
+
+pub fn json_api_get_type_at<M: Manager>(
+    _: M,
+    json: *const c_void,
+    path: *const c_char,
+    json_type: *mut c_int,
+) -> c_int {
+    let json = unsafe { &*(json as *const M::V) };
+    match select_values(json, path).and_then(|results| results.first().copied()) {
+        Some(v) => {
+            unsafe { *json_type = json_api_get_type_internal(v) as c_int };
+            Status::Ok as c_int
+        }
+        None => Status::Err as c_int,
+    }
+}
+
+pub fn json_api_get_len_at<M: Manager>(
+    m: M,
+    json: *const c_void,
+    path: *const c_char,
+    count: *mut size_t,
+) -> c_int {
+    let value = unsafe { &*(json as *const M::V) };
+    match select_values(value, path).and_then(|results| results.first().copied()) {
+        Some(v) => json_api_get_len(m, v as *const M::V as *const c_void, count),
+        None => Status::Err as c_int,
+    }
+}
+
+/// Called by `JSONAPI_visit` for each match, with its `JSONType`, and its serialization that is
+/// only valid during the call, returns non-zero to stop visiting
+pub type JSONVisitor = extern "C" fn(
+    json_type: c_int,
+    json: *const c_char,
+    len: size_t,
+    privdata: *mut c_void,
+) -> c_int;
+
+pub fn json_api_visit<M: Manager>(
+    _: M,
+    json: *const c_void,
+    path: *const c_char,
+    visitor: JSONVisitor,
+    privdata: *mut c_void,
+) -> c_int {
+    let json = unsafe { &*(json as *const M::V) };
+    let results = match select_values(json, path) {
+        Some(results) => results,
+        None => return Status::Err as c_int,
+    };
+    for v in results {
+        let res = match serde_json::to_string(v) {
+            Ok(res) => res,
+            Err(_) => return Status::Err as c_int,
+        };
+        let json_type = json_api_get_type_internal(v) as c_int;
+        if visitor(
+            json_type,
+            res.as_ptr() as *const c_char,
+            res.len(),
+            privdata,
+        ) != 0
+        {
+            break;
+        }
+    }
+    Status::Ok as c_int
+}
+
+pub fn json_api_get_json_from_key<M: Manager>(
+    manager: M,
+    ctx: *mut rawmod::RedisModuleCtx,
+    key: *const c_char,
+    path: *const c_char,
+    str: *mut *mut rawmod::RedisModuleString,
+) -> c_int {
+    let key = match unsafe { CStr::from_ptr(key).to_str() } {
+        Ok(key) => key,
+        Err(_) => return Status::Err as c_int,
+    };
+    let json = json_api_open_key_internal(manager, ctx, RedisString::create(ctx, key));
+    if json.is_null() {
+        return Status::Err as c_int;
     }
-    match selector.select() {
-        Ok(s) => Box::into_raw(Box::new(ResultsIterator { results: s, pos: 0 })) as *mut c_void,
-        Err(_) => null(),
+    match select_values(unsafe { &*json }, path).map(|results| serde_json::to_string(&results)) {
+        Some(Ok(res)) => create_rmstring(ctx, &res, str),
+        _ => Status::Err as c_int,
     }
 }
 
@@ -538,10 +633,129 @@ macro_rules! redis_json_module_export_shared_api {
             }
         }
 
+        /// Serializes the values matching `path` in the JSON value of the key named `key` as a
+        /// JSON array, in one call and without going through RESP
+        ///
+        /// On success `*str` is set to a string owned by the caller, to free with
+        /// `RedisModule_FreeString` (or automatically when `ctx` uses automatic memory). The
+        /// string is a copy, so it stays valid after the key is modified or deleted. Fails when
+        /// the key does not exist or does not hold JSON, and when `path` is not valid.
+        #[no_mangle]
+        pub extern "C" fn JSONAPI_getJSONFromKey(
+            ctx: *mut rawmod::RedisModuleCtx,
+            key: *const c_char,
+            path: *const c_char,
+            str: *mut *mut rawmod::RedisModuleString,
+        ) -> c_int {
+            $pre_command_function_expr(&get_llapi_ctx(), &Vec::new());
+
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => json_api_get_json_from_key(mngr, ctx, key, path, str),
+                None => json_api_get_json_from_key(
+                    manager::RedisJsonKeyManager {
+                        phantom: PhantomData,
+                    },
+                    ctx,
+                    key,
+                    path,
+                    str,
+                ),
+            }
+        }
+
+        /// Calls `visitor` with each value matching `path` in the opened JSON value `json`, in
+        /// document order, so only the matched values are serialized
+        ///
+        /// Like every function of this API it must be called with the GIL held, i.e. from a
+        /// command or with a thread safe context locked. The visitor runs synchronously in the
+        /// calling thread while the keyspace is locked, so it must neither block nor modify the
+        /// key. Fails when `path` is not valid.
+        #[no_mangle]
+        pub extern "C" fn JSONAPI_visit(
+            json: *const c_void,
+            path: *const c_char,
+            visitor: JSONVisitor,
+            privdata: *mut c_void,
+        ) -> c_int {
+            $pre_command_function_expr(&get_llapi_ctx(), &Vec::new());
+
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => json_api_visit(mngr, json, path, visitor, privdata),
+                None => json_api_visit(
+                    manager::RedisJsonKeyManager {
+                        phantom: PhantomData,
+                    },
+                    json,
+                    path,
+                    visitor,
+                    privdata,
+                ),
+            }
+        }
+
+        /// Sets `*json_type` to the `JSONType` of the first value matching `path` in the opened
+        /// JSON value `json`, fails when nothing matches or `path` is not valid
+        #[no_mangle]
+        pub extern "C" fn JSONAPI_getTypeAt(
+            json: *const c_void,
+            path: *const c_char,
+            json_type: *mut c_int,
+        ) -> c_int {
+            $pre_command_function_expr(&get_llapi_ctx(), &Vec::new());
+
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => json_api_get_type_at(mngr, json, path, json_type),
+                None => json_api_get_type_at(
+                    manager::RedisJsonKeyManager {
+                        phantom: PhantomData,
+                    },
+                    json,
+                    path,
+                    json_type,
+                ),
+            }
+        }
+
+        /// Sets `*count` to the length of the first value matching `path` in the opened JSON
+        /// value `json`, as `getLen` does, fails when nothing matches, `path` is not valid or
+        /// the value is not a string, an array or an object
+        #[no_mangle]
+        pub extern "C" fn JSONAPI_getLenAt(
+            json: *const c_void,
+            path: *const c_char,
+            count: *mut size_t,
+        ) -> c_int {
+            $pre_command_function_expr(&get_llapi_ctx(), &Vec::new());
+
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => json_api_get_len_at(mngr, json, path, count),
+                None => json_api_get_len_at(
+                    manager::RedisJsonKeyManager {
+                        phantom: PhantomData,
+                    },
+                    json,
+                    path,
+                    count,
+                ),
+            }
+        }
+
         static REDISJSON_GETAPI: &str = concat!("RedisJSON_V1", "\0");
+        // The same struct is shared under the name of every version of the API, as each one only
+        // adds functions at its end. Modules needing the functions of a version ask for its name,
+        // which fails with an older RedisJSON instead of calling past the end of the struct.
+        // `REDIS_JSON_API_VERSION` must be the latest version.
+        static REDISJSON_GETAPI_V2: &str = concat!("RedisJSON_V2", "\0");
 
         pub fn export_shared_api(ctx: &Context) {
-            ctx.log_notice("Exported RedisJSON_V1 API");
+            ctx.log_notice(&format!(
+                "Exported RedisJSON_V1 to RedisJSON_V{} API",
+                $crate::REDIS_JSON_API_VERSION
+            ));
             unsafe {
                 LLAPI_CTX = Some(rawmod::RedisModule_GetThreadSafeContext.unwrap()(
                     std::ptr::null_mut(),
@@ -551,6 +765,10 @@ macro_rules! redis_json_module_export_shared_api {
                 &JSONAPI as *const RedisJSONAPI_V1 as *const c_void,
                 REDISJSON_GETAPI.as_ptr() as *const c_char,
             );
+            ctx.export_shared_api(
+                &JSONAPI as *const RedisJSONAPI_V1 as *const c_void,
+                REDISJSON_GETAPI_V2.as_ptr() as *const c_char,
+            );
         }
 
         static JSONAPI: RedisJSONAPI_V1 = RedisJSONAPI_V1 {
@@ -569,6 +787,10 @@ macro_rules! redis_json_module_export_shared_api {
             getString: JSONAPI_getString,
             getJSON: JSONAPI_getJSON,
             isJSON: JSONAPI_isJSON,
+            getJSONFromKey: JSONAPI_getJSONFromKey,
+            visit: JSONAPI_visit,
+            getTypeAt: JSONAPI_getTypeAt,
+            getLenAt: JSONAPI_getLenAt,
         };
 
         #[repr(C)]
@@ -602,6 +824,100 @@ macro_rules! redis_json_module_export_shared_api {
                 str: *mut *mut rawmod::RedisModuleString,
             ) -> c_int,
             pub isJSON: extern "C" fn(key: *mut rawmod::RedisModuleKey) -> c_int,
+            // RedisJSON_V2, functions are only added after the existing ones, so modules built
+            // against an older version of this struct keep working
+            pub getJSONFromKey: extern "C" fn(
+                ctx: *mut rawmod::RedisModuleCtx,
+                key: *const c_char,
+                path: *const c_char,
+                str: *mut *mut rawmod::RedisModuleString,
+            ) -> c_int,
+            pub visit: extern "C" fn(
+                json: *const c_void,
+                path: *const c_char,
+                visitor: JSONVisitor,
+                privdata: *mut c_void,
+            ) -> c_int,
+            pub getTypeAt: extern "C" fn(
+                json: *const c_void,
+                path: *const c_char,
+                json_type: *mut c_int,
+            ) -> c_int,
+            pub getLenAt: extern "C" fn(
+                json: *const c_void,
+                path: *const c_char,
+                count: *mut size_t,
+            ) -> c_int,
         }
     };
 }
+
+// The API struct is only defined when the module is built on its own
+#[cfg(all(test, not(feature = "as-library")))]
+mod tests {
+    /// The names of the functions of `RedisJSONAPI_V1` in the C header, in their order
+    fn header_functions() -> Vec<&'static str> {
+        let header = include_str!("include/rejson_api.h");
+        let start = header.find("typedef struct RedisJSONAPI_V1 {").unwrap();
+        let end = start + header[start..].find("} RedisJSONAPI_V1;").unwrap();
+        header[start..end]
+            .split("(*")
+            .skip(1)
+            .map(|s| &s[..s.find(')').unwrap()])
+            .collect()
+    }
+
+    #[test]
+    fn test_header_visitor() {
+        let header = include_str!("include/rejson_api.h");
+        // The arguments of `JSONVisitor`
+        assert!(header.contains(
+            "typedef int (*JSONVisitor)(JSONType type, const char *json, size_t len, void *privdata);"
+        ));
+    }
+
+    #[test]
+    fn test_header_api_names() {
+        let header = include_str!("include/rejson_api.h");
+        for version in 1..=crate::REDIS_JSON_API_VERSION {
+            assert!(header.contains(&format!(
+                "#define RedisJSON_V{}_API_NAME \"RedisJSON_V{}\"",
+                version, version
+            )));
+        }
+    }
+
+    #[test]
+    fn test_header_functions() {
+        // In the order of the fields of `RedisJSONAPI_V1`, which modules rely on
+        assert_eq!(
+            header_functions(),
+            [
+                "openKey",
+                "openKeyFromStr",
+                "get",
+                "next",
+                "len",
+                "freeIter",
+                "getAt",
+                "getLen",
+                "getType",
+                "getInt",
+                "getDouble",
+                "getBoolean",
+                "getString",
+                "getJSON",
+                "isJSON",
+                "getJSONFromKey",
+                "visit",
+                "getTypeAt",
+                "getLenAt",
+            ]
+        );
+        // Every function of the struct is declared
+        assert_eq!(
+            std::mem::size_of::<crate::RedisJSONAPI_V1>(),
+            header_functions().len() * std::mem::size_of::<usize>()
+        );
+    }
+}
@@ -0,0 +1,134 @@
+// LRU cache of compiled JSONPath expressions
+//
+// Commands run on Redis' main thread, so the cache is kept per thread and needs no locking.
+// Its capacity comes from the PATH_CACHE_SIZE module argument, it can be reset with
+// JSON._CACHEINIT and is reported by JSON._CACHEINFO.
+
+use crate::config;
+use jsonpath_lib::parser::{Node, NodeVisitor, ParseToken};
+use jsonpath_lib::select::JsonPathError;
+use jsonpath_lib::Parser;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::rc::Rc;
+
+/// A parsed JSONPath, shared between the cache and the selectors using it
+pub type CompiledPath = Rc<Node>;
+
+struct Entry {
+    node: CompiledPath,
+    last_used: u64,
+    bytes: usize,
+}
+
+#[derive(Default)]
+struct PathCache {
+    capacity: usize,
+    entries: HashMap<String, Entry>,
+    // last use -> path, the first item is the least recently used path
+    lru: BTreeMap<u64, String>,
+    tick: u64,
+    bytes: usize,
+    hits: u64,
+    misses: u64,
+}
+
+pub struct CacheInfo {
+    pub capacity: usize,
+    pub entries: usize,
+    pub bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+thread_local! {
+    static PATH_CACHE: RefCell<PathCache> = RefCell::new(PathCache {
+        capacity: config::path_cache_size(),
+        ..PathCache::default()
+    });
+}
+
+/// Counts the number of nodes of a compiled path, to estimate its memory usage
+struct TokenCounter(usize);
+
+impl NodeVisitor for TokenCounter {
+    fn visit_token(&mut self, _token: &ParseToken) {
+        self.0 += 1;
+    }
+}
+
+impl PathCache {
+    fn get_or_compile(&mut self, path: &str) -> Result<CompiledPath, JsonPathError> {
+        if self.capacity == 0 {
+            return Ok(Rc::new(Parser::compile(path).map_err(JsonPathError::Path)?));
+        }
+
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(path) {
+            self.hits += 1;
+            let path = self.lru.remove(&entry.last_used).unwrap();
+            self.lru.insert(tick, path);
+            entry.last_used = tick;
+            return Ok(Rc::clone(&entry.node));
+        }
+
+        self.misses += 1;
+        let node = Rc::new(Parser::compile(path).map_err(JsonPathError::Path)?);
+        if self.entries.len() >= self.capacity {
+            self.evict();
+        }
+        let mut counter = TokenCounter(0);
+        counter.visit(&node);
+        let bytes = 2 * path.len() + counter.0 * mem::size_of::<Node>() + mem::size_of::<Entry>();
+        self.bytes += bytes;
+        self.lru.insert(tick, path.to_string());
+        self.entries.insert(
+            path.to_string(),
+            Entry {
+                node: Rc::clone(&node),
+                last_used: tick,
+                bytes,
+            },
+        );
+        Ok(node)
+    }
+
+    fn evict(&mut self) {
+        let oldest = self.lru.keys().next().copied();
+        if let Some(path) = oldest.and_then(|tick| self.lru.remove(&tick)) {
+            if let Some(entry) = self.entries.remove(&path) {
+                self.bytes -= entry.bytes;
+            }
+        }
+    }
+}
+
+/// Returns the compiled form of `path`, compiling it only if it is not cached
+pub fn compile(path: &str) -> Result<CompiledPath, JsonPathError> {
+    PATH_CACHE.with(|cache| cache.borrow_mut().get_or_compile(path))
+}
+
+/// Drops all cached paths and statistics, a `capacity` of 0 disables the cache
+pub fn init(capacity: usize) {
+    PATH_CACHE.with(|cache| {
+        *cache.borrow_mut() = PathCache {
+            capacity,
+            ..PathCache::default()
+        }
+    });
+}
+
+pub fn info() -> CacheInfo {
+    PATH_CACHE.with(|cache| {
+        let cache = cache.borrow();
+        CacheInfo {
+            capacity: cache.capacity,
+            entries: cache.entries.len(),
+            bytes: cache.bytes,
+            hits: cache.hits,
+            misses: cache.misses,
+        }
+    })
+}
@@ -1,8 +1,18 @@
-use crate::formatter::RedisJsonFormatter;
+use crate::array_index::ArrayIndex;
+use crate::cache;
+use crate::compress;
+use crate::config;
+use crate::diff;
+use crate::flatten::{self, PathNotation};
+use crate::formatter::{self, NumFormat, RedisJsonFormatter, SortedKeys};
 use crate::manager::{AddUpdateInfo, Manager, ReadHolder, SetUpdateInfo, UpdateInfo, WriteHolder};
-use crate::redisjson::{Format, Path};
+use crate::path_function::PathFunction;
+use crate::pointer;
+use crate::redisjson::{Format, Path, RedisJSON};
+use crate::resp;
+use crate::schema;
 use jsonpath_lib::select::select_value::{SelectValue, SelectValueType};
-use redis_module::{Context, RedisValue};
+use redis_module::{raw, Context, RedisValue};
 use redis_module::{NextArg, RedisError, RedisResult, RedisString, REDIS_OK};
 
 use jsonpath_lib::select::Selector;
@@ -13,17 +23,63 @@ use crate::error::Error;
 
 use crate::redisjson::SetOptions;
 
-use serde_json::{Map, Value};
+use serde_json::{Map, Number, Value};
 
 use serde::Serialize;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::mem;
+use std::ops::Range;
+use std::os::raw::{c_char, c_int, c_long, c_void};
+use std::ptr;
 
 const JSON_ROOT_PATH: &str = "$";
 const CMD_ARG_NOESCAPE: &str = "NOESCAPE";
 const CMD_ARG_INDENT: &str = "INDENT";
+const CMD_ARG_INDENTSIZE: &str = "INDENTSIZE";
 const CMD_ARG_NEWLINE: &str = "NEWLINE";
 const CMD_ARG_SPACE: &str = "SPACE";
 const CMD_ARG_FORMAT: &str = "FORMAT";
+const CMD_ARG_SORTKEYS: &str = "SORTKEYS";
+const CMD_ARG_PRETTY: &str = "PRETTY";
+const CMD_ARG_REQUIRE: &str = "REQUIRE";
+const CMD_ARG_PRECISION: &str = "PRECISION";
+const CMD_ARG_NUMFORMAT: &str = "NUMFORMAT";
+const CMD_ARG_SEPARATOR: &str = "SEPARATOR";
+const CMD_ARG_CREATE: &str = "CREATE";
+const CMD_ARG_CREATEPATH: &str = "CREATEPATH";
+const CMD_ARG_GT: &str = "GT";
+const CMD_ARG_LT: &str = "LT";
+const CMD_ARG_GET: &str = "GET";
+const CMD_ARG_PATCH: &str = "PATCH";
+const CMD_ARG_SCHEMA: &str = "SCHEMA";
+const CMD_ARG_ERRORS: &str = "ERRORS";
+const CMD_ARG_CANONICAL: &str = "CANONICAL";
+const CMD_ARG_ROOTNAME: &str = "ROOTNAME";
+const CMD_ARG_LIMIT: &str = "LIMIT";
+const CMD_ARG_NOTATION: &str = "NOTATION";
+const CMD_ARG_COMPRESS: &str = "COMPRESS";
+const FORMAT_NDJSON: &str = "NDJSON";
+/// Name of the member holding the value in the BSON documents replied by JSON.GET
+const BSON_VALUE_KEY: &str = "value";
+const CMD_ARG_NUMERIC: &str = "NUMERIC";
+const CMD_ARG_DEFAULT: &str = "DEFAULT";
+const CMD_ARG_EXCLUDE: &str = "EXCLUDE";
+const CMD_ARG_BYTES: &str = "BYTES";
+const CMD_ARG_KEEPTTL: &str = "KEEPTTL";
+const CMD_ARG_STRINGS: &str = "STRINGS";
+
+// Number of members returned by JSON.ENTRIES when COUNT is not given
+const DEFAULT_ENTRIES_COUNT: usize = 10;
+
+// Formatting used by PRETTY for whatever is not set explicitly
+const PRETTY_INDENT: &str = "\t";
+const PRETTY_NEWLINE: &str = "\n";
+const PRETTY_SPACE: &str = " ";
+
+// INDENTSIZE is a number of these spaces, at most 10 like in JSON.stringify
+const INDENT_SPACES: &str = "          ";
 
 // Compile time evaluation of the max len() of all elements of the array
 const fn max_strlen(arr: &[&str]) -> usize {
@@ -43,15 +99,144 @@ const fn max_strlen(arr: &[&str]) -> usize {
     max_strlen
 }
 
-// We use this constant to further optimize json_get command, by calculating the max subcommand length
 // Any subcommand added to JSON.GET should be included on the following array
-const JSONGET_SUBCOMMANDS_MAXSTRLEN: usize = max_strlen(&[
+const JSONGET_SUBCOMMANDS: &[&str] = &[
     CMD_ARG_NOESCAPE,
     CMD_ARG_INDENT,
+    CMD_ARG_INDENTSIZE,
     CMD_ARG_NEWLINE,
     CMD_ARG_SPACE,
     CMD_ARG_FORMAT,
-]);
+    CMD_ARG_SORTKEYS,
+    CMD_ARG_PRETTY,
+    CMD_ARG_REQUIRE,
+    CMD_ARG_PRECISION,
+    CMD_ARG_NUMFORMAT,
+    CMD_ARG_ROOTNAME,
+    CMD_ARG_LIMIT,
+    CMD_ARG_COMPRESS,
+    CMD_ARG_DEFAULT,
+    CMD_ARG_EXCLUDE,
+];
+
+// We use this constant to further optimize json_get command, by calculating the max subcommand length
+const JSONGET_SUBCOMMANDS_MAXSTRLEN: usize = max_strlen(JSONGET_SUBCOMMANDS);
+
+// Capacity the serialization buffer keeps between replies, so a single huge reply does not
+// hold on to its memory
+const SERIALIZE_BUFFER_MAX_CAPACITY: usize = 1 << 20;
+
+thread_local! {
+    // Replies are serialized here and replied straight from it, so the buffer's growth is not
+    // paid again on each reply. Each thread running commands has its own buffer
+    static SERIALIZE_BUFFER: RefCell<Vec<u8>> = RefCell::new(Vec::new());
+}
+
+/// Takes the serialization buffer, it is empty until `release_serialize_buffer` gives it back
+fn take_serialize_buffer() -> Vec<u8> {
+    SERIALIZE_BUFFER.with(|buffer| mem::take(&mut *buffer.borrow_mut()))
+}
+
+fn release_serialize_buffer(mut buffer: Vec<u8>) {
+    buffer.clear();
+    buffer.shrink_to(SERIALIZE_BUFFER_MAX_CAPACITY);
+    SERIALIZE_BUFFER.with(|b| *b.borrow_mut() = buffer);
+}
+
+/// Replies `value` and gives a serialization buffer it holds back, instead of replying a copy of
+/// the buffer. Redis copies the reply into the client's output buffer, so the reply is in memory
+/// twice at most while it is sent
+fn reply_serialized(ctx: &Context, value: RedisValue) -> RedisValue {
+    match value {
+        RedisValue::StringBuffer(buffer) => {
+            unsafe {
+                raw::RedisModule_ReplyWithStringBuffer.unwrap()(
+                    ctx.ctx,
+                    buffer.as_ptr() as *const c_char,
+                    buffer.len(),
+                );
+            }
+            release_serialize_buffer(buffer);
+            RedisValue::NoReply
+        }
+        value => value,
+    }
+}
+
+/// Whether the client issuing the current command negotiated RESP3 (`HELLO 3`)
+fn is_resp3(ctx: &Context) -> bool {
+    let flags = unsafe { raw::RedisModule_GetContextFlags.unwrap()(ctx.ctx) } as u32;
+    flags & raw::REDISMODULE_CTX_FLAGS_RESP3 != 0
+}
+
+type ReplyWithBool = unsafe extern "C" fn(*mut raw::RedisModuleCtx, c_int) -> c_int;
+type ReplyWithMap = unsafe extern "C" fn(*mut raw::RedisModuleCtx, c_long) -> c_int;
+
+/// Looks up the module API function `name`, a NUL terminated name, the way `RedisModule_Init`
+/// looks up the module API, from the first field of the context. It is `None` when the server
+/// does not have it. `F` must be the type of the function
+pub(crate) unsafe fn module_api<F: Copy>(ctx: &Context, name: &[u8]) -> Option<F> {
+    type GetApi = unsafe extern "C" fn(*const c_char, *mut c_void) -> c_int;
+    let mut api: *mut c_void = ptr::null_mut();
+    let get_api: GetApi = mem::transmute(*(ctx.ctx as *const *const c_void));
+    let status = get_api(
+        name.as_ptr() as *const c_char,
+        &mut api as *mut _ as *mut c_void,
+    );
+    if status != raw::REDISMODULE_OK as c_int || api.is_null() {
+        return None;
+    }
+    Some(mem::transmute_copy(&api))
+}
+
+/// `RedisModule_ReplyWithBool`, which Redis has since 7.0 and redis-module does not bind. It is
+/// `None` on older servers
+fn reply_with_bool_api(ctx: &Context) -> Option<ReplyWithBool> {
+    unsafe { module_api(ctx, b"RedisModule_ReplyWithBool\0") }
+}
+
+/// `RedisModule_ReplyWithMap`, which Redis has since 7.0 and redis-module does not bind. It is
+/// `None` on older servers
+fn reply_with_map_api(ctx: &Context) -> Option<ReplyWithMap> {
+    unsafe { module_api(ctx, b"RedisModule_ReplyWithMap\0") }
+}
+
+/// Replies with booleans, or null for `None`, as RESP3 booleans when the client negotiated
+/// RESP3 and as the strings `"true"` and `"false"` otherwise
+fn reply_bools(ctx: &Context, bools: &[Option<bool>], array: bool) -> RedisResult {
+    let api = if is_resp3(ctx) {
+        reply_with_bool_api(ctx)
+    } else {
+        None
+    };
+    let reply_with_bool = match api {
+        Some(f) => f,
+        None => {
+            let mut res = bools
+                .iter()
+                .map(|b| b.map_or(RedisValue::Null, |b| b.to_string().into()));
+            return Ok(if array {
+                RedisValue::Array(res.collect())
+            } else {
+                res.next().unwrap_or(RedisValue::Null)
+            });
+        }
+    };
+    if array {
+        raw::reply_with_array(ctx.ctx, bools.len() as c_long);
+    }
+    for b in bools {
+        match b {
+            Some(b) => unsafe {
+                reply_with_bool(ctx.ctx, *b as c_int);
+            },
+            None => {
+                ctx.reply(Ok(RedisValue::Null));
+            }
+        }
+    }
+    Ok(RedisValue::NoReply)
+}
 
 pub struct KeyValue<'a, V: SelectValue> {
     val: &'a V,
@@ -90,17 +275,24 @@ impl<'a, V: SelectValue> KeyValue<'a, V> {
         let results = self.get_values(path)?;
         match results.first() {
             Some(s) => Ok(s),
-            None => Err("ERR path does not exist".into()),
+            None => Err(Error::path_not_found()),
         }
     }
 
-    fn resp_serialize(&'a self, path: &'a str) -> RedisResult {
+    /// With `canonical` object members are replied sorted by key instead of in stored order
+    fn resp_serialize(&'a self, path: &'a str, resp3: bool, canonical: bool) -> RedisResult {
         let v = self.get_first(path)?;
-        Ok(self.resp_serialize_inner(v))
+        Ok(self.resp_serialize_inner(v, resp3, canonical, 0)?)
     }
 
-    fn resp_serialize_inner(&'a self, v: &V) -> RedisValue {
-        match v.get_type() {
+    fn resp_serialize_inner(
+        &'a self,
+        v: &V,
+        resp3: bool,
+        canonical: bool,
+        depth: usize,
+    ) -> Result<RedisValue, Error> {
+        let res = match v.get_type() {
             SelectValueType::Null => RedisValue::Null,
 
             SelectValueType::Bool => {
@@ -113,101 +305,238 @@ impl<'a, V: SelectValue> KeyValue<'a, V> {
 
             SelectValueType::Long => RedisValue::Integer(v.get_long()),
 
-            SelectValueType::Double => RedisValue::Float(v.get_double()),
+            SelectValueType::Double => {
+                let num = v.get_double();
+                if resp3 {
+                    RedisValue::Float(num)
+                } else {
+                    // RESP2 has no double type, and replying with `%.17g` would turn `2.0` into `2`,
+                    // so keep the JSON representation to preserve the int/float distinction. It is a
+                    // simple string, like booleans, so it is not mistaken for a string by FORMAT RESP
+                    RedisValue::SimpleString(if num.is_finite() {
+                        formatter::format_float(num)
+                    } else {
+                        num.to_string()
+                    })
+                }
+            }
 
             SelectValueType::String => RedisValue::BulkString(v.get_str()),
 
+            SelectValueType::Array | SelectValueType::Object if depth >= config::max_depth() => {
+                return Err(format!(
+                    "ERR maximum nesting depth of {} exceeded",
+                    config::max_depth()
+                )
+                .into());
+            }
+
             SelectValueType::Array => {
                 let mut res: Vec<RedisValue> = Vec::with_capacity(v.len().unwrap() + 1);
                 res.push(RedisValue::SimpleStringStatic("["));
-                v.values()
-                    .unwrap()
-                    .for_each(|v| res.push(self.resp_serialize_inner(v)));
+                for v in v.values().unwrap() {
+                    res.push(self.resp_serialize_inner(v, resp3, canonical, depth + 1)?);
+                }
                 RedisValue::Array(res)
             }
 
             SelectValueType::Object => {
-                let mut res: Vec<RedisValue> = Vec::with_capacity(v.len().unwrap() + 1);
+                let mut res: Vec<RedisValue> = Vec::with_capacity(v.len().unwrap() * 2 + 1);
                 res.push(RedisValue::SimpleStringStatic("{"));
-                for (k, v) in v.items().unwrap() {
+                let mut items: Vec<_> = v.items().unwrap().collect();
+                if canonical {
+                    items.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+                }
+                for (k, v) in items {
                     res.push(RedisValue::BulkString(k.to_string()));
-                    res.push(self.resp_serialize_inner(v));
+                    res.push(self.resp_serialize_inner(v, resp3, canonical, depth + 1)?);
                 }
                 RedisValue::Array(res)
             }
-        }
+        };
+        Ok(res)
     }
 
     fn get_values<'b>(&'a self, path: &'b str) -> Result<Vec<&'a V>, Error> {
+        let node = cache::compile(path)?;
         let mut selector = Selector::new();
-        selector.str_path(path)?;
+        selector.compiled_path(&node);
         selector.value(self.val);
         let results = selector.select()?;
         Ok(results)
     }
 
-    fn serialize_object<O: Serialize>(
+    /// Serializes `o` into `buffer` in the format of the options
+    fn serialize_into<O: Serialize>(&'a self, buffer: &mut Vec<u8>, o: &O, options: &GetOptions) {
+        let formatter = RedisJsonFormatter::new(
+            options.indent,
+            options.space,
+            options.newline,
+            options.precision,
+            options.num_format,
+        );
+
+        let mut out = serde_json::Serializer::with_formatter(buffer, formatter);
+        match options.root_name {
+            Some(name) => {
+                let mut root = BTreeMap::new();
+                root.insert(name, o);
+                root.serialize(&mut out).unwrap()
+            }
+            None => o.serialize(&mut out).unwrap(),
+        }
+    }
+
+    /// Serializes the reply of JSON.GET in the FORMAT of the options
+    fn serialize_reply<O: Serialize>(
         &'a self,
         o: &O,
-        indent: Option<&str>,
-        newline: Option<&str>,
-        space: Option<&str>,
-    ) -> String {
-        let formatter = RedisJsonFormatter::new(indent, space, newline);
+        options: &GetOptions,
+    ) -> Result<RedisValue, Error> {
+        if options.format == Format::JSON {
+            // The buffer itself is the reply, `reply_serialized` gives it back once it is replied
+            let mut buffer = take_serialize_buffer();
+            self.serialize_into(&mut buffer, o, options);
+            return Ok(RedisValue::StringBuffer(buffer));
+        }
+        let value = match options.root_name {
+            Some(name) => {
+                let mut root = BTreeMap::new();
+                root.insert(name, o);
+                serde_json::to_value(root)?
+            }
+            None => serde_json::to_value(o)?,
+        };
+        encode_reply(value, options.format)
+    }
+
+    /// The first value at `path`, or the DEFAULT option when there is none
+    fn first_or_default<'o>(
+        &'a self,
+        path: &str,
+        options: &'o GetOptions,
+    ) -> Result<Found<'o, V>, Error>
+    where
+        'a: 'o,
+    {
+        let mut found = self.get_found(path, options)?;
+        match options.default_value() {
+            _ if !found.is_empty() => Ok(found.swap_remove(0)),
+            Some(default) => Ok(Found::Default(default)),
+            None => Err(Error::path_not_found()),
+        }
+    }
 
-        let mut out = serde_json::Serializer::with_formatter(Vec::new(), formatter);
-        o.serialize(&mut out).unwrap();
-        String::from_utf8(out.into_inner()).unwrap()
+    /// The values matched by `path`, or the results of the function it ends with
+    fn get_found<'o>(
+        &'a self,
+        path: &str,
+        options: &'o GetOptions,
+    ) -> Result<Vec<Found<'o, V>>, Error>
+    where
+        'a: 'o,
+    {
+        match PathFunction::split(path) {
+            Some((path, function)) => self
+                .get_values(path)?
+                .into_iter()
+                .map(|v| function.apply(v).map(Found::Computed))
+                .collect(),
+            None => Ok(self
+                .get_values(path)?
+                .into_iter()
+                .map(|v| Found::Value(options.wrap(v)))
+                .collect()),
+        }
     }
 
-    fn to_json(
+    /// Serializes each item on its own line, as newline-delimited JSON, into the serialization
+    /// buffer
+    fn serialize_lines<O: Serialize>(
         &'a self,
-        paths: &mut Vec<Path>,
-        indent: Option<&str>,
-        newline: Option<&str>,
-        space: Option<&str>,
-        format: Format,
-    ) -> Result<RedisValue, Error> {
-        if format == Format::BSON {
-            return Err("Soon to come...".into());
+        items: impl Iterator<Item = O>,
+        options: &GetOptions,
+    ) -> RedisValue {
+        let mut buffer = take_serialize_buffer();
+        for item in items {
+            self.serialize_into(&mut buffer, &item, options);
+            buffer.push(b'\n');
+        }
+        RedisValue::StringBuffer(buffer)
+    }
+
+    /// Serializes the elements of `value` as newline-delimited JSON, taking LIMIT into account
+    fn array_to_lines(&'a self, value: &'a V, options: &GetOptions) -> Result<RedisValue, Error> {
+        if value.get_type() != SelectValueType::Array {
+            return Err("ERR FORMAT NDJSON requires the value to be an array".into());
+        }
+        let (offset, count) = options.limit.unwrap_or((0, usize::MAX));
+        let elements = value.values().unwrap().skip(offset).take(count);
+        Ok(self.serialize_lines(
+            elements.map(|v| SortedKeys::new(v, options.sort_keys)),
+            options,
+        ))
+    }
+
+    fn to_json(&'a self, paths: &mut Vec<Path>, options: &GetOptions) -> Result<RedisValue, Error> {
+        if paths.len() > 1 && options.ndjson {
+            return Err("ERR FORMAT NDJSON requires a single path".into());
         }
         if paths.len() > 1 {
             // TODO: Creating a temp doc here duplicates memory usage. This can be very memory inefficient.
             // A better way would be to create a doc of references to the original doc but no current support
             // in serde_json. I'm going for this implementation anyway because serde_json isn't supposed to be
             // memory efficient and we're using it anyway. See https://github.com/serde-rs/json/issues/635.
-            let temp_doc = paths.drain(..).fold(HashMap::new(), |mut acc, path| {
+            let mut temp_doc = BTreeMap::new();
+            for path in paths.drain(..) {
+                if PathFunction::split(path.get_path()).is_some() {
+                    let value = self.get_found(path.get_path(), options)?.into_iter().next();
+                    let value = value.or_else(|| options.default_value().map(Found::Default));
+                    if value.is_none() && options.require {
+                        return Err(path_not_found(&path));
+                    }
+                    temp_doc.insert(path.get_original(), value);
+                    continue;
+                }
+                let node = match cache::compile(path.get_path()) {
+                    Ok(node) => node,
+                    Err(_) if !options.require => continue,
+                    Err(e) => return Err(e.into()),
+                };
                 let mut selector = Selector::new();
+                selector.compiled_path(&node);
                 selector.value(self.val);
-                if selector.str_path(path.get_path()).is_err() {
-                    return acc;
-                }
                 let value = match selector.select() {
-                    Ok(s) => s.first().map(|v| *v),
+                    Ok(s) => s.first().map(|v| Found::Value(options.wrap(*v))),
                     Err(_) => None,
                 };
-                acc.insert(path.get_original(), value);
-                acc
-            });
-            Ok(self
-                .serialize_object(&temp_doc, indent, newline, space)
-                .into())
+                let value = value.or_else(|| options.default_value().map(Found::Default));
+                if value.is_none() && options.require {
+                    return Err(path_not_found(&path));
+                }
+                temp_doc.insert(path.get_original(), value);
+            }
+            self.serialize_reply(&temp_doc, options)
         } else {
             let path = &paths[0];
-            if path.is_legacy() {
-                Ok(self
-                    .serialize_object(
-                        self.get_first(&paths[0].get_path())?,
-                        indent,
-                        newline,
-                        space,
-                    )
-                    .into())
+            if path.is_legacy() && options.ndjson {
+                self.array_to_lines(self.get_first(path.get_path())?, options)
+            } else if path.is_legacy() {
+                let value = self.first_or_default(path.get_path(), options)?;
+                self.serialize_reply(&value, options)
             } else {
-                let values = self.get_values(path.get_path())?;
-                Ok(self
-                    .serialize_object(&values, indent, newline, space)
-                    .into())
+                let mut values = self.get_found(path.get_path(), options)?;
+                if values.is_empty() && options.require {
+                    return Err(path_not_found(path));
+                }
+                if values.is_empty() {
+                    values.extend(options.default_value().map(Found::Default));
+                }
+                if options.ndjson {
+                    // The matches are written one per line instead of as an array
+                    return Ok(self.serialize_lines(values.into_iter(), options));
+                }
+                self.serialize_reply(&values, options)
             }
         }
     }
@@ -234,17 +563,16 @@ impl<'a, V: SelectValue> KeyValue<'a, V> {
                 })])
             } else {
                 // Adding somewhere in existing object, use jsonpath_lib::replace_with
-                let mut selector = Selector::default();
-                if let Err(e) = selector.str_path(
+                let node = cache::compile(
                     &parsed_static_path
                         .static_path_elements
                         .iter()
                         .map(|e| e.to_string())
                         .collect::<Vec<String>>()
                         .join(""),
-                ) {
-                    return Err(e.into());
-                }
+                )?;
+                let mut selector = Selector::default();
+                selector.compiled_path(&node);
                 selector.value(self.val);
                 let mut res = selector.select_with_paths(|_| true)?;
                 Ok(res
@@ -261,7 +589,9 @@ impl<'a, V: SelectValue> KeyValue<'a, V> {
             // if we reach here with array path we must be out of range
             // otherwise the path would be valid to be set and we would not
             // have reached here!!
-            Err("array index out of range".into())
+            Err(Error::IndexOutOfBounds(
+                "array index out of range".to_string(),
+            ))
         } else {
             Err("path not an object or array".into())
         }
@@ -273,9 +603,10 @@ impl<'a, V: SelectValue> KeyValue<'a, V> {
         option: &SetOptions,
     ) -> Result<Vec<UpdateInfo>, Error> {
         if SetOptions::NotExists != *option {
+            let node = cache::compile(path)?;
             let mut selector = Selector::default();
             let mut res = selector
-                .str_path(path)?
+                .compiled_path(&node)
                 .value(self.val)
                 .select_with_paths(|_| true)?;
             if !res.is_empty() {
@@ -296,6 +627,7 @@ impl<'a, V: SelectValue> KeyValue<'a, V> {
         let res = match format {
             Format::JSON => serde_json::to_string(results)?,
             Format::BSON => return Err("Soon to come...".into()), //results.into() as Bson,
+            Format::RESP => return Err("ERR FORMAT RESP is only supported by JSON.SET".into()),
         };
         Ok(res)
     }
@@ -310,6 +642,19 @@ impl<'a, V: SelectValue> KeyValue<'a, V> {
         Ok(s.to_string())
     }
 
+    /// The code JSON.TYPE NUMERIC replies for the type of `value`, these codes never change
+    pub fn value_code(value: &V) -> i64 {
+        match value.get_type() {
+            SelectValueType::Null => 0,
+            SelectValueType::Bool => 1,
+            SelectValueType::Long => 2,
+            SelectValueType::Double => 3,
+            SelectValueType::String => 4,
+            SelectValueType::Object => 5,
+            SelectValueType::Array => 6,
+        }
+    }
+
     pub fn value_name(value: &V) -> &str {
         match value.get_type() {
             SelectValueType::Null => "null",
@@ -322,28 +667,46 @@ impl<'a, V: SelectValue> KeyValue<'a, V> {
         }
     }
 
-    pub fn str_len(&self, path: &str) -> Result<usize, Error> {
-        let first = self.get_first(path)?;
-        match first.get_type() {
-            SelectValueType::String => Ok(first.get_str().len()),
-            _ => Err("ERR wrong type of path value".into()),
+    /// The length of each value at `path`, as a single integer when the path matches one value,
+    /// otherwise as an array with null for the values `len` does not apply to. These are native
+    /// RESP3 types as well, so the reply needs no conversion when the client uses RESP3
+    fn len_of<F: Fn(&V) -> Option<usize>>(&self, path: &str, len: F) -> Result<RedisValue, Error> {
+        let values = self.get_values(path)?;
+        match values.as_slice() {
+            [] => Err(Error::path_not_found()),
+            [value] => Ok(RedisValue::Integer(
+                len(value).ok_or_else(Error::wrong_type)? as i64,
+            )),
+            values => Ok(RedisValue::Array(
+                values
+                    .iter()
+                    .map(|v| len(v).map_or(RedisValue::Null, |l| RedisValue::Integer(l as i64)))
+                    .collect(),
+            )),
         }
     }
 
-    pub fn arr_len(&self, path: &str) -> Result<usize, Error> {
-        let first = self.get_first(path)?;
-        match first.get_type() {
-            SelectValueType::Array => Ok(first.len().unwrap()),
-            _ => Err("ERR wrong type of path value".into()),
-        }
+    /// Length of the matched strings, in bytes with `bytes` and in Unicode scalar values otherwise
+    pub fn str_len(&self, path: &str, bytes: bool) -> Result<RedisValue, Error> {
+        self.len_of(path, |v| match v.get_type() {
+            SelectValueType::String if bytes => Some(v.get_str().len()),
+            SelectValueType::String => Some(v.get_str().chars().count()),
+            _ => None,
+        })
     }
 
-    pub fn obj_len(&self, path: &str) -> Result<usize, Error> {
-        let first = self.get_first(path)?;
-        match first.get_type() {
-            SelectValueType::Object => Ok(first.len().unwrap()),
-            _ => Err("ERR wrong type of path value".into()),
-        }
+    pub fn arr_len(&self, path: &str) -> Result<RedisValue, Error> {
+        self.len_of(path, |v| match v.get_type() {
+            SelectValueType::Array => v.len(),
+            _ => None,
+        })
+    }
+
+    pub fn obj_len(&self, path: &str) -> Result<RedisValue, Error> {
+        self.len_of(path, |v| match v.get_type() {
+            SelectValueType::Object => v.len(),
+            _ => None,
+        })
     }
 
     pub fn is_eqaul<T1: SelectValue, T2: SelectValue>(&self, a: &T1, b: &T2) -> bool {
@@ -388,6 +751,35 @@ impl<'a, V: SelectValue> KeyValue<'a, V> {
         }
     }
 
+    /// The slice of an array of `len` elements searched by JSON.ARRINDEX, None when it is empty
+    fn arr_index_range(len: i64, start: i64, end: i64) -> Option<Range<i64>> {
+        // end=-1/0 means INFINITY to support backward with RedisJSON
+        if len == 0 || end < -1 {
+            return None;
+        }
+
+        // Normalize start
+        let start = if start < 0 {
+            0.max(len + start)
+        } else {
+            // start >= 0
+            start.min(len - 1)
+        };
+
+        // Normalize end
+        let end = match end {
+            0 => len,
+            e if e < 0 => len + end,
+            _ => end.min(len),
+        };
+
+        if end < start {
+            // don't search at all
+            return None;
+        }
+        Some(start..end)
+    }
+
     pub fn arr_index(
         &self,
         path: &str,
@@ -397,35 +789,14 @@ impl<'a, V: SelectValue> KeyValue<'a, V> {
     ) -> Result<i64, Error> {
         let res = self.get_first(path)?;
         if res.get_type() == SelectValueType::Array {
-            // end=-1/0 means INFINITY to support backward with RedisJSON
-            if res.len().unwrap() == 0 || end < -1 {
-                return Ok(-1);
-            }
-            let v: Value = serde_json::from_str(scalar_json)?;
-
-            let len = res.len().unwrap() as i64;
-
-            // Normalize start
-            let start = if start < 0 {
-                0.max(len + start)
-            } else {
-                // start >= 0
-                start.min(len - 1)
-            };
-
-            // Normalize end
-            let end = match end {
-                0 => len,
-                e if e < 0 => len + end,
-                _ => end.min(len),
+            let range = match Self::arr_index_range(res.len().unwrap() as i64, start, end) {
+                Some(range) => range,
+                None => return Ok(-1),
             };
+            let v: Value = serde_json::from_str(scalar_json)?;
 
-            if end < start {
-                // don't search at all
-                return Ok(-1);
-            }
             let mut i = -1;
-            for index in start..end {
+            for index in range {
                 if self.is_eqaul(res.get_index(index as usize).unwrap(), &v) {
                     i = index;
                     break;
@@ -438,10 +809,334 @@ impl<'a, V: SelectValue> KeyValue<'a, V> {
         }
     }
 
-    pub fn obj_keys(&self, path: &str) -> Result<Box<dyn Iterator<Item = &'_ str> + '_>, Error> {
-        self.get_first(path)?
-            .keys()
-            .ok_or_else(|| "ERR wrong type of path value".into())
+    /// All the positions of `scalar_json` in the array at `path`, searched like `arr_index`
+    pub fn arr_index_all(
+        &self,
+        path: &str,
+        scalar_json: &str,
+        start: i64,
+        end: i64,
+    ) -> Result<Vec<i64>, Error> {
+        let res = self.get_first(path)?;
+        if res.get_type() != SelectValueType::Array {
+            return Err(Error::wrong_type());
+        }
+        let range = match Self::arr_index_range(res.len().unwrap() as i64, start, end) {
+            Some(range) => range,
+            None => return Ok(vec![]),
+        };
+        let v: Value = serde_json::from_str(scalar_json)?;
+
+        Ok(range
+            .filter(|&index| self.is_eqaul(res.get_index(index as usize).unwrap(), &v))
+            .collect())
+    }
+
+    pub fn arr_slice(
+        &self,
+        path: &str,
+        start: i64,
+        stop: i64,
+        step: usize,
+    ) -> Result<Vec<&'_ V>, Error> {
+        let first = self.get_first(path)?;
+        match first.get_type() {
+            SelectValueType::Array => {
+                let len = first.len().unwrap() as i64;
+                let start = start.normalize_bound(len);
+                let stop = stop.normalize_bound(len);
+                Ok(first
+                    .values()
+                    .unwrap()
+                    .take(stop)
+                    .skip(start)
+                    .step_by(step)
+                    .collect())
+            }
+            _ => Err(Error::wrong_type()),
+        }
+    }
+
+    /// The keys of the object at `path`, or when the path matches several values an array with
+    /// the keys of each of them in document order, and null for the values that are not objects
+    pub fn obj_keys(&self, path: &str) -> Result<RedisValue, Error> {
+        let keys_of = |v: &V| {
+            v.keys()
+                .map(|keys| RedisValue::Array(keys.map(RedisValue::from).collect()))
+        };
+        let values = self.get_values(path)?;
+        match values.as_slice() {
+            [] => Err(Error::path_not_found()),
+            [value] => keys_of(value).ok_or_else(Error::wrong_type),
+            values => Ok(RedisValue::Array(
+                values
+                    .iter()
+                    .map(|v| keys_of(v).unwrap_or(RedisValue::Null))
+                    .collect(),
+            )),
+        }
+    }
+
+    pub fn obj_values(&self, path: &str) -> Result<Box<dyn Iterator<Item = &'_ V> + '_>, Error> {
+        let first = self.get_first(path)?;
+        match first.get_type() {
+            SelectValueType::Object => Ok(first.values().unwrap()),
+            _ => Err(Error::wrong_type()),
+        }
+    }
+
+    pub fn obj_entries(
+        &self,
+        path: &str,
+    ) -> Result<Box<dyn Iterator<Item = (&'_ str, &'_ V)> + '_>, Error> {
+        self.get_first(path)?.items().ok_or_else(Error::wrong_type)
+    }
+}
+
+fn path_not_found(path: &Path) -> Error {
+    Error::PathNotFound(format!("ERR path '{}' does not exist", path.get_original()))
+}
+
+/// Output options shared by `JSON.GET` and `JSON.MGET`
+struct GetOptions<'a> {
+    format: Format,
+    indent: Option<&'a str>,
+    newline: Option<&'a str>,
+    space: Option<&'a str>,
+    sort_keys: bool,
+    // Paths that match nothing are an error instead of null
+    require: bool,
+    // Significant digits of the floats written
+    precision: Option<usize>,
+    num_format: NumFormat,
+    // Wraps the reply in an object with this single member
+    root_name: Option<&'a str>,
+    // (offset, count) of the elements returned when a match is an array
+    limit: Option<(usize, usize)>,
+    // Gzip the serialized reply
+    compress: bool,
+    // FORMAT NDJSON, the elements of the reply are written one per line
+    ndjson: bool,
+    // Replied for the paths that match nothing, instead of null
+    default: Option<Value>,
+    // Values removed from the document before it is replied
+    exclude: Vec<Path<'a>>,
+}
+
+/// A value matched by a path, the result of the function a path ends with, or the DEFAULT of
+/// a path that matches nothing
+enum Found<'v, V: SelectValue> {
+    Value(SortedKeys<'v, V>),
+    Computed(Value),
+    Default(SortedKeys<'v, Value>),
+}
+
+impl<V: SelectValue> Serialize for Found<'_, V> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Found::Value(v) => v.serialize(serializer),
+            Found::Computed(v) => v.serialize(serializer),
+            Found::Default(v) => v.serialize(serializer),
+        }
+    }
+}
+
+impl<'a> GetOptions<'a> {
+    fn default_value(&self) -> Option<SortedKeys<'_, Value>> {
+        self.default
+            .as_ref()
+            .map(|v| SortedKeys::new(v, self.sort_keys))
+    }
+
+    /// A copy of `doc` without the values matching the EXCLUDE paths, the stored document is left
+    /// as is. A path that matches nothing removes nothing
+    fn excluded<V: SelectValue>(&self, doc: &V) -> Result<Value, RedisError> {
+        let mut res = serde_json::to_value(doc)?;
+        let mut paths: Vec<Vec<String>> = Vec::new();
+        for path in &self.exclude {
+            for p in find_paths(path.get_path(), &res, |_| true)? {
+                if !paths.contains(&p) {
+                    paths.push(p);
+                }
+            }
+        }
+        for p in sort_paths_for_removal(paths) {
+            let (last, parent) = p
+                .split_last()
+                .ok_or(RedisError::Str("ERR EXCLUDE can not remove the root"))?;
+            let parent = parent.iter().try_fold(&mut res, |v, token| match v {
+                Value::Object(obj) => obj.get_mut(token),
+                Value::Array(arr) => token.parse().ok().and_then(move |i: usize| arr.get_mut(i)),
+                _ => None,
+            });
+            match parent {
+                Some(Value::Object(obj)) => {
+                    // Shifting the members after it, so the copy keeps the order of the document
+                    obj.shift_remove(last);
+                }
+                Some(Value::Array(arr)) => {
+                    if let Ok(index) = last.parse::<usize>() {
+                        arr.remove(index);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(res)
+    }
+
+    /// Prepares a matched value to be serialized with these options
+    fn wrap<'v, V: SelectValue>(&self, value: &'v V) -> SortedKeys<'v, V> {
+        SortedKeys::new(value, self.sort_keys).limit(self.limit)
+    }
+
+    fn is_option(arg: &str) -> bool {
+        arg.len() <= JSONGET_SUBCOMMANDS_MAXSTRLEN
+            && JSONGET_SUBCOMMANDS
+                .iter()
+                .any(|opt| arg.eq_ignore_ascii_case(opt))
+    }
+
+    /// Consumes the remaining arguments, anything that is not an option is added to `paths`
+    fn parse<I: Iterator<Item = RedisString>>(
+        args: &mut I,
+        paths: &mut Vec<Path<'a>>,
+    ) -> Result<Self, RedisError> {
+        let mut options = GetOptions {
+            format: Format::JSON,
+            indent: None,
+            newline: None,
+            space: None,
+            sort_keys: !config::preserve_order(),
+            require: false,
+            precision: None,
+            num_format: NumFormat::Auto,
+            root_name: None,
+            limit: None,
+            compress: false,
+            ndjson: false,
+            default: None,
+            exclude: Vec::new(),
+        };
+        let mut pretty = false;
+        let (mut indent, mut indent_size) = (false, false);
+        while let Ok(arg) = args.next_str() {
+            match arg {
+                // fast way to consider arg a path by using the max length of all possible subcommands
+                // See #390 for the comparison of this function with/without this optimization
+                arg if arg.len() > JSONGET_SUBCOMMANDS_MAXSTRLEN => paths.push(Path::new(arg)),
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_INDENT) => {
+                    options.indent = Some(args.next_str()?);
+                    indent = true
+                }
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_INDENTSIZE) => {
+                    let size = args.next_u64()? as usize;
+                    if size > config::MAX_INDENT_SIZE {
+                        return Err(RedisError::Str("ERR INDENTSIZE must be between 0 and 10"));
+                    }
+                    options.indent = Some(&INDENT_SPACES[..size]);
+                    indent_size = true
+                }
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_NEWLINE) => {
+                    options.newline = Some(args.next_str()?)
+                }
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_SPACE) => {
+                    options.space = Some(args.next_str()?)
+                }
+                // serde_json only escapes '"', '\' and control characters, so non-ASCII characters are
+                // always written literally, which is what NOESCAPE asks for. Compatibility with ReJSON
+                // v1.0 which escapes them by default. See #168
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_NOESCAPE) => continue,
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_FORMAT) => match args.next_str()? {
+                    // Only a way of serializing JSON, so it is not a value Format
+                    f if f.eq_ignore_ascii_case(FORMAT_NDJSON) => {
+                        options.format = Format::JSON;
+                        options.ndjson = true
+                    }
+                    f => {
+                        options.format = Format::from_str(f)?;
+                        options.ndjson = false
+                    }
+                },
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_SORTKEYS) => options.sort_keys = true,
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_PRETTY) => pretty = true,
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_REQUIRE) => options.require = true,
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_PRECISION) => {
+                    options.precision = match args.next_u64()? {
+                        // An f64 has at most 17 significant decimal digits
+                        p @ 1..=17 => Some(p as usize),
+                        _ => return Err(RedisError::Str("ERR PRECISION must be between 1 and 17")),
+                    }
+                }
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_NUMFORMAT) => {
+                    options.num_format = match args.next_str()? {
+                        f if f.eq_ignore_ascii_case("AUTO") => NumFormat::Auto,
+                        f if f.eq_ignore_ascii_case("FIXED") => NumFormat::Fixed,
+                        f if f.eq_ignore_ascii_case("SCI") => NumFormat::Scientific,
+                        _ => {
+                            return Err(RedisError::Str("ERR NUMFORMAT must be FIXED, SCI or AUTO"))
+                        }
+                    }
+                }
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_ROOTNAME) => {
+                    options.root_name = Some(args.next_str()?)
+                }
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_COMPRESS) => {
+                    options.compress = parse_compression(args.next_str()?)?
+                }
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_DEFAULT) => {
+                    // Parsed once, however many paths it is replied for
+                    let default = RedisJSON::parse_input(args.next_str()?).map_err(|e| {
+                        RedisError::String(format!("ERR failed to parse DEFAULT: {}", e))
+                    })?;
+                    options.default = Some(default)
+                }
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_EXCLUDE) => {
+                    let path = Path::new(args.next_str()?);
+                    cache::compile(path.get_path())?;
+                    options.exclude.push(path)
+                }
+                arg if arg.eq_ignore_ascii_case(CMD_ARG_LIMIT) => {
+                    let offset = args.next_u64()? as usize;
+                    options.limit = Some((offset, args.next_u64()? as usize))
+                }
+                _ => paths.push(Path::new(arg)),
+            };
+        }
+        // Given together, one of them would be ignored
+        if indent && indent_size {
+            return Err(RedisError::Str(
+                "ERR INDENT can not be combined with INDENTSIZE",
+            ));
+        }
+        if pretty {
+            options.indent = options.indent.or(Some(PRETTY_INDENT));
+            options.newline = options.newline.or(Some(PRETTY_NEWLINE));
+            options.space = options.space.or(Some(PRETTY_SPACE));
+        }
+        // Any formatting option given replaces the DEFAULT_INDENT_SIZE formatting as a whole
+        let default_indent = config::default_indent_size();
+        if default_indent > 0
+            && !options.ndjson
+            && options.indent.is_none()
+            && options.newline.is_none()
+            && options.space.is_none()
+        {
+            options.indent = Some(&INDENT_SPACES[..default_indent]);
+            options.newline = Some(PRETTY_NEWLINE);
+            options.space = Some(PRETTY_SPACE);
+        }
+        if options.require && options.default.is_some() {
+            return Err(RedisError::Str(
+                "ERR DEFAULT can not be combined with REQUIRE",
+            ));
+        }
+        if options.ndjson && options.newline.map_or(false, |n| !n.is_empty()) {
+            return Err(RedisError::Str(
+                "ERR FORMAT NDJSON can not be combined with NEWLINE or PRETTY",
+            ));
+        }
+        Ok(options)
     }
 }
 
@@ -455,26 +1150,7 @@ pub fn command_json_get<M: Manager>(
 
     // Set Capcity to 1 assumiung the common case has one path
     let mut paths: Vec<Path> = Vec::with_capacity(1);
-    let mut format = Format::JSON;
-    let mut indent = None;
-    let mut space = None;
-    let mut newline = None;
-    while let Ok(arg) = args.next_str() {
-        match arg {
-            // fast way to consider arg a path by using the max length of all possible subcommands
-            // See #390 for the comparison of this function with/without this optimization
-            arg if arg.len() > JSONGET_SUBCOMMANDS_MAXSTRLEN => paths.push(Path::new(arg)),
-            arg if arg.eq_ignore_ascii_case(CMD_ARG_INDENT) => indent = Some(args.next_str()?),
-            arg if arg.eq_ignore_ascii_case(CMD_ARG_NEWLINE) => newline = Some(args.next_str()?),
-            arg if arg.eq_ignore_ascii_case(CMD_ARG_SPACE) => space = Some(args.next_str()?),
-            // Silently ignore. Compatibility with ReJSON v1.0 which has this option. See #168 TODO add support
-            arg if arg.eq_ignore_ascii_case(CMD_ARG_NOESCAPE) => continue,
-            arg if arg.eq_ignore_ascii_case(CMD_ARG_FORMAT) => {
-                format = Format::from_str(args.next_str()?)?
-            }
-            _ => paths.push(Path::new(arg)),
-        };
-    }
+    let options = GetOptions::parse(&mut args, &mut paths)?;
 
     // path is optional -> no path found we use root "$"
     if paths.is_empty() {
@@ -483,11 +1159,39 @@ pub fn command_json_get<M: Manager>(
 
     let key = manager.open_key_read(ctx, &key)?;
     let value = match key.get_value()? {
-        Some(doc) => KeyValue::new(doc).to_json(&mut paths, indent, newline, space, format)?,
+        Some(doc) if !options.exclude.is_empty() => {
+            KeyValue::new(&options.excluded(doc)?).to_json(&mut paths, &options)?
+        }
+        Some(doc) => KeyValue::new(doc).to_json(&mut paths, &options)?,
         None => RedisValue::Null,
     };
 
-    Ok(value)
+    Ok(if options.compress {
+        compress_reply(value)
+    } else {
+        reply_serialized(ctx, value)
+    })
+}
+
+/// Parses the algorithm of the COMPRESS option, only gzip is supported
+fn parse_compression(arg: &str) -> Result<bool, RedisError> {
+    if arg.eq_ignore_ascii_case("GZIP") {
+        Ok(true)
+    } else {
+        Err(RedisError::Str("ERR COMPRESS must be GZIP"))
+    }
+}
+
+fn compress_reply(value: RedisValue) -> RedisValue {
+    match value {
+        RedisValue::BulkString(s) => RedisValue::StringBuffer(compress::gzip(s.as_bytes())),
+        RedisValue::StringBuffer(b) => {
+            let res = compress::gzip(&b);
+            release_serialize_buffer(b);
+            RedisValue::StringBuffer(res)
+        }
+        value => value,
+    }
 }
 
 pub fn command_json_set<M: Manager>(
@@ -499,79 +1203,344 @@ pub fn command_json_set<M: Manager>(
 
     let key = args.next_arg()?;
     let path = Path::new(args.next_str()?);
-    let value = args.next_str()?;
+    let input = args.next_arg()?;
 
     let mut format = Format::JSON;
     let mut set_option = SetOptions::None;
+    let mut create_path = false;
+    let mut compare = None;
+    let mut get = false;
+    let mut compressed = false;
 
     while let Some(s) = args.next() {
         match s.try_as_str()? {
-            arg if arg.eq_ignore_ascii_case("NX") && set_option == SetOptions::None => {
+            arg if arg.eq_ignore_ascii_case("NX")
+                && set_option == SetOptions::None
+                && compare.is_none() =>
+            {
                 set_option = SetOptions::NotExists
             }
             arg if arg.eq_ignore_ascii_case("XX") && set_option == SetOptions::None => {
                 set_option = SetOptions::AlreadyExists
             }
+            // NX never replaces a value, so there is nothing to compare with
+            arg if arg.eq_ignore_ascii_case(CMD_ARG_GT)
+                && compare.is_none()
+                && set_option != SetOptions::NotExists =>
+            {
+                compare = Some(Ordering::Greater)
+            }
+            arg if arg.eq_ignore_ascii_case(CMD_ARG_LT)
+                && compare.is_none()
+                && set_option != SetOptions::NotExists =>
+            {
+                compare = Some(Ordering::Less)
+            }
             arg if arg.eq_ignore_ascii_case("FORMAT") => {
                 format = Format::from_str(args.next_str()?)?;
             }
+            arg if arg.eq_ignore_ascii_case(CMD_ARG_CREATEPATH) => create_path = true,
+            arg if arg.eq_ignore_ascii_case(CMD_ARG_GET) => get = true,
+            // The document of an existing key is replaced where it is, even at the root, so its
+            // TTL is always kept. KEEPTTL is accepted for clients that send it like in SET
+            arg if arg.eq_ignore_ascii_case(CMD_ARG_KEEPTTL) => {}
+            arg if arg.eq_ignore_ascii_case(CMD_ARG_COMPRESS) => {
+                compressed = parse_compression(args.next_str()?)?
+            }
             _ => return Err(RedisError::Str("ERR syntax error")),
         };
     }
+    let decompressed;
+    let value = if compressed {
+        decompressed = compress::gunzip(input.as_slice(), config::max_doc_size())?;
+        decompressed.as_bytes()
+    } else if format == Format::BSON {
+        // Binary, so it is not expected to be valid UTF-8
+        input.as_slice()
+    } else {
+        json_arg(&input)
+            .map_err(|e| arg_parse_error(e, 3, &path))?
+            .as_bytes()
+    };
 
+    // Checked before parsing, so an oversized value is never built in memory. The size of the
+    // document is checked once it is parsed, where the value is set
+    if format == Format::JSON {
+        check_input_size(std::str::from_utf8(value).unwrap_or_default())
+            .map_err(|e| arg_parse_error(e, 3, &path))?;
+    }
     let mut redis_key = manager.open_key_write(ctx, key)?;
-    let current = redis_key.get_value()?;
+    let val = manager
+        .from_bytes(value, format)
+        .map_err(|e| arg_parse_error(e, 3, &path))?;
+    // The previous value is replied whether or not the conditions allow setting the new one
+    let previous = match (get, redis_key.get_value()?) {
+        // The previous value is replied as JSON when setting from the RESP form
+        (true, Some(root)) if format == Format::RESP => {
+            Some(previous_value(root, &path, Format::JSON)?)
+        }
+        (true, Some(root)) => Some(previous_value(root, &path, format)?),
+        (true, None) => Some(RedisValue::Null),
+        (false, _) => None,
+    };
+    // Subscribers get a distinct event when the key is created rather than updated
+    let new_key = redis_key.get_value()?.is_none();
+    let event = if new_key { "json.create" } else { "json.set" };
+    // With XX the value is only replaced, so its parents already exist
+    let mut created = None;
+    if create_path && set_option != SetOptions::AlreadyExists && path.get_path() != JSON_ROOT_PATH {
+        if new_key {
+            redis_key.set_value(Vec::new(), manager.from_str("{}", Format::JSON)?)?;
+        }
+        match create_parents(&manager, &mut redis_key, &path) {
+            Ok(member) => created = member,
+            Err(e) => {
+                if new_key {
+                    redis_key.delete()?;
+                }
+                return Err(e);
+            }
+        }
+    }
 
-    let val = manager.from_str(value, format)?;
+    let res = match compare {
+        Some(ordering) => set_if_ordered::<M>(ctx, &mut redis_key, &path, value, &val, ordering),
+        None => Ok(None),
+    };
+    let res = match res {
+        Ok(Some(res)) => Ok(res),
+        Ok(None) => set_path_value::<M>(ctx, &mut redis_key, &path, val, set_option, event),
+        Err(e) => Err(e),
+    };
+    match res {
+        Ok(res) => Ok(previous.unwrap_or(res)),
+        Err(e) => {
+            // The parents are only created for the value, so they are removed with it
+            if new_key {
+                redis_key.delete()?;
+            } else if let Some(member) = created {
+                redis_key.delete_path(member)?;
+            }
+            Err(e)
+        }
+    }
+}
 
-    match (current, set_option) {
+/// Replaces the numbers at `path` that `value` is ordered after as `ordering`, returning `None`
+/// when nothing matches the path so the value is added as with no condition
+fn set_if_ordered<M: Manager>(
+    ctx: &Context,
+    redis_key: &mut M::WriteHolder,
+    path: &Path,
+    value: &[u8],
+    val: &M::O,
+    ordering: Ordering,
+) -> Result<Option<RedisValue>, RedisError> {
+    let number = match serde_json::from_slice(value) {
+        Ok(Value::Number(n)) => n,
+        _ => return Err(RedisError::Str("ERR GT and LT require a number")),
+    };
+    let root = match redis_key.get_value()? {
+        Some(root) => root,
+        None => return Ok(None),
+    };
+    let mut all_numbers = true;
+    let mut matched = false;
+    let paths = find_paths(path.get_path(), root, |v| {
+        matched = true;
+        match v.get_type() {
+            SelectValueType::Long | SelectValueType::Double => {
+                compare_number(&number, v) == Some(ordering)
+            }
+            _ => {
+                all_numbers = false;
+                false
+            }
+        }
+    })?;
+    if !all_numbers {
+        return Err(RedisError::Str(
+            "ERR GT and LT require the current value to be a number",
+        ));
+    }
+    if !matched {
+        return Ok(None);
+    }
+    if paths.is_empty() {
+        return Ok(Some(RedisValue::Null));
+    }
+    for p in paths {
+        redis_key.set_value(p, val.clone())?;
+    }
+    redis_key.apply_changes_at(ctx, "json.set", path.get_path())?;
+    Ok(Some(RedisValue::SimpleStringStatic("OK")))
+}
+
+/// Encodes a value replied by JSON.GET in a FORMAT that JSON.SET accepts back
+fn encode_reply(value: Value, format: Format) -> Result<RedisValue, Error> {
+    match format {
+        Format::JSON => Ok(serde_json::to_string(&value)?.into()),
+        // A BSON document can not be an array or a scalar, so the value is its single member
+        Format::BSON => {
+            let mut doc = bson::Document::new();
+            doc.insert(BSON_VALUE_KEY, bson::Bson::from(value));
+            let mut res = Vec::new();
+            bson::encode_document(&mut res, &doc)
+                .map_err(|e| Error::Custom(format!("ERR failed to encode BSON: {}", e)))?;
+            Ok(RedisValue::StringBuffer(res))
+        }
+        Format::RESP => Ok(resp::serialize(&value).into()),
+    }
+}
+
+/// The value at `path` before `JSON.SET ... GET` replaces it, as `JSON.GET` would reply it
+fn previous_value<V: SelectValue>(
+    root: &V,
+    path: &Path,
+    format: Format,
+) -> Result<RedisValue, Error> {
+    let doc = KeyValue::new(root);
+    let values = doc.get_values(path.get_path())?;
+    match values.first() {
+        None => Ok(RedisValue::Null),
+        Some(v) if path.is_legacy() => encode_reply(serde_json::to_value(*v)?, format),
+        Some(_) => encode_reply(serde_json::to_value(&values)?, format),
+    }
+}
+
+/// Orders `number` relative to the number `current`, integers are compared exactly
+fn compare_number<V: SelectValue>(number: &Number, current: &V) -> Option<Ordering> {
+    match (number.as_i64(), current.get_type()) {
+        (Some(n), SelectValueType::Long) => Some(n.cmp(&current.get_long())),
+        (_, SelectValueType::Long) => number.as_f64()?.partial_cmp(&(current.get_long() as f64)),
+        _ => number.as_f64()?.partial_cmp(&current.get_double()),
+    }
+}
+
+/// Tells which argument of the command failed to parse, `index` is its position after the
+/// command name
+fn arg_parse_error(e: Error, index: usize, path: &Path) -> RedisError {
+    match e {
+        Error::ParseError(msg) => RedisError::String(format!(
+            "ERR failed to parse argument {} for path '{}': {}",
+            index,
+            path.get_original(),
+            msg
+        )),
+        e => e.into(),
+    }
+}
+
+/// The text of an argument holding a JSON value. Bytes that are not valid UTF-8 are an error
+/// rather than replaced, so a value is never stored other than it was sent
+fn json_arg(arg: &RedisString) -> Result<&str, Error> {
+    arg.try_as_str()
+        .map_err(|_| Error::ParseError("invalid UTF-8".to_string()))
+}
+
+/// Rejects a value larger than the MAX_INPUT_SIZE configuration, or than MAX_DOC_SIZE when it is
+/// smaller, before it is parsed, so an oversized value is never built in memory. The size of the
+/// document is checked when it is written, see `KeyHolderWrite::check_size`
+///
+/// The nesting depth needs no such check: the parsers fail once arrays and objects are nested
+/// more than 127 levels deep, long before the stack could overflow.
+fn check_input_size(json: &str) -> Result<(), Error> {
+    let limit = match (config::max_input_size(), config::max_doc_size()) {
+        (0, limit) | (limit, 0) => limit,
+        (input, doc) => input.min(doc),
+    };
+    if limit > 0 && json.len() > limit {
+        return Err(Error::Custom(format!(
+            "ERR input size of {} bytes exceeds the limit of {} bytes",
+            json.len(),
+            limit
+        )));
+    }
+    Ok(())
+}
+
+/// Same as `json_arg`, for a value that is added to a document
+fn json_input(arg: &RedisString) -> Result<&str, Error> {
+    let json = json_arg(arg)?;
+    check_input_size(json)?;
+    Ok(json)
+}
+
+/// Same as `arg_parse_error`, for the zero-based `item` of a list of JSON values
+fn item_parse_error(e: Error, index: usize, item: usize, path: &Path) -> RedisError {
+    match e {
+        Error::ParseError(msg) => RedisError::String(format!(
+            "ERR failed to parse argument {} for path '{}' (item {}): {}",
+            index,
+            path.get_original(),
+            item,
+            msg
+        )),
+        e => e.into(),
+    }
+}
+
+/// Stores `val` at `path` the way `JSON.SET` does, notifying with `cmd`
+fn set_path_value<M: Manager>(
+    ctx: &Context,
+    redis_key: &mut M::WriteHolder,
+    path: &Path,
+    val: M::O,
+    set_option: SetOptions,
+    cmd: &str,
+) -> RedisResult {
+    if store_path_value::<M>(redis_key, path, val, set_option)? {
+        redis_key.apply_changes_at(ctx, cmd, path.get_path())?;
+        REDIS_OK
+    } else {
+        Ok(RedisValue::Null)
+    }
+}
+
+/// Same as `set_path_value` without notifying, returns whether `val` was stored
+fn store_path_value<M: Manager>(
+    redis_key: &mut M::WriteHolder,
+    path: &Path,
+    val: M::O,
+    set_option: SetOptions,
+) -> Result<bool, RedisError> {
+    let current = redis_key.get_value()?;
+
+    match (current, set_option) {
         (Some(ref mut doc), ref op) => {
             if path.get_path() == JSON_ROOT_PATH {
                 if *op != SetOptions::NotExists {
                     redis_key.set_value(Vec::new(), val)?;
-                    redis_key.apply_changes(ctx, "json.set")?;
-                    REDIS_OK
+                    Ok(true)
                 } else {
-                    Ok(RedisValue::Null)
+                    Ok(false)
                 }
             } else {
                 let mut update_info = KeyValue::new(*doc).find_paths(path.get_path(), op)?;
-                if !update_info.is_empty() {
-                    let mut res = false;
-                    if update_info.len() == 1 {
-                        res = match update_info.pop().unwrap() {
-                            UpdateInfo::SUI(sui) => redis_key.set_value(sui.path, val)?,
-                            UpdateInfo::AUI(aui) => redis_key.dict_add(aui.path, &aui.key, val)?,
-                        }
-                    } else {
-                        for ui in update_info {
-                            res = match ui {
-                                UpdateInfo::SUI(sui) => {
-                                    redis_key.set_value(sui.path, val.clone())?
-                                }
-                                UpdateInfo::AUI(aui) => {
-                                    redis_key.dict_add(aui.path, &aui.key, val.clone())?
-                                }
+                let mut res = false;
+                if update_info.len() == 1 {
+                    res = match update_info.pop().unwrap() {
+                        UpdateInfo::SUI(sui) => redis_key.set_value(sui.path, val)?,
+                        UpdateInfo::AUI(aui) => redis_key.dict_add(aui.path, &aui.key, val)?,
+                    }
+                } else {
+                    for ui in update_info {
+                        res = match ui {
+                            UpdateInfo::SUI(sui) => redis_key.set_value(sui.path, val.clone())?,
+                            UpdateInfo::AUI(aui) => {
+                                redis_key.dict_add(aui.path, &aui.key, val.clone())?
                             }
                         }
                     }
-                    if res {
-                        redis_key.apply_changes(ctx, "json.set")?;
-                        REDIS_OK
-                    } else {
-                        Ok(RedisValue::Null)
-                    }
-                } else {
-                    Ok(RedisValue::Null)
                 }
+                Ok(res)
             }
         }
-        (None, SetOptions::AlreadyExists) => Ok(RedisValue::Null),
+        (None, SetOptions::AlreadyExists) => Ok(false),
         (None, _) => {
             if path.get_path() == JSON_ROOT_PATH {
                 redis_key.set_value(Vec::new(), val)?;
-                redis_key.apply_changes(ctx, "json.set")?;
-                REDIS_OK
+                Ok(true)
             } else {
                 Err(RedisError::Str(
                     "ERR new objects must be created at the root",
@@ -581,17 +1550,154 @@ pub fn command_json_set<M: Manager>(
     }
 }
 
+/// Adds an empty object for every missing parent of the static `path`, returning the path of the
+/// member that holds them, if any, to remove them again when the value can not be set
+///
+/// Nothing is added unless all the missing parents can be, so a failure leaves the value as is.
+fn create_parents<M: Manager>(
+    manager: &M,
+    redis_key: &mut M::WriteHolder,
+    path: &Path,
+) -> Result<Option<Vec<String>>, RedisError> {
+    let parsed_static_path =
+        StaticPathParser::check(path.get_path()).map_err(RedisError::String)?;
+    if parsed_static_path.valid != VisitStatus::Valid {
+        return Err(RedisError::Str(
+            "ERR parents can only be created for a static path",
+        ));
+    }
+    let elements = parsed_static_path.static_path_elements;
+    let parent_len = elements.len().saturating_sub(1);
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+
+    // Find the deepest parent that exists, everything below it is created
+    let mut parent = String::new();
+    let mut missing = parent_len;
+    for (i, element) in elements[..parent_len].iter().enumerate() {
+        let prefix = format!("{}{}", parent, element);
+        if find_paths(&prefix, root, |_| true)?.is_empty() {
+            missing = i;
+            break;
+        }
+        parent = prefix;
+    }
+    if missing == parent_len {
+        return Ok(None);
+    }
+
+    let mut keys = Vec::new();
+    let mut prefix = parent.clone();
+    for element in &elements[missing..parent_len] {
+        prefix.push_str(&element.to_string());
+        match element {
+            StaticPathElement::ObjectKey(key) => keys.push(key),
+            _ => {
+                return Err(RedisError::String(format!(
+                    "ERR cannot create array element '{}'",
+                    prefix
+                )))
+            }
+        }
+    }
+    let mut parents = find_paths(&parent, root, |v| v.get_type() == SelectValueType::Object)?;
+    let parent_path = parents.pop().ok_or_else(|| {
+        RedisError::String(format!(
+            "ERR cannot create '{}{}' in a non-object",
+            parent, elements[missing]
+        ))
+    })?;
+
+    // The missing parents below the first one are added to it as nested objects
+    let nested = keys[1..]
+        .iter()
+        .rev()
+        .fold(Value::Object(Map::new()), |value, key| {
+            let mut parent = Map::new();
+            parent.insert(key.to_string(), value);
+            Value::Object(parent)
+        });
+    let nested = manager.from_str(&serde_json::to_string(&nested)?, Format::JSON)?;
+    redis_key.dict_add(parent_path.clone(), keys[0], nested)?;
+    let mut member = parent_path;
+    member.push(keys[0].to_string());
+    Ok(Some(member))
+}
+
+/// Adds an empty array at the static `path` where it is a missing member of an object, returning
+/// the paths of the added arrays
+fn create_arrays<M: Manager>(
+    manager: &M,
+    redis_key: &mut M::WriteHolder,
+    path: &Path,
+) -> Result<Vec<Vec<String>>, RedisError> {
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    let update_info = KeyValue::new(root).find_paths(path.get_path(), &SetOptions::NotExists)?;
+    let mut created = Vec::new();
+    for ui in update_info {
+        if let UpdateInfo::AUI(mut aui) = ui {
+            let array = manager.from_str("[]", Format::JSON)?;
+            if redis_key.dict_add(aui.path.clone(), &aui.key, array)? {
+                aui.path.push(aui.key);
+                created.push(aui.path);
+            }
+        }
+    }
+    Ok(created)
+}
+
 fn find_paths<T: SelectValue, F: FnMut(&T) -> bool>(
     path: &str,
     doc: &T,
     f: F,
 ) -> Result<Vec<Vec<String>>, RedisError> {
+    let node = cache::compile(path)?;
     Ok(Selector::default()
-        .str_path(path)?
+        .compiled_path(&node)
         .value(doc)
         .select_with_paths(f)?)
 }
 
+/// Drops the paths nested in other paths and orders the rest so that removing them one by one
+/// does not shift the array indices of the paths that are yet to be removed
+/// Drops the paths nested in another one of `paths`, for the commands where changing a value
+/// also changes everything below it
+fn remove_nested_paths(paths: Vec<Vec<String>>) -> Vec<Vec<String>> {
+    let nested: Vec<bool> = paths
+        .iter()
+        .map(|p| {
+            paths
+                .iter()
+                .any(|other| other.len() < p.len() && p.starts_with(other))
+        })
+        .collect();
+    paths
+        .into_iter()
+        .zip(nested)
+        .filter_map(|(p, nested)| if nested { None } else { Some(p) })
+        .collect()
+}
+
+fn sort_paths_for_removal(paths: Vec<Vec<String>>) -> Vec<Vec<String>> {
+    // Paths nested in another matched path are removed along with it
+    let mut paths = remove_nested_paths(paths);
+    paths.sort_by(|a, b| {
+        a.iter()
+            .zip(b.iter())
+            .map(|(a, b)| match (a.parse::<usize>(), b.parse::<usize>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                _ => a.cmp(b),
+            })
+            .find(|o| *o != std::cmp::Ordering::Equal)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .reverse()
+    });
+    paths
+}
+
 pub fn command_json_del<M: Manager>(
     manager: M,
     ctx: &Context,
@@ -600,28 +1706,40 @@ pub fn command_json_del<M: Manager>(
     let mut args = args.into_iter().skip(1);
 
     let key = args.next_arg()?;
-    let path = match args.next() {
-        None => Path::new(JSON_ROOT_PATH),
-        Some(s) => Path::new(s.try_as_str()?),
-    };
+    let mut paths = Vec::with_capacity(1);
+    for arg in args {
+        paths.push(Path::new(arg.try_as_str()?));
+    }
+    if paths.is_empty() {
+        paths.push(Path::new(JSON_ROOT_PATH));
+    }
 
     let mut redis_key = manager.open_key_write(ctx, key)?;
     let deleted = match redis_key.get_value()? {
         Some(doc) => {
-            let res = if path.get_path() == JSON_ROOT_PATH {
+            let res = if paths.iter().any(|p| p.get_path() == JSON_ROOT_PATH) {
                 redis_key.delete()?;
                 1
             } else {
-                let paths = find_paths(path.get_path(), doc, |_| true)?;
+                let mut matches: Vec<Vec<String>> = Vec::new();
+                for path in &paths {
+                    for p in find_paths(path.get_path(), doc, |_| true)? {
+                        if !matches.contains(&p) {
+                            matches.push(p);
+                        }
+                    }
+                }
                 let mut changed = 0;
-                for p in paths {
+                for p in sort_paths_for_removal(matches) {
                     if redis_key.delete_path(p)? {
                         changed += 1;
                     }
                 }
                 changed
             };
-            if res > 0 {
+            if res > 0 && paths.len() == 1 {
+                redis_key.apply_changes_at(ctx, "json.del", paths[0].get_path())?;
+            } else if res > 0 {
                 redis_key.apply_changes(ctx, "json.del")?;
             }
             res
@@ -640,24 +1758,87 @@ pub fn command_json_mget<M: Manager>(
         return Err(RedisError::WrongArity);
     }
 
-    args.last().ok_or(RedisError::WrongArity).and_then(|path| {
-        let path = Path::new(path.try_as_str()?);
-        let keys = &args[1..args.len() - 1];
+    // The keys are followed by a path, the first option (if any) ends the list and can be
+    // followed by additional paths, same as in JSON.GET. There is no separator, so a key named
+    // like an option is taken as that option unless it is one of the first two arguments
+    let options_pos = args
+        .iter()
+        .skip(3)
+        .position(|arg| arg.try_as_str().map_or(false, GetOptions::is_option))
+        .map_or(args.len(), |pos| pos + 3);
+
+    let mut args = args.into_iter().skip(1);
+    let keys: Vec<RedisString> = args.by_ref().take(options_pos - 2).collect();
+    let mut paths = vec![Path::new(args.next_str()?)];
+    let options = GetOptions::parse(&mut args, &mut paths)?;
+
+    // An invalid path is an error even when none of the keys exist. A function is not part of
+    // the path the values are selected with
+    for path in &paths {
+        let values = PathFunction::split(path.get_path()).map_or(path.get_path(), |(v, _)| v);
+        cache::compile(values).map_err(Error::from)?;
+    }
 
-        let results: Result<Vec<RedisValue>, RedisError> = keys
-            .iter()
-            .map(|key| {
-                manager
-                    .open_key_read(ctx, key)?
-                    .get_value()?
-                    .map(|doc| KeyValue::new(doc).to_string(path.get_path(), Format::JSON))
-                    .transpose()
-                    .map_or_else(|_| Ok(RedisValue::Null), |v| Ok(v.into()))
+    let results: Result<Vec<RedisValue>, RedisError> = keys
+        .iter()
+        .map(|key| {
+            let key = manager.open_key_read(ctx, key)?;
+            let value = match key.get_value()? {
+                Some(doc) if !options.exclude.is_empty() => {
+                    let doc = options.excluded(doc)?;
+                    mget_value(&KeyValue::new(&doc), &paths, &options)?
+                }
+                Some(doc) => mget_value(&KeyValue::new(doc), &paths, &options)?,
+                None => RedisValue::Null,
+            };
+            Ok(if options.compress {
+                compress_reply(value)
+            } else {
+                value
             })
-            .collect();
+        })
+        .collect();
+
+    // Each value is replied from the buffer it was serialized into
+    let results = results?;
+    raw::reply_with_array(ctx.ctx, results.len() as c_long);
+    for value in results {
+        ctx.reply(Ok(reply_serialized(ctx, value)));
+    }
+    Ok(RedisValue::NoReply)
+}
 
-        Ok(results?.into())
-    })
+/// The reply of JSON.MGET for an existing key
+///
+/// A path that matches nothing is null, like a missing key, unless REQUIRE is given. Any other
+/// error is the error of the command, so it is not mistaken for a missing key.
+fn mget_value<V: SelectValue>(
+    doc: &KeyValue<V>,
+    paths: &[Path],
+    options: &GetOptions,
+) -> Result<RedisValue, Error> {
+    match mget_paths(doc, paths, options) {
+        Err(Error::PathNotFound(_)) if !options.require => Ok(RedisValue::Null),
+        res => res,
+    }
+}
+
+fn mget_paths<V: SelectValue>(
+    doc: &KeyValue<V>,
+    paths: &[Path],
+    options: &GetOptions,
+) -> Result<RedisValue, Error> {
+    if paths.len() > 1 {
+        let mut paths: Vec<Path> = paths.iter().map(|p| Path::new(p.get_original())).collect();
+        doc.to_json(&mut paths, options)
+    } else {
+        // A single path replies with its first match regardless of the path syntax
+        if options.ndjson {
+            return doc.array_to_lines(doc.get_first(paths[0].get_path())?, options);
+        }
+        let value = doc.first_or_default(paths[0].get_path(), options)?;
+        doc.serialize_reply(&value, options)
+    }
 }
 
 pub fn command_json_type<M: Manager>(
@@ -667,17 +1848,40 @@ pub fn command_json_type<M: Manager>(
 ) -> RedisResult {
     let mut args = args.into_iter().skip(1);
     let key = args.next_arg()?;
-    let path = Path::new(args.next_str()?);
+    let mut path = None;
+    let mut numeric = false;
+    while let Ok(arg) = args.next_str() {
+        match arg {
+            arg if arg.eq_ignore_ascii_case(CMD_ARG_NUMERIC) && !numeric => numeric = true,
+            arg if path.is_none() && !numeric => path = Some(Path::new(arg)),
+            _ => return Err(RedisError::Str("ERR syntax error")),
+        }
+    }
+    let path = path.unwrap_or_else(|| Path::new(JSON_ROOT_PATH));
 
     let key = manager.open_key_read(ctx, &key)?;
 
-    let value = key.get_value()?.map_or_else(
-        || RedisValue::Null,
-        |doc| match KeyValue::new(doc).get_type(path.get_path()) {
-            Ok(s) => s.into(),
+    let type_of = |v| -> RedisValue {
+        if numeric {
+            KeyValue::value_code(v).into()
+        } else {
+            KeyValue::value_name(v).into()
+        }
+    };
+    let value = match key.get_value()? {
+        // Legacy paths reply the type of the first match only
+        Some(doc) if path.is_legacy() => match KeyValue::new(doc).get_first(path.get_path()) {
+            Ok(v) => type_of(v),
             Err(_) => RedisValue::Null,
         },
-    );
+        Some(doc) => KeyValue::new(doc)
+            .get_values(path.get_path())?
+            .into_iter()
+            .map(type_of)
+            .collect::<Vec<RedisValue>>()
+            .into(),
+        None => RedisValue::Null,
+    };
 
     Ok(value)
 }
@@ -686,6 +1890,10 @@ enum NumOp {
     Incr,
     Mult,
     Pow,
+    Div,
+    Mod,
+    Min,
+    Max,
 }
 
 fn command_json_num_op<M>(
@@ -702,7 +1910,11 @@ where
 
     let key = args.next_arg()?;
     let path = Path::new(args.next_str()?);
-    let number = args.next_str()?;
+    let number = match serde_json::from_str(args.next_str()?) {
+        Ok(Value::Number(n)) => n,
+        Ok(_) => return Err(RedisError::Str("bad input number")),
+        Err(e) => return Err(arg_parse_error(e.into(), 3, &path)),
+    };
 
     let mut redis_key = manager.open_key_write(ctx, key)?;
 
@@ -715,14 +1927,29 @@ where
     if !paths.is_empty() {
         let mut res = None;
         for p in paths {
-            res = Some(match op {
-                NumOp::Incr => redis_key.incr_by(p, number)?,
-                NumOp::Mult => redis_key.mult_by(p, number)?,
-                NumOp::Pow => redis_key.pow_by(p, number)?,
-            });
+            let num_res = match op {
+                NumOp::Incr => redis_key.incr_by(p, &number),
+                NumOp::Mult => redis_key.mult_by(p, &number),
+                NumOp::Pow => redis_key.pow_by(p, &number),
+                NumOp::Div => redis_key.div_by(p, &number),
+                NumOp::Mod => redis_key.mod_by(p, &number),
+                NumOp::Min => redis_key.min_by(p, &number),
+                NumOp::Max => redis_key.max_by(p, &number),
+            };
+            match num_res {
+                Ok(n) => res = Some(n),
+                Err(e) => {
+                    // A result that is not a valid number can show up after other matches are
+                    // changed already. They are still replicated, so the replicas end up the same
+                    if res.is_some() {
+                        redis_key.apply_changes_at(ctx, cmd, path.get_path())?;
+                    }
+                    return Err(e);
+                }
+            }
         }
-        redis_key.apply_changes(ctx, cmd)?;
-        Ok(res.unwrap().to_string().into())
+        redis_key.apply_changes_at(ctx, cmd, path.get_path())?;
+        Ok(formatter::format_number(&res.unwrap()).into())
     } else {
         Err(RedisError::String(format!(
             "Path '{}' does not exist or does not contains a number",
@@ -755,6 +1982,38 @@ pub fn command_json_num_powby<M: Manager>(
     command_json_num_op(manager, ctx, args, "json.numpowby", NumOp::Pow)
 }
 
+pub fn command_json_num_divby<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    command_json_num_op(manager, ctx, args, "json.numdivby", NumOp::Div)
+}
+
+pub fn command_json_num_modby<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    command_json_num_op(manager, ctx, args, "json.nummodby", NumOp::Mod)
+}
+
+pub fn command_json_num_minby<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    command_json_num_op(manager, ctx, args, "json.numminby", NumOp::Min)
+}
+
+pub fn command_json_num_maxby<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    command_json_num_op(manager, ctx, args, "json.nummaxby", NumOp::Max)
+}
+
 pub fn command_json_bool_toggle<M: Manager>(
     manager: M,
     ctx: &Context,
@@ -768,25 +2027,129 @@ pub fn command_json_bool_toggle<M: Manager>(
     let root = redis_key
         .get_value()?
         .ok_or_else(RedisError::nonexistent_key)?;
-    let paths = find_paths(path.get_path(), root, |v| {
+    let matches = find_paths(path.get_path(), root, |_| true)?;
+    let mut bools = find_paths(path.get_path(), root, |v| {
         v.get_type() == SelectValueType::Bool
     })?;
-    if !paths.is_empty() {
+    if matches.len() <= 1 {
+        // A single match replies with a scalar
+        return match bools.pop() {
+            Some(p) => {
+                let res = redis_key.bool_toggle(p)?;
+                redis_key.apply_changes_at(ctx, "json.toggle", path.get_path())?;
+                reply_bools(ctx, &[Some(res)], false)
+            }
+            None => Err(RedisError::String(format!(
+                "Path '{}' does not exist or not a bool",
+                path
+            ))),
+        };
+    }
+
+    // Toggle every boolean match, other matches are replied as null
+    let toggled: HashSet<&Vec<String>> = bools.iter().collect();
+    let mut res = Vec::with_capacity(matches.len());
+    for p in matches {
+        res.push(if toggled.contains(&p) {
+            Some(redis_key.bool_toggle(p)?)
+        } else {
+            None
+        });
+    }
+    if !bools.is_empty() {
+        redis_key.apply_changes_at(ctx, "json.toggle", path.get_path())?;
+    }
+    reply_bools(ctx, &res, true)
+}
+
+pub fn command_json_str_append<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let path_or_json = args.next_str()?;
+
+    // path is optional, but is always given along with several strings
+    let mut strings = args
+        .map(|arg| arg.try_as_str())
+        .collect::<Result<Vec<&str>, RedisError>>()?;
+    // BYTES is not a valid JSON string, so it can only be the option
+    let bytes = strings
+        .last()
+        .map_or(false, |s| s.eq_ignore_ascii_case(CMD_ARG_BYTES));
+    if bytes {
+        strings.pop();
+    }
+    let path = if strings.is_empty() {
+        strings.push(path_or_json);
+        Path::new(JSON_ROOT_PATH)
+    } else {
+        Path::new(path_or_json)
+    };
+    let json = if strings.len() == 1 {
+        strings[0].to_string()
+    } else {
+        let mut joined = String::new();
+        for (i, s) in strings.into_iter().enumerate() {
+            match serde_json::from_str(s).map_err(|e| arg_parse_error(e.into(), i + 3, &path))? {
+                Value::String(s) => joined.push_str(&s),
+                _ => {
+                    return Err(RedisError::String(format!(
+                        "ERR wrong type of value - expected string but found {}",
+                        s
+                    )))
+                }
+            }
+        }
+        serde_json::to_string(&Value::String(joined))?
+    };
+
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+
+    let matches = find_paths(path.get_path(), root, |_| true)?;
+    let paths = find_paths(path.get_path(), root, |v| {
+        v.get_type() == SelectValueType::String
+    })?;
+    if matches.len() > 1 {
+        // Each match gets its new length, or null when it is not a string
+        let strings: HashSet<&Vec<String>> = paths.iter().collect();
+        let mut res = Vec::with_capacity(matches.len());
+        for m in matches {
+            if strings.contains(&m) {
+                res.push(RedisValue::Integer(
+                    redis_key.str_append(m, json.clone(), bytes)? as i64,
+                ));
+            } else {
+                res.push(RedisValue::Null);
+            }
+        }
+        if !paths.is_empty() {
+            redis_key.apply_changes_at(ctx, "json.strappend", path.get_path())?;
+        }
+        Ok(res.into())
+    } else if !paths.is_empty() {
         let mut res = None;
         for p in paths {
-            res = Some(redis_key.bool_toggle(p)?);
+            res = Some(redis_key.str_append(p, json.to_string(), bytes)?);
         }
-        redis_key.apply_changes(ctx, "json.toggle")?;
-        Ok(res.unwrap().to_string().into())
+        redis_key.apply_changes_at(ctx, "json.strappend", path.get_path())?;
+        Ok(res.unwrap().into())
     } else {
         Err(RedisError::String(format!(
-            "Path '{}' does not exist or not a bool",
+            "Path '{}' does not exist or not a string",
             path
         )))
     }
 }
 
-pub fn command_json_str_append<M: Manager>(
+pub fn command_json_str_replace<M: Manager>(
     manager: M,
     ctx: &Context,
     args: Vec<RedisString>,
@@ -794,18 +2157,25 @@ pub fn command_json_str_append<M: Manager>(
     let mut args = args.into_iter().skip(1);
 
     let key = args.next_arg()?;
-    let path_or_json = args.next_str()?;
-
-    let path;
-    let json;
+    let path = Path::new(args.next_str()?);
+    let search = args.next_str()?;
+    let replacement = args.next_str()?;
 
-    // path is optional
-    if let Ok(val) = args.next_arg() {
-        path = Path::new(path_or_json);
-        json = val.try_as_str()?;
-    } else {
-        path = Path::new(JSON_ROOT_PATH);
-        json = path_or_json;
+    let mut count = None;
+    while let Some(s) = args.next() {
+        match s.try_as_str()? {
+            arg if arg.eq_ignore_ascii_case("COUNT") && count.is_none() => {
+                let n = args.next_i64()?;
+                if n < 0 {
+                    return Err(RedisError::Str("ERR COUNT must not be negative"));
+                }
+                count = Some(n as usize);
+            }
+            _ => return Err(RedisError::Str("ERR syntax error")),
+        };
+    }
+    if search.is_empty() {
+        return Err(RedisError::Str("ERR search string must not be empty"));
     }
 
     let mut redis_key = manager.open_key_write(ctx, key)?;
@@ -818,12 +2188,18 @@ pub fn command_json_str_append<M: Manager>(
         v.get_type() == SelectValueType::String
     })?;
     if !paths.is_empty() {
-        let mut res = None;
+        let mut res = 0;
+        let mut replaced = 0;
         for p in paths {
-            res = Some(redis_key.str_append(p, json.to_string())?);
+            let (len, n) = redis_key.str_replace(p, search, replacement, count)?;
+            res = len;
+            replaced += n;
         }
-        redis_key.apply_changes(ctx, "json.strappend")?;
-        Ok(res.unwrap().into())
+        // Nothing is notified nor replicated when no string changed
+        if replaced > 0 {
+            redis_key.apply_changes_at(ctx, "json.strreplace", path.get_path())?;
+        }
+        Ok(res.into())
     } else {
         Err(RedisError::String(format!(
             "Path '{}' does not exist or not a string",
@@ -839,13 +2215,23 @@ pub fn command_json_str_len<M: Manager>(
 ) -> RedisResult {
     let mut args = args.into_iter().skip(1);
     let key = args.next_arg()?;
-    let path = Path::new(args.next_str()?);
+    let mut path = None;
+    let mut bytes = false;
+    for arg in args {
+        let arg = arg.try_as_str()?;
+        if arg.eq_ignore_ascii_case(CMD_ARG_BYTES) {
+            bytes = true;
+        } else if path.is_none() {
+            path = Some(Path::new(arg));
+        } else {
+            return Err(RedisError::WrongArity);
+        }
+    }
+    let path = path.unwrap_or_else(|| Path::new(JSON_ROOT_PATH));
 
     let key = manager.open_key_read(ctx, &key)?;
     match key.get_value()? {
-        Some(doc) => Ok(RedisValue::Integer(
-            KeyValue::new(doc).str_len(path.get_path())? as i64,
-        )),
+        Some(doc) => Ok(KeyValue::new(doc).str_len(path.get_path(), bytes)?),
         None => Ok(RedisValue::Null),
     }
 }
@@ -860,10 +2246,49 @@ pub fn command_json_arr_append<M: Manager>(
     let key = args.next_arg()?;
     let path = Path::new(args.next_str()?);
 
+    // CREATE is not valid JSON, so it can not be mistaken for the first item
+    let create = match args.peek() {
+        Some(arg) if arg.to_string_lossy().eq_ignore_ascii_case(CMD_ARG_CREATE) => {
+            args.next();
+            true
+        }
+        _ => false,
+    };
+    let first_item = if create { 4 } else { 3 };
+
     // We require at least one JSON item to append
     args.peek().ok_or(RedisError::WrongArity)?;
     let args = args
-        .map(|json| manager.from_str(&json.to_string_lossy(), Format::JSON))
+        .enumerate()
+        .map(|(i, json)| {
+            json_input(&json)
+                .and_then(|json| manager.from_str(json, Format::JSON))
+                .map_err(|e| item_parse_error(e, first_item + i, i, &path))
+        })
+        .collect::<Result<_, _>>()?;
+
+    append_to_arrays(manager, ctx, key, &path, args, create, "json.arrappend")
+}
+
+pub fn command_json_arr_append_unique<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1).peekable();
+
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+
+    // We require at least one JSON item to append
+    args.peek().ok_or(RedisError::WrongArity)?;
+    let items: Vec<M::O> = args
+        .enumerate()
+        .map(|(i, json)| {
+            json_input(&json)
+                .and_then(|json| manager.from_str(json, Format::JSON))
+                .map_err(|e| item_parse_error(e, 3 + i, i, &path))
+        })
         .collect::<Result<_, _>>()?;
 
     let mut redis_key = manager.open_key_write(ctx, key)?;
@@ -871,24 +2296,76 @@ pub fn command_json_arr_append<M: Manager>(
         .get_value()?
         .ok_or_else(RedisError::nonexistent_key)?;
 
-    let mut paths = find_paths(path.get_path(), root, |v| {
+    let mut matches = find_paths(path.get_path(), root, |_| true)?;
+    let arrays = find_paths(path.get_path(), root, |v| {
         v.get_type() == SelectValueType::Array
     })?;
-    if paths.is_empty() {
+    if matches.is_empty() {
         Err(RedisError::String(format!(
             "Path '{}' does not exist",
             path
         )))
-    } else if paths.len() == 1 {
-        let res = redis_key.arr_append(paths.pop().unwrap(), args)?;
-        redis_key.apply_changes(ctx, "json.arrappend")?;
+    } else if matches.len() == 1 {
+        // A value that is not an array is reported by the update
+        let res = redis_key.arr_append_unique(matches.pop().unwrap(), items)?;
+        redis_key.apply_changes_at(ctx, "json.arrappendunique", path.get_path())?;
         Ok(res.into())
     } else {
-        let mut res = None;
+        // Each match gets its new length, or null when it is not an array
+        let targets: HashSet<&Vec<String>> = arrays.iter().collect();
+        let mut res = Vec::with_capacity(matches.len());
+        for m in matches {
+            if targets.contains(&m) {
+                res.push(RedisValue::Integer(
+                    redis_key.arr_append_unique(m, items.clone())? as i64,
+                ));
+            } else {
+                res.push(RedisValue::Null);
+            }
+        }
+        if !arrays.is_empty() {
+            redis_key.apply_changes_at(ctx, "json.arrappendunique", path.get_path())?;
+        }
+        Ok(res.into())
+    }
+}
+
+/// Appends `items` to the arrays matching `path`, creating them first with `create`
+fn append_to_arrays<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    key: RedisString,
+    path: &Path,
+    items: Vec<M::O>,
+    create: bool,
+    command: &str,
+) -> RedisResult {
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+
+    let mut paths = find_paths(path.get_path(), root, |v| {
+        v.get_type() == SelectValueType::Array
+    })?;
+    if paths.is_empty() && create {
+        paths = create_arrays(&manager, &mut redis_key, path)?;
+    }
+    if paths.is_empty() {
+        Err(RedisError::String(format!(
+            "Path '{}' does not exist",
+            path
+        )))
+    } else if paths.len() == 1 {
+        let res = redis_key.arr_append(paths.pop().unwrap(), items)?;
+        redis_key.apply_changes_at(ctx, command, path.get_path())?;
+        Ok(res.into())
+    } else {
+        let mut res = None;
         for p in paths {
-            res = Some(redis_key.arr_append(p, args.clone())?);
+            res = Some(redis_key.arr_append(p, items.clone())?);
         }
-        redis_key.apply_changes(ctx, "json.arrappend")?;
+        redis_key.apply_changes_at(ctx, command, path.get_path())?;
         Ok(res.unwrap().into())
     }
 }
@@ -902,7 +2379,7 @@ pub fn command_json_arr_index<M: Manager>(
 
     let key = args.next_arg()?;
     let path = Path::new(args.next_str()?);
-    let json_scalar = args.next_str()?;
+    let json_value = args.next_str()?;
     let start: i64 = args.next().map(|v| v.parse_integer()).unwrap_or(Ok(0))?;
     let end: i64 = args.next().map(|v| v.parse_integer()).unwrap_or(Ok(0))?;
 
@@ -911,12 +2388,40 @@ pub fn command_json_arr_index<M: Manager>(
     let key = manager.open_key_read(ctx, &key)?;
 
     let index = key.get_value()?.map_or(Ok(-1), |doc| {
-        KeyValue::new(doc).arr_index(path.get_path(), json_scalar, start, end)
+        KeyValue::new(doc).arr_index(path.get_path(), json_value, start, end)
     })?;
 
     Ok(index.into())
 }
 
+pub fn command_json_arr_index_all<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+    let json_value = args.next_str()?;
+    let start: i64 = args.next().map(|v| v.parse_integer()).unwrap_or(Ok(0))?;
+    let end: i64 = args.next().map(|v| v.parse_integer()).unwrap_or(Ok(0))?;
+
+    args.done()?;
+
+    let key = manager.open_key_read(ctx, &key)?;
+
+    match key.get_value()? {
+        Some(doc) => Ok(KeyValue::new(doc)
+            .arr_index_all(path.get_path(), json_value, start, end)?
+            .into_iter()
+            .map(RedisValue::Integer)
+            .collect::<Vec<_>>()
+            .into()),
+        None => Ok(RedisValue::Null),
+    }
+}
+
 pub fn command_json_arr_insert<M: Manager>(
     manager: M,
     ctx: &Context,
@@ -931,7 +2436,12 @@ pub fn command_json_arr_insert<M: Manager>(
     // We require at least one JSON item to append
     args.peek().ok_or(RedisError::WrongArity)?;
     let args = args
-        .map(|json| manager.from_str(&json.to_string_lossy(), Format::JSON))
+        .enumerate()
+        .map(|(i, json)| {
+            json_input(&json)
+                .and_then(|json| manager.from_str(json, Format::JSON))
+                .map_err(|e| item_parse_error(e, 4 + i, i, &path))
+        })
         .collect::<Result<_, _>>()?;
 
     let mut redis_key = manager.open_key_write(ctx, key)?;
@@ -948,7 +2458,7 @@ pub fn command_json_arr_insert<M: Manager>(
         for p in paths {
             res = Some(redis_key.arr_insert(p, &args, index)?);
         }
-        redis_key.apply_changes(ctx, "json.arrinsert")?;
+        redis_key.apply_changes_at(ctx, "json.arrinsert", path.get_path())?;
         Ok(res.unwrap().into())
     } else {
         Err(RedisError::String(format!(
@@ -969,9 +2479,7 @@ pub fn command_json_arr_len<M: Manager>(
 
     let key = manager.open_key_read(ctx, &key)?;
     match key.get_value()? {
-        Some(doc) => Ok(RedisValue::Integer(
-            KeyValue::new(doc).arr_len(path.get_path())? as i64,
-        )),
+        Some(doc) => Ok(KeyValue::new(doc).arr_len(path.get_path())?),
         None => Ok(RedisValue::Null),
     }
 }
@@ -986,10 +2494,10 @@ pub fn command_json_arr_pop<M: Manager>(
     let key = args.next_arg()?;
 
     let (path, index) = match args.next() {
-        None => (Path::new(JSON_ROOT_PATH), i64::MAX),
+        None => (Path::new(JSON_ROOT_PATH), -1),
         Some(s) => {
             let path = Path::new(s.try_as_str()?);
-            let index = args.next_i64().unwrap_or(-1);
+            let index: i64 = args.next().map(|v| v.parse_integer()).unwrap_or(Ok(-1))?;
             (path, index)
         }
     };
@@ -1004,17 +2512,37 @@ pub fn command_json_arr_pop<M: Manager>(
         v.get_type() == SelectValueType::Array
     })?;
     if !paths.is_empty() {
+        // Every index is checked before anything is popped, so an index out of range in one of
+        // the arrays leaves all of them as they are
+        for p in &paths {
+            let len = pointer::resolve(root, p).and_then(|v| v.len()).unwrap_or(0) as i64;
+            let i = if index < 0 { len + index } else { index };
+            if len > 0 && !(0..len).contains(&i) {
+                return Err(RedisError::Str("ERR index out of range"));
+            }
+        }
         let mut res = None;
+        let mut popped = false;
         for p in paths {
-            res = Some(redis_key.arr_pop(p, index)?);
-        }
-        match res.unwrap() {
-            Some(r) => {
-                redis_key.apply_changes(ctx, "json.arrpop")?;
-                Ok(r.into())
+            match redis_key.arr_pop(p, index) {
+                Ok(r) => {
+                    popped |= r.is_some();
+                    res = r;
+                }
+                Err(e) => {
+                    // Popping from an array can move the arrays nested in it. What was popped
+                    // until then is still replicated, so the replicas end up the same
+                    if popped {
+                        redis_key.apply_changes_at(ctx, "json.arrpop", path.get_path())?;
+                    }
+                    return Err(e);
+                }
             }
-            None => Ok(().into()),
         }
+        if popped {
+            redis_key.apply_changes_at(ctx, "json.arrpop", path.get_path())?;
+        }
+        Ok(res.into())
     } else {
         Err(RedisError::String(format!(
             "Path '{}' does not exist or not an array",
@@ -1049,7 +2577,7 @@ pub fn command_json_arr_trim<M: Manager>(
         for p in paths {
             res = Some(redis_key.arr_trim(p, start, stop)?);
         }
-        redis_key.apply_changes(ctx, "json.arrtrim")?;
+        redis_key.apply_changes_at(ctx, "json.arrtrim", path.get_path())?;
         Ok(res.unwrap().into())
     } else {
         Err(RedisError::String(format!(
@@ -1059,21 +2587,82 @@ pub fn command_json_arr_trim<M: Manager>(
     }
 }
 
-pub fn command_json_obj_keys<M: Manager>(
+pub fn command_json_arr_slice<M: Manager>(
     manager: M,
     ctx: &Context,
     args: Vec<RedisString>,
 ) -> RedisResult {
     let mut args = args.into_iter().skip(1);
+
     let key = args.next_arg()?;
     let path = Path::new(args.next_str()?);
+    let start = args.next_i64()?;
+    let stop = args.next_i64()?;
+    let step = match args.next() {
+        None => 1,
+        Some(s) => match s.parse_integer()? {
+            0 => 1,
+            n if n > 0 => n as usize,
+            _ => return Err(RedisError::Str("ERR step must not be negative")),
+        },
+    };
+    args.done()?;
+
+    let key = manager.open_key_read(ctx, &key)?;
+
+    match key.get_value()? {
+        Some(doc) => {
+            let doc = KeyValue::new(doc);
+            let slice = doc.arr_slice(path.get_path(), start, stop, step)?;
+            Ok(serde_json::to_string(&slice)?.into())
+        }
+        None => Ok(RedisValue::Null),
+    }
+}
+
+pub fn command_json_obj_keys<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+    let key = args.next_arg()?;
+    let path = match args.next() {
+        None => Path::new(JSON_ROOT_PATH),
+        Some(s) => Path::new(s.try_as_str()?),
+    };
+    args.done()?;
+
+    let key = manager.open_key_read(ctx, &key)?;
+
+    let value = match key.get_value()? {
+        Some(doc) => KeyValue::new(doc).obj_keys(path.get_path())?,
+        None => RedisValue::Null,
+    };
+
+    Ok(value)
+}
+
+pub fn command_json_obj_values<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+    let key = args.next_arg()?;
+    let path = match args.next() {
+        None => Path::new(JSON_ROOT_PATH),
+        Some(s) => Path::new(s.try_as_str()?),
+    };
+    args.done()?;
 
     let key = manager.open_key_read(ctx, &key)?;
 
     let value = match key.get_value()? {
         Some(doc) => KeyValue::new(doc)
-            .obj_keys(path.get_path())?
-            .collect::<Vec<&str>>()
+            .obj_values(path.get_path())?
+            .map(|v| Ok(serde_json::to_string(v)?.into()))
+            .collect::<Result<Vec<RedisValue>, Error>>()?
             .into(),
         None => RedisValue::Null,
     };
@@ -1081,6 +2670,90 @@ pub fn command_json_obj_keys<M: Manager>(
     Ok(value)
 }
 
+pub fn command_json_obj_entries<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+    let key = args.next_arg()?;
+    let path = match args.next() {
+        None => Path::new(JSON_ROOT_PATH),
+        Some(s) => Path::new(s.try_as_str()?),
+    };
+    args.done()?;
+
+    let key = manager.open_key_read(ctx, &key)?;
+
+    let value = match key.get_value()? {
+        Some(doc) => KeyValue::new(doc)
+            .obj_entries(path.get_path())?
+            .map(|(k, v)| Ok(vec![RedisValue::from(k), serde_json::to_string(v)?.into()].into()))
+            .collect::<Result<Vec<RedisValue>, Error>>()?
+            .into(),
+        None => RedisValue::Null,
+    };
+
+    Ok(value)
+}
+
+pub fn command_json_entries<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+    // The position of the next member, members keep their position while the object is not
+    // changed so a cursor stays valid between calls
+    let cursor = args.next_u64()? as usize;
+
+    let mut count = None;
+    while let Some(s) = args.next() {
+        match s.try_as_str()? {
+            arg if arg.eq_ignore_ascii_case("COUNT") && count.is_none() => {
+                count = match args.next_u64()? {
+                    0 => return Err(RedisError::Str("ERR COUNT must be positive")),
+                    n => Some(n as usize),
+                }
+            }
+            _ => return Err(RedisError::Str("ERR syntax error")),
+        };
+    }
+    let count = count.unwrap_or(DEFAULT_ENTRIES_COUNT);
+
+    let key = manager.open_key_read(ctx, &key)?;
+    let doc = match key.get_value()? {
+        Some(doc) => KeyValue::new(doc),
+        None => return Ok(RedisValue::Null),
+    };
+    let obj = doc.get_first(path.get_path())?;
+    if obj.get_type() != SelectValueType::Object {
+        return Err(Error::wrong_type().into());
+    }
+    let len = obj.len().unwrap();
+    if cursor >= len {
+        // A stale cursor, e.g. after members were removed, ends the scan without going over them
+        return Ok(vec![RedisValue::from("0"), RedisValue::Array(Vec::new())].into());
+    }
+    // Objects have no indexed access, so the members before the cursor are still gone over, but
+    // only the members of the page are serialized
+    let entries = obj
+        .items()
+        .unwrap()
+        .skip(cursor)
+        .take(count)
+        .map(|(k, v)| Ok(vec![RedisValue::from(k), serde_json::to_string(v)?.into()].into()))
+        .collect::<Result<Vec<RedisValue>, Error>>()?;
+    // As SCAN, a cursor of 0 ends the iteration
+    let next = match cursor + entries.len() {
+        next if next < len => next,
+        _ => 0,
+    };
+    Ok(vec![RedisValue::from(next.to_string()), entries.into()].into())
+}
+
 pub fn command_json_obj_len<M: Manager>(
     manager: M,
     ctx: &Context,
@@ -1092,9 +2765,7 @@ pub fn command_json_obj_len<M: Manager>(
 
     let key = manager.open_key_read(ctx, &key)?;
     match key.get_value()? {
-        Some(doc) => Ok(RedisValue::Integer(
-            KeyValue::new(doc).obj_len(path.get_path())? as i64,
-        )),
+        Some(doc) => Ok(KeyValue::new(doc).obj_len(path.get_path())?),
         None => Ok(RedisValue::Null),
     }
 }
@@ -1106,11 +2777,16 @@ pub fn command_json_clear<M: Manager>(
 ) -> RedisResult {
     let mut args = args.into_iter().skip(1);
     let key = args.next_arg()?;
+    let mut strings = false;
     let paths = args.try_fold::<_, _, Result<Vec<Path>, RedisError>>(
         Vec::with_capacity(args.len()),
         |mut acc, arg| {
             let s = arg.try_as_str()?;
-            acc.push(Path::new(s));
+            if s.eq_ignore_ascii_case(CMD_ARG_STRINGS) {
+                strings = true;
+            } else {
+                acc.push(Path::new(s));
+            }
             Ok(acc)
         },
     )?;
@@ -1121,31 +2797,87 @@ pub fn command_json_clear<M: Manager>(
         paths
     };
 
-    let path = paths.first().unwrap().get_path();
-
-    // FIXME: handle multi paths
     let mut redis_key = manager.open_key_write(ctx, key)?;
 
     let root = redis_key
         .get_value()?
         .ok_or_else(RedisError::nonexistent_key)?;
 
-    let paths = find_paths(path, root, |_v| true)?;
-    if !paths.is_empty() {
-        let mut res = None;
-        for p in paths {
-            res = Some(redis_key.clear(p)?);
+    let mut matches: Vec<Vec<String>> = Vec::new();
+    for path in &paths {
+        for p in find_paths(path.get_path(), root, |_v| true)? {
+            if !matches.contains(&p) {
+                matches.push(p);
+            }
         }
-        redis_key.apply_changes(ctx, "json.clear")?;
-        Ok(res.unwrap().into())
+    }
+    if !matches.is_empty() {
+        // Clearing a value clears everything below it, so nested matches are not visited
+        let mut res = 0;
+        for p in remove_nested_paths(matches) {
+            res += redis_key.clear(p, strings)?;
+        }
+        if res > 0 {
+            redis_key.apply_changes(ctx, "json.clear")?;
+        }
+        Ok(res.into())
     } else {
         Err(RedisError::String(format!(
             "Path '{}' does not exist",
-            path
+            paths[0]
         )))
     }
 }
 
+/// The reply of JSON.MEMORY and JSON.DEBUG MEMORY, `args` start with the key
+fn memory_usage<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    mut args: impl Iterator<Item = RedisString>,
+) -> RedisResult {
+    let key = args.next_arg()?;
+    let paths = args
+        .map(|arg| arg.try_as_str().map(Path::new))
+        .collect::<Result<Vec<Path>, RedisError>>()?;
+
+    let key = manager.open_key_read(ctx, &key)?;
+    let doc = key.get_value()?;
+    if paths.len() <= 1 {
+        let path = paths
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| Path::new(JSON_ROOT_PATH));
+        let value = match doc {
+            Some(doc) => manager.get_memory(KeyValue::new(doc).get_first(path.get_path())?)?,
+            None => 0,
+        };
+        return Ok(value.into());
+    }
+
+    // Several paths reply the size of each one's first match, 0 when it matches nothing
+    let mut res = Vec::with_capacity(paths.len());
+    for path in &paths {
+        let value = match doc {
+            Some(doc) => match KeyValue::new(doc).get_first(path.get_path()) {
+                Ok(v) => manager.get_memory(v)?,
+                Err(Error::PathNotFound(_)) => 0,
+                Err(e) => return Err(e.into()),
+            },
+            None => 0,
+        };
+        res.push(RedisValue::Integer(value as i64));
+    }
+    Ok(res.into())
+}
+
+pub fn command_json_memory<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    memory_usage(manager, ctx, args.into_iter().skip(1))
+}
+
 pub fn command_json_debug<M: Manager>(
     manager: M,
     ctx: &Context,
@@ -1153,21 +2885,54 @@ pub fn command_json_debug<M: Manager>(
 ) -> RedisResult {
     let mut args = args.into_iter().skip(1);
     match args.next_str()?.to_uppercase().as_str() {
-        "MEMORY" => {
+        "MEMORY" => memory_usage(manager, ctx, args),
+        "KEYSPACE" => {
             let key = args.next_arg()?;
-            let path = Path::new(args.next_str()?);
+            let path = match args.next() {
+                None => Path::new(JSON_ROOT_PATH),
+                Some(s) => Path::new(s.try_as_str()?),
+            };
+            args.done()?;
 
             let key = manager.open_key_read(ctx, &key)?;
-            let value = match key.get_value()? {
-                Some(doc) => manager.get_memory(KeyValue::new(doc).get_first(path.get_path())?)?,
-                None => 0,
+            let shape = match key.get_value()? {
+                Some(doc) => manager.get_shape(KeyValue::new(doc).get_first(path.get_path())?),
+                None => return Ok(RedisValue::Null),
             };
-            Ok(value.into())
+            Ok(vec![
+                RedisValue::SimpleStringStatic("objects"),
+                (shape.objects as i64).into(),
+                RedisValue::SimpleStringStatic("arrays"),
+                (shape.arrays as i64).into(),
+                RedisValue::SimpleStringStatic("strings"),
+                (shape.strings as i64).into(),
+                RedisValue::SimpleStringStatic("numbers"),
+                (shape.numbers as i64).into(),
+                RedisValue::SimpleStringStatic("booleans"),
+                (shape.booleans as i64).into(),
+                RedisValue::SimpleStringStatic("nulls"),
+                (shape.nulls as i64).into(),
+                RedisValue::SimpleStringStatic("max_depth"),
+                (shape.max_depth as i64).into(),
+                RedisValue::SimpleStringStatic("leaves"),
+                (shape.leaves as i64).into(),
+            ]
+            .into())
+        }
+        "VERSION" => {
+            let key = args.next_arg()?;
+            args.done()?;
+            let key = manager.open_key_read(ctx, &key)?;
+            Ok(key
+                .get_encver()?
+                .map_or(RedisValue::Null, |v| (v as i64).into()))
         }
         "HELP" => {
             let results = vec![
-                "MEMORY <key> [path] - reports memory usage",
-                "HELP                - this message",
+                "MEMORY <key> [path ...] - reports memory usage",
+                "KEYSPACE <key> [path]   - reports the number of values of each type and the depth",
+                "VERSION <key>           - reports the encoding version the key was loaded from",
+                "HELP                    - this message",
             ];
             Ok(results.into())
         }
@@ -1185,30 +2950,845 @@ pub fn command_json_resp<M: Manager>(
     let mut args = args.into_iter().skip(1);
 
     let key = args.next_arg()?;
-    let path = match args.next() {
-        None => Path::new(JSON_ROOT_PATH),
-        Some(s) => Path::new(s.try_as_str()?),
+    let mut path = None;
+    let mut canonical = false;
+    let args: Vec<RedisString> = args.collect();
+    for arg in &args {
+        match arg.try_as_str()? {
+            arg if arg.eq_ignore_ascii_case(CMD_ARG_CANONICAL) => canonical = true,
+            arg if path.is_none() => path = Some(Path::new(arg)),
+            _ => return Err(RedisError::WrongArity),
+        }
+    }
+    let path = path.unwrap_or_else(|| Path::new(JSON_ROOT_PATH));
+
+    let key = manager.open_key_read(ctx, &key)?;
+    match key.get_value()? {
+        Some(doc) => {
+            let resp3 = is_resp3(ctx);
+            let value = KeyValue::new(doc).resp_serialize(path.get_path(), resp3, canonical)?;
+            match reply_with_map_api(ctx) {
+                Some(reply_with_map) if resp3 => {
+                    reply_resp3(ctx, value, reply_with_map, reply_with_bool_api(ctx));
+                    Ok(RedisValue::NoReply)
+                }
+                _ => Ok(value),
+            }
+        }
+        None => Ok(RedisValue::Null),
+    }
+}
+
+/// Replies `value`, as `resp_serialize` builds it for RESP3 clients, with its objects as RESP3
+/// maps of their keys to their values and its arrays as arrays of their elements, without the
+/// `[` marker. Booleans are RESP3 booleans when the server has `RedisModule_ReplyWithBool`
+fn reply_resp3(
+    ctx: &Context,
+    value: RedisValue,
+    reply_with_map: ReplyWithMap,
+    reply_with_bool: Option<ReplyWithBool>,
+) {
+    match value {
+        RedisValue::Array(items)
+            if matches!(items.first(), Some(RedisValue::SimpleStringStatic("{"))) =>
+        {
+            unsafe { reply_with_map(ctx.ctx, (items.len() / 2) as c_long) };
+            for item in items.into_iter().skip(1) {
+                reply_resp3(ctx, item, reply_with_map, reply_with_bool);
+            }
+        }
+        RedisValue::Array(items)
+            if matches!(items.first(), Some(RedisValue::SimpleStringStatic("["))) =>
+        {
+            raw::reply_with_array(ctx.ctx, (items.len() - 1) as c_long);
+            for item in items.into_iter().skip(1) {
+                reply_resp3(ctx, item, reply_with_map, reply_with_bool);
+            }
+        }
+        RedisValue::Array(items) => {
+            raw::reply_with_array(ctx.ctx, items.len() as c_long);
+            for item in items {
+                reply_resp3(ctx, item, reply_with_map, reply_with_bool);
+            }
+        }
+        // Simple strings are only booleans for RESP3 clients, floats are doubles
+        RedisValue::SimpleString(s) => match reply_with_bool {
+            Some(reply_with_bool) => unsafe {
+                reply_with_bool(ctx.ctx, (s == "true") as c_int);
+            },
+            None => {
+                ctx.reply(Ok(RedisValue::SimpleString(s)));
+            }
+        },
+        value => {
+            ctx.reply(Ok(value));
+        }
+    }
+}
+
+pub fn command_json_pointer<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let tokens = pointer::parse(args.next_str()?)?;
+    args.done()?;
+
+    let key = manager.open_key_read(ctx, &key)?;
+    let value = match key.get_value()? {
+        Some(doc) => match pointer::resolve(doc, &tokens) {
+            Some(v) => serde_json::to_string(v)?.into(),
+            None => RedisValue::Null,
+        },
+        None => RedisValue::Null,
     };
+    Ok(value)
+}
+
+pub fn command_json_flatten<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let mut path = None;
+    let mut separator = None;
+    while let Ok(arg) = args.next_str() {
+        match arg {
+            arg if arg.eq_ignore_ascii_case(CMD_ARG_SEPARATOR) && separator.is_none() => {
+                separator = Some(args.next_str()?)
+            }
+            arg if path.is_none() && separator.is_none() => path = Some(Path::new(arg)),
+            _ => return Err(RedisError::Str("ERR syntax error")),
+        }
+    }
+    let path = path.unwrap_or_else(|| Path::new(JSON_ROOT_PATH));
+    let separator = separator.unwrap_or(flatten::DEFAULT_SEPARATOR);
+    if separator.is_empty() {
+        return Err(RedisError::Str("ERR separator must not be empty"));
+    }
 
     let key = manager.open_key_read(ctx, &key)?;
     match key.get_value()? {
-        Some(doc) => KeyValue::new(doc).resp_serialize(path.get_path()),
+        Some(doc) => {
+            let doc = KeyValue::new(doc);
+            let flat = flatten::flatten(doc.get_first(path.get_path())?, separator)?;
+            Ok(serde_json::to_string(&flat)?.into())
+        }
         None => Ok(RedisValue::Null),
     }
 }
 
+pub fn command_json_keys<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let mut path = None;
+    let mut notation = None;
+    while let Ok(arg) = args.next_str() {
+        match arg {
+            arg if arg.eq_ignore_ascii_case(CMD_ARG_NOTATION) && notation.is_none() => {
+                notation = Some(match args.next_str()? {
+                    n if n.eq_ignore_ascii_case("JSONPATH") => PathNotation::JsonPath,
+                    n if n.eq_ignore_ascii_case("POINTER") => PathNotation::Pointer,
+                    _ => return Err(RedisError::Str("ERR NOTATION must be JSONPATH or POINTER")),
+                })
+            }
+            arg if path.is_none() && notation.is_none() => path = Some(Path::new(arg)),
+            _ => return Err(RedisError::Str("ERR syntax error")),
+        }
+    }
+    let path = path.unwrap_or_else(|| Path::new(JSON_ROOT_PATH));
+
+    let key = manager.open_key_read(ctx, &key)?;
+    match key.get_value()? {
+        Some(doc) => {
+            let doc = KeyValue::new(doc);
+            let value = doc.get_first(path.get_path())?;
+            let paths = flatten::leaf_paths(value, notation.unwrap_or(PathNotation::JsonPath))?;
+            Ok(paths.into())
+        }
+        None => Ok(RedisValue::Null),
+    }
+}
+
+pub fn command_json_unflatten<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+    let flat = args.next_str()?;
+    let separator = match args.next_str() {
+        Ok(arg) if arg.eq_ignore_ascii_case(CMD_ARG_SEPARATOR) => args.next_str()?,
+        Ok(_) => return Err(RedisError::Str("ERR syntax error")),
+        Err(_) => flatten::DEFAULT_SEPARATOR,
+    };
+    args.done()?;
+    if separator.is_empty() {
+        return Err(RedisError::Str("ERR separator must not be empty"));
+    }
+
+    check_input_size(flat)?;
+    let value = flatten::unflatten(RedisJSON::parse_input(flat)?, separator)?;
+
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+    // The manager's value type is opaque here, so the rebuilt value goes through its parser
+    let val = manager.from_str(&serde_json::to_string(&value)?, Format::JSON)?;
+    set_path_value::<M>(
+        ctx,
+        &mut redis_key,
+        &path,
+        val,
+        SetOptions::None,
+        "json.unflatten",
+    )
+}
+
+pub fn command_json_copy_path<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let from = Path::new(args.next_str()?);
+    let to = Path::new(args.next_str()?);
+    let create = match args.next_str() {
+        Ok(arg) if arg.eq_ignore_ascii_case(CMD_ARG_CREATE) => true,
+        Ok(_) => return Err(RedisError::Str("ERR syntax error")),
+        Err(_) => false,
+    };
+    args.done()?;
+
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    let json = match KeyValue::new(root).get_values(from.get_path())?.first() {
+        Some(v) => serde_json::to_string(v)?,
+        None => {
+            return Err(RedisError::String(format!(
+                "Path '{}' does not exist",
+                from
+            )))
+        }
+    };
+
+    let created = if create {
+        create_parents(&manager, &mut redis_key, &to)?
+    } else {
+        None
+    };
+    let res = match manager.from_str(&json, Format::JSON) {
+        Ok(val) => set_path_value::<M>(
+            ctx,
+            &mut redis_key,
+            &to,
+            val,
+            SetOptions::None,
+            "json.copypath",
+        ),
+        Err(e) => Err(e.into()),
+    };
+    match res {
+        Ok(RedisValue::Null) | Err(_) => {
+            // The parents are only created for the copy, so they are removed with it
+            if let Some(member) = created {
+                redis_key.delete_path(member)?;
+            }
+            res.and_then(|_| Err(RedisError::String(format!("Path '{}' does not exist", to))))
+        }
+        res => res,
+    }
+}
+
+pub fn command_json_move_path<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let from = Path::new(args.next_str()?);
+    let to = Path::new(args.next_str()?);
+    args.done()?;
+
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    let from_path = match find_paths(from.get_path(), root, |_| true)?
+        .into_iter()
+        .next()
+    {
+        Some(p) => p,
+        None => {
+            return Err(RedisError::String(format!(
+                "Path '{}' does not exist",
+                from
+            )))
+        }
+    };
+    let mut doc = KeyValue::new(root);
+    let to_paths: Vec<Vec<String>> = doc
+        .find_paths(to.get_path(), &SetOptions::None)?
+        .into_iter()
+        .map(|ui| match ui {
+            UpdateInfo::SUI(sui) => sui.path,
+            UpdateInfo::AUI(mut aui) => {
+                aui.path.push(aui.key);
+                aui.path
+            }
+        })
+        .collect();
+    if to_paths.iter().any(|p| *p == from_path) {
+        // Moving a value to itself leaves the document as it is
+        return REDIS_OK;
+    }
+    if from_path.is_empty() || to_paths.iter().any(|p| p.starts_with(&from_path)) {
+        return Err(RedisError::Str(
+            "ERR cannot move a value into one of its descendants",
+        ));
+    }
+    let value = pointer::resolve(root, &from_path)
+        .ok_or_else(|| RedisError::String(format!("Path '{}' does not exist", from)))?;
+    let json = serde_json::to_string(value)?;
+    // Taken before the value is removed, so that a failed move changes nothing, not even the
+    // order of the members of the object it was in
+    let snapshot = redis_key.snapshot()?;
+
+    // As in RFC 6902 the value is removed before locating `to-path`
+    redis_key.delete_path(from_path)?;
+    let res = match manager.from_str(&json, Format::JSON) {
+        Ok(val) => set_path_value::<M>(
+            ctx,
+            &mut redis_key,
+            &to,
+            val,
+            SetOptions::None,
+            "json.movepath",
+        ),
+        Err(e) => Err(e.into()),
+    };
+    match res {
+        Ok(RedisValue::Null) | Err(_) => {
+            redis_key.restore(snapshot)?;
+            res.and_then(|_| Err(RedisError::String(format!("Path '{}' does not exist", to))))
+        }
+        res => res,
+    }
+}
+
+/// An operation of `JSON.MULTIOP`, given as `{"op": <name>, "path": <path>, "args": [...]}`
+struct MultiOp {
+    name: String,
+    path: String,
+    args: Vec<Value>,
+}
+
+impl MultiOp {
+    fn parse(index: usize, op: Value) -> Result<MultiOp, RedisError> {
+        let invalid = || {
+            RedisError::String(format!(
+                "ERR operation {} must be an object with an 'op' name, and optionally a 'path' \
+                 string and an 'args' array",
+                index
+            ))
+        };
+        let mut op = match op {
+            Value::Object(op) => op,
+            _ => return Err(invalid()),
+        };
+        let name = match op.remove("op") {
+            Some(Value::String(name)) => name.to_lowercase(),
+            _ => return Err(invalid()),
+        };
+        let path = match op.remove("path") {
+            None => JSON_ROOT_PATH.to_string(),
+            Some(Value::String(path)) => path,
+            Some(_) => return Err(invalid()),
+        };
+        let args = match op.remove("args") {
+            None => Vec::new(),
+            Some(Value::Array(args)) => args,
+            Some(_) => return Err(invalid()),
+        };
+        if !op.is_empty() {
+            return Err(invalid());
+        }
+        Ok(MultiOp { name, path, args })
+    }
+
+    fn arg(&self, index: usize) -> Result<&Value, RedisError> {
+        self.args.get(index).ok_or_else(|| {
+            RedisError::String(format!(
+                "ERR missing argument {} for '{}'",
+                index, self.name
+            ))
+        })
+    }
+
+    fn int_arg(&self, index: usize) -> Result<i64, RedisError> {
+        self.arg(index)?.as_i64().ok_or_else(|| {
+            RedisError::String(format!(
+                "ERR argument {} for '{}' must be an integer",
+                index, self.name
+            ))
+        })
+    }
+
+    fn check_args(&self, min: usize, max: usize) -> Result<(), RedisError> {
+        if (min..=max).contains(&self.args.len()) {
+            Ok(())
+        } else {
+            Err(RedisError::String(format!(
+                "ERR wrong number of arguments for '{}'",
+                self.name
+            )))
+        }
+    }
+}
+
+/// Finds the paths of the values matching `path` that `is_target` accepts, failing when there is
+/// none
+fn find_targets<M: Manager, F: FnMut(&M::V) -> bool>(
+    redis_key: &mut M::WriteHolder,
+    path: &Path,
+    expected: &str,
+    is_target: F,
+) -> Result<Vec<Vec<String>>, RedisError> {
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    let paths = find_paths(path.get_path(), &*root, is_target)?;
+    if paths.is_empty() {
+        Err(RedisError::String(format!(
+            "Path '{}' does not exist or not {}",
+            path, expected
+        )))
+    } else {
+        Ok(paths)
+    }
+}
+
+/// Applies an operation of `JSON.MULTIOP`, replying like the command it is named after does for
+/// a single match
+fn apply_multi_op<M: Manager>(
+    manager: &M,
+    redis_key: &mut M::WriteHolder,
+    op: &MultiOp,
+) -> RedisResult {
+    let path = Path::new(&op.path);
+    let values = |args: &[Value]| {
+        args.iter()
+            .map(|v| Ok(manager.from_str(&serde_json::to_string(v)?, Format::JSON)?))
+            .collect::<Result<Vec<M::O>, RedisError>>()
+    };
+    let is_array = |v: &M::V| v.get_type() == SelectValueType::Array;
+
+    let num_op = match op.name.as_str() {
+        "numincrby" => Some(NumOp::Incr),
+        "nummultby" => Some(NumOp::Mult),
+        "numpowby" => Some(NumOp::Pow),
+        "numdivby" => Some(NumOp::Div),
+        "nummodby" => Some(NumOp::Mod),
+        "numminby" => Some(NumOp::Min),
+        "nummaxby" => Some(NumOp::Max),
+        _ => None,
+    };
+    if let Some(num_op) = num_op {
+        op.check_args(1, 1)?;
+        let number = match op.arg(0)? {
+            Value::Number(n) => n,
+            _ => {
+                return Err(RedisError::String(format!(
+                    "ERR argument 0 for '{}' must be a number",
+                    op.name
+                )))
+            }
+        };
+        let mut res = None;
+        for p in find_targets::<M, _>(redis_key, &path, "a number", |v| {
+            v.get_type() == SelectValueType::Double || v.get_type() == SelectValueType::Long
+        })? {
+            res = Some(match num_op {
+                NumOp::Incr => redis_key.incr_by(p, number)?,
+                NumOp::Mult => redis_key.mult_by(p, number)?,
+                NumOp::Pow => redis_key.pow_by(p, number)?,
+                NumOp::Div => redis_key.div_by(p, number)?,
+                NumOp::Mod => redis_key.mod_by(p, number)?,
+                NumOp::Min => redis_key.min_by(p, number)?,
+                NumOp::Max => redis_key.max_by(p, number)?,
+            });
+        }
+        return Ok(formatter::format_number(&res.unwrap()).into());
+    }
+
+    match op.name.as_str() {
+        "set" => {
+            op.check_args(1, 1)?;
+            let val = values(&op.args)?.pop().unwrap();
+            if store_path_value::<M>(redis_key, &path, val, SetOptions::None)? {
+                REDIS_OK
+            } else {
+                Ok(RedisValue::Null)
+            }
+        }
+        "del" => {
+            op.check_args(0, 0)?;
+            if path.get_path() == JSON_ROOT_PATH {
+                return Err(RedisError::Str(
+                    "ERR JSON.MULTIOP can not delete the root, use JSON.DEL",
+                ));
+            }
+            let root = redis_key
+                .get_value()?
+                .ok_or_else(RedisError::nonexistent_key)?;
+            let paths = find_paths(path.get_path(), &*root, |_| true)?;
+            let mut deleted = 0;
+            for p in sort_paths_for_removal(paths) {
+                if redis_key.delete_path(p)? {
+                    deleted += 1;
+                }
+            }
+            Ok((deleted as i64).into())
+        }
+        "toggle" => {
+            op.check_args(0, 0)?;
+            let mut res = None;
+            for p in find_targets::<M, _>(redis_key, &path, "a bool", |v| {
+                v.get_type() == SelectValueType::Bool
+            })? {
+                res = Some(redis_key.bool_toggle(p)?);
+            }
+            Ok(res.unwrap().to_string().into())
+        }
+        "strappend" => {
+            op.check_args(1, 1)?;
+            let json = serde_json::to_string(op.arg(0)?)?;
+            let mut res = None;
+            for p in find_targets::<M, _>(redis_key, &path, "a string", |v| {
+                v.get_type() == SelectValueType::String
+            })? {
+                res = Some(redis_key.str_append(p, json.clone(), false)?);
+            }
+            Ok(res.unwrap().into())
+        }
+        "arrappend" => {
+            op.check_args(1, usize::MAX)?;
+            let items = values(&op.args)?;
+            let mut res = None;
+            for p in find_targets::<M, _>(redis_key, &path, "an array", is_array)? {
+                res = Some(redis_key.arr_append(p, items.clone())?);
+            }
+            Ok(res.unwrap().into())
+        }
+        "arrinsert" => {
+            op.check_args(2, usize::MAX)?;
+            let index = op.int_arg(0)?;
+            let items = values(&op.args[1..])?;
+            let mut res = None;
+            for p in find_targets::<M, _>(redis_key, &path, "an array", is_array)? {
+                res = Some(redis_key.arr_insert(p, &items, index)?);
+            }
+            Ok(res.unwrap().into())
+        }
+        "arrpop" => {
+            op.check_args(0, 1)?;
+            let index = if op.args.is_empty() {
+                -1
+            } else {
+                op.int_arg(0)?
+            };
+            let mut res = None;
+            for p in find_targets::<M, _>(redis_key, &path, "an array", is_array)? {
+                res = Some(redis_key.arr_pop(p, index)?);
+            }
+            Ok(res.unwrap().map_or(RedisValue::Null, RedisValue::from))
+        }
+        "arrtrim" => {
+            op.check_args(2, 2)?;
+            let (start, stop) = (op.int_arg(0)?, op.int_arg(1)?);
+            let mut res = None;
+            for p in find_targets::<M, _>(redis_key, &path, "an array", is_array)? {
+                res = Some(redis_key.arr_trim(p, start, stop)?);
+            }
+            Ok(res.unwrap().into())
+        }
+        "clear" => {
+            op.check_args(0, 0)?;
+            let root = redis_key
+                .get_value()?
+                .ok_or_else(RedisError::nonexistent_key)?;
+            let paths = find_paths(path.get_path(), &*root, |_| true)?;
+            if paths.is_empty() {
+                return Err(RedisError::String(format!(
+                    "Path '{}' does not exist",
+                    path
+                )));
+            }
+            // Clearing a value clears everything below it, so nested matches are not visited
+            let mut res = 0;
+            for p in remove_nested_paths(paths) {
+                res += redis_key.clear(p, false)?;
+            }
+            Ok(res.into())
+        }
+        name => Err(RedisError::String(format!(
+            "ERR unknown operation '{}'",
+            name
+        ))),
+    }
+}
+
+pub fn command_json_multi_op<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let ops = args.next_str()?;
+    check_input_size(ops)?;
+    let ops = match serde_json::from_str(ops) {
+        Ok(Value::Array(ops)) => ops,
+        Ok(_) => return Err(RedisError::Str("ERR operations must be an array")),
+        Err(e) => {
+            return Err(RedisError::String(format!(
+                "ERR failed to parse operations: {}",
+                e
+            )))
+        }
+    };
+    args.done()?;
+    let ops = ops
+        .into_iter()
+        .enumerate()
+        .map(|(i, op)| MultiOp::parse(i, op))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+
+    // The document as it was, to put it back if an operation fails
+    let snapshot = redis_key.snapshot()?;
+    let mut res = Vec::with_capacity(ops.len());
+    for (i, op) in ops.iter().enumerate() {
+        match apply_multi_op(&manager, &mut redis_key, op) {
+            Ok(r) => res.push(r),
+            Err(e) => {
+                redis_key.restore(snapshot)?;
+                return Err(match e {
+                    RedisError::Str(msg) => {
+                        RedisError::String(format!("{} (operation {})", msg, i))
+                    }
+                    RedisError::String(msg) => {
+                        RedisError::String(format!("{} (operation {})", msg, i))
+                    }
+                    e => e,
+                });
+            }
+        }
+    }
+    if !ops.is_empty() {
+        redis_key.apply_changes(ctx, "json.multiop")?;
+    }
+    Ok(res.into())
+}
+
+pub fn command_json_diff<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let from_key = args.next_arg()?;
+    let to_key = args.next_arg()?;
+    let mut path = None;
+    let mut json_patch = false;
+    while let Ok(arg) = args.next_str() {
+        match arg {
+            arg if arg.eq_ignore_ascii_case(CMD_ARG_PATCH) && !json_patch => json_patch = true,
+            arg if path.is_none() && !json_patch => path = Some(Path::new(arg)),
+            _ => return Err(RedisError::Str("ERR syntax error")),
+        }
+    }
+    let path = path.unwrap_or_else(|| Path::new(JSON_ROOT_PATH));
+
+    let from_key = manager.open_key_read(ctx, &from_key)?;
+    let to_key = manager.open_key_read(ctx, &to_key)?;
+    let (from, to) = match (from_key.get_value()?, to_key.get_value()?) {
+        (Some(from), Some(to)) => (KeyValue::new(from), KeyValue::new(to)),
+        _ => return Ok(RedisValue::Null),
+    };
+    let from = from.to_value(from.get_first(path.get_path())?);
+    let to = to.to_value(to.get_first(path.get_path())?);
+    let res = if json_patch {
+        serde_json::to_string(&diff::json_patch(&from, &to))?
+    } else {
+        serde_json::to_string(&diff::merge_patch(&from, &to))?
+    };
+    Ok(res.into())
+}
+
+pub fn command_json_validate<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let mut path = None;
+    let mut schema = None;
+    let mut errors = false;
+    while let Ok(arg) = args.next_str() {
+        match arg {
+            arg if arg.eq_ignore_ascii_case(CMD_ARG_SCHEMA) && schema.is_none() => {
+                schema = Some(args.next_str()?)
+            }
+            arg if arg.eq_ignore_ascii_case(CMD_ARG_ERRORS) && !errors => errors = true,
+            arg if path.is_none() && schema.is_none() && !errors => path = Some(Path::new(arg)),
+            _ => return Err(RedisError::Str("ERR syntax error")),
+        }
+    }
+    let path = path.unwrap_or_else(|| Path::new(JSON_ROOT_PATH));
+    let schema: Value = match schema {
+        Some(schema) => serde_json::from_str(schema)
+            .map_err(|e| RedisError::String(format!("ERR invalid schema: {}", e)))?,
+        None => return Err(RedisError::WrongArity),
+    };
+
+    let key = manager.open_key_read(ctx, &key)?;
+    let doc = match key.get_value()? {
+        Some(doc) => KeyValue::new(doc),
+        None => return Ok(RedisValue::Null),
+    };
+    let value = doc.to_value(doc.get_first(path.get_path())?);
+    let res = schema::validate(&schema, &value)?;
+    if errors {
+        Ok(res
+            .into_iter()
+            .map(|e| vec![RedisValue::from(e.path), RedisValue::from(e.message)].into())
+            .collect::<Vec<RedisValue>>()
+            .into())
+    } else {
+        Ok((res.is_empty() as i64).into())
+    }
+}
+
+pub fn command_json_validate_path<M: Manager>(
+    _manager: M,
+    _ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+    let path = Path::new(args.next_str()?);
+    args.done()?;
+
+    // Compiled like the commands do, so the path is then already in the cache. A function is
+    // not part of the path the values are selected with
+    let values = PathFunction::split(path.get_path()).map_or(path.get_path(), |(v, _)| v);
+    cache::compile(values).map_err(Error::from)?;
+    REDIS_OK
+}
+
 pub fn command_json_cache_info<M: Manager>(
     _manager: M,
     _ctx: &Context,
-    _args: Vec<RedisString>,
+    args: Vec<RedisString>,
 ) -> RedisResult {
-    Err(RedisError::Str("Command was not implemented"))
+    if args.len() > 1 {
+        return Err(RedisError::WrongArity);
+    }
+    let info = cache::info();
+    Ok(vec![
+        RedisValue::SimpleStringStatic("hits"),
+        (info.hits as i64).into(),
+        RedisValue::SimpleStringStatic("misses"),
+        (info.misses as i64).into(),
+        RedisValue::SimpleStringStatic("items"),
+        (info.entries as i64).into(),
+        RedisValue::SimpleStringStatic("bytes"),
+        (info.bytes as i64).into(),
+        RedisValue::SimpleStringStatic("max_items"),
+        (info.capacity as i64).into(),
+    ]
+    .into())
 }
 
 pub fn command_json_cache_init<M: Manager>(
     _manager: M,
     _ctx: &Context,
-    _args: Vec<RedisString>,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+    let capacity = match args.next() {
+        Some(size) => match size.parse_integer() {
+            Ok(n) if n >= 0 => n as usize,
+            _ => {
+                return Err(RedisError::Str(
+                    "ERR cache size must be a non-negative integer",
+                ))
+            }
+        },
+        None => config::path_cache_size(),
+    };
+    args.done()?;
+    cache::init(capacity);
+    REDIS_OK
+}
+
+pub fn command_json_config<M: Manager>(
+    _manager: M,
+    _ctx: &Context,
+    args: Vec<RedisString>,
 ) -> RedisResult {
-    Err(RedisError::Str("Command was not implemented"))
+    let mut args = args.into_iter().skip(1);
+    let op = args.next_str()?;
+    if op.eq_ignore_ascii_case("GET") {
+        let name = args.next_str()?;
+        args.done()?;
+        let names = if name == "*" {
+            config::NAMES.to_vec()
+        } else {
+            config::find(name).into_iter().collect()
+        };
+        let mut res = Vec::with_capacity(names.len() * 2);
+        for name in names {
+            res.push(RedisValue::SimpleStringStatic(name));
+            res.push(config::get(name).unwrap().into());
+        }
+        Ok(res.into())
+    } else if op.eq_ignore_ascii_case("SET") {
+        let name = args.next_str()?;
+        let value = args.next_str()?;
+        args.done()?;
+        config::update(name, value).map_err(|e| {
+            RedisError::String(format!("ERR invalid configuration '{}': {}", name, e))
+        })?;
+        REDIS_OK
+    } else {
+        Err(RedisError::Str(
+            "ERR unknown subcommand, expected GET or SET",
+        ))
+    }
 }
@@ -0,0 +1,43 @@
+// Gzip compression of the serialized values replied by JSON.GET and accepted by JSON.SET
+//
+// The compressed data is a plain gzip member (RFC 1952), it starts with the bytes 0x1f 0x8b.
+
+use crate::error::Error;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+
+/// Compresses a serialized value
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::with_capacity(data.len() / 4), Compression::fast());
+    // Writing to a Vec never fails
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// The size a value is decompressed to at most when there is no MAX_DOC_SIZE limit, the default
+/// proto-max-bulk-len of Redis: no value sent uncompressed can be larger
+pub const DEFAULT_LIMIT: usize = 512 * 1024 * 1024;
+
+/// Decompresses a serialized value, failing if it is larger than `limit` bytes (0 is
+/// `DEFAULT_LIMIT`)
+///
+/// The limit is enforced while decompressing, so a small input can not expand to an
+/// unbounded amount of memory.
+pub fn gunzip(data: &[u8], limit: usize) -> Result<String, Error> {
+    let limit = if limit > 0 { limit } else { DEFAULT_LIMIT };
+    let mut res = Vec::new();
+    GzDecoder::new(data)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut res)
+        .map_err(|e| Error::from(format!("ERR invalid gzip data: {}", e)))?;
+    if res.len() > limit {
+        return Err(format!(
+            "ERR document size exceeds the limit of {} bytes when decompressed",
+            limit
+        )
+        .into());
+    }
+    String::from_utf8(res).map_err(|_| "ERR decompressed value is not valid UTF-8".into())
+}
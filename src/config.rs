@@ -0,0 +1,362 @@
+// Module configuration, given as module load time arguments, e.g.:
+//
+//   redis-server --loadmodule ./rejson.so MAX_DEPTH 64
+//
+// and changed at runtime with `JSON.CONFIG SET MAX_DEPTH 64`. On Redis 7.0 and later the options
+// are also module configuration parameters, `CONFIG SET rejson.max-depth 64`, which can be
+// given in redis.conf and are saved by `CONFIG REWRITE`.
+
+use crate::cache;
+use crate::commands::module_api;
+use redis_module::{raw, Context, RedisString, Status};
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::ptr;
+use std::slice;
+use std::str;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// The deepest nesting the serde_json parser reads back, so stored documents can always be reloaded
+pub const DEFAULT_MAX_DEPTH: usize = 127;
+
+pub const DEFAULT_PATH_CACHE_SIZE: usize = 1000;
+
+/// Large enough for any document worth storing, small enough that a client can not make the
+/// module parse hundreds of megabytes at once
+pub const DEFAULT_MAX_INPUT_SIZE: usize = 64 * 1024 * 1024;
+
+/// The largest indentation INDENTSIZE takes
+pub const MAX_INDENT_SIZE: usize = 10;
+
+const CONFIG_MAX_DEPTH: &str = "MAX_DEPTH";
+pub const CONFIG_PATH_CACHE_SIZE: &str = "PATH_CACHE_SIZE";
+const CONFIG_DUPLICATE_KEYS: &str = "DUPLICATE_KEYS";
+const CONFIG_NOTIFY_PATHS: &str = "NOTIFY_PATHS";
+const CONFIG_MAX_DOC_SIZE: &str = "MAX_DOC_SIZE";
+const CONFIG_SHARE_DOCS: &str = "SHARE_DOCS";
+const CONFIG_MAX_INPUT_SIZE: &str = "MAX_INPUT_SIZE";
+const CONFIG_DEFAULT_INDENT_SIZE: &str = "DEFAULT_INDENT_SIZE";
+const CONFIG_PRESERVE_ORDER: &str = "PRESERVE_ORDER";
+
+/// All the options, in the order `JSON.CONFIG GET *` replies them
+pub const NAMES: [&str; 9] = [
+    CONFIG_MAX_DEPTH,
+    CONFIG_PATH_CACHE_SIZE,
+    CONFIG_DUPLICATE_KEYS,
+    CONFIG_NOTIFY_PATHS,
+    CONFIG_MAX_DOC_SIZE,
+    CONFIG_SHARE_DOCS,
+    CONFIG_MAX_INPUT_SIZE,
+    CONFIG_DEFAULT_INDENT_SIZE,
+    CONFIG_PRESERVE_ORDER,
+];
+
+static MAX_DEPTH: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_DEPTH);
+static PATH_CACHE_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_PATH_CACHE_SIZE);
+static REJECT_DUPLICATE_KEYS: AtomicBool = AtomicBool::new(false);
+static NOTIFY_PATHS: AtomicBool = AtomicBool::new(false);
+static MAX_DOC_SIZE: AtomicUsize = AtomicUsize::new(0);
+static SHARE_DOCS: AtomicBool = AtomicBool::new(false);
+static MAX_INPUT_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_INPUT_SIZE);
+static DEFAULT_INDENT_SIZE: AtomicUsize = AtomicUsize::new(0);
+static PRESERVE_ORDER: AtomicBool = AtomicBool::new(true);
+
+/// Maximum nesting depth of arrays and objects in a document
+pub fn max_depth() -> usize {
+    MAX_DEPTH.load(Ordering::Relaxed)
+}
+
+/// Maximum number of compiled paths kept in the path cache, 0 disables the cache
+pub fn path_cache_size() -> usize {
+    PATH_CACHE_SIZE.load(Ordering::Relaxed)
+}
+
+/// Whether parsing an object with the same key twice fails, instead of keeping the last value
+pub fn reject_duplicate_keys() -> bool {
+    REJECT_DUPLICATE_KEYS.load(Ordering::Relaxed)
+}
+
+/// Whether keyspace events name the path that was changed along with the command
+pub fn notify_paths() -> bool {
+    NOTIFY_PATHS.load(Ordering::Relaxed)
+}
+
+/// Maximum size in bytes of a document stored by JSON.SET, 0 means no limit
+pub fn max_doc_size() -> usize {
+    MAX_DOC_SIZE.load(Ordering::Relaxed)
+}
+
+/// Whether documents set as a whole are hashed to share them with the keys holding the same one
+pub fn share_docs() -> bool {
+    SHARE_DOCS.load(Ordering::Relaxed)
+}
+
+/// Maximum size in bytes of a JSON value given to a command, 0 means no limit
+pub fn max_input_size() -> usize {
+    MAX_INPUT_SIZE.load(Ordering::Relaxed)
+}
+
+/// Indentation, in spaces, of the JSON replies that are not given any formatting option, 0 means
+/// they are compact
+pub fn default_indent_size() -> usize {
+    DEFAULT_INDENT_SIZE.load(Ordering::Relaxed)
+}
+
+/// Whether objects are replied with their members in insertion order, instead of sorted by key
+pub fn preserve_order() -> bool {
+    PRESERVE_ORDER.load(Ordering::Relaxed)
+}
+
+fn parse_yes_no(value: Option<&str>) -> Result<bool, String> {
+    match value {
+        Some(v) if v.eq_ignore_ascii_case("YES") => Ok(true),
+        Some(v) if v.eq_ignore_ascii_case("NO") => Ok(false),
+        _ => Err("expected YES or NO".to_string()),
+    }
+}
+
+/// `DUPLICATE_KEYS` is either `LAST` (the last value wins) or `ERROR`
+fn parse_duplicate_keys(value: Option<&str>) -> Result<bool, String> {
+    match value {
+        Some(v) if v.eq_ignore_ascii_case("LAST") => Ok(false),
+        Some(v) if v.eq_ignore_ascii_case("ERROR") => Ok(true),
+        _ => Err("expected LAST or ERROR".to_string()),
+    }
+}
+
+fn parse_non_negative(value: Option<&str>) -> Result<usize, String> {
+    match value.map(|v| v.parse::<i64>()) {
+        Some(Ok(n)) if n >= 0 => Ok(n as usize),
+        _ => Err("expected a non-negative integer".to_string()),
+    }
+}
+
+/// Deeper documents could not be loaded back by the parser, and replying them recurses deeper
+fn parse_max_depth(value: Option<&str>) -> Result<usize, String> {
+    match value.map(|v| v.parse::<i64>()) {
+        Some(Ok(n)) if n > 0 && n as usize <= DEFAULT_MAX_DEPTH => Ok(n as usize),
+        _ => Err(format!(
+            "expected an integer between 1 and {}",
+            DEFAULT_MAX_DEPTH
+        )),
+    }
+}
+
+fn parse_indent_size(value: Option<&str>) -> Result<usize, String> {
+    match value.map(|v| v.parse::<usize>()) {
+        Some(Ok(n)) if n <= MAX_INDENT_SIZE => Ok(n),
+        _ => Err(format!(
+            "expected an integer between 0 and {}",
+            MAX_INDENT_SIZE
+        )),
+    }
+}
+
+/// The canonical name of the option `name`, which is case insensitive
+pub fn find(name: &str) -> Option<&'static str> {
+    NAMES.iter().copied().find(|n| n.eq_ignore_ascii_case(name))
+}
+
+/// The current value of the option `name`, as it is given to set it
+pub fn get(name: &str) -> Option<String> {
+    let value = match find(name)? {
+        CONFIG_MAX_DEPTH => max_depth().to_string(),
+        CONFIG_PATH_CACHE_SIZE => path_cache_size().to_string(),
+        CONFIG_DUPLICATE_KEYS if reject_duplicate_keys() => "ERROR".to_string(),
+        CONFIG_DUPLICATE_KEYS => "LAST".to_string(),
+        CONFIG_NOTIFY_PATHS if notify_paths() => "YES".to_string(),
+        CONFIG_NOTIFY_PATHS => "NO".to_string(),
+        CONFIG_MAX_DOC_SIZE => max_doc_size().to_string(),
+        CONFIG_SHARE_DOCS if share_docs() => "YES".to_string(),
+        CONFIG_SHARE_DOCS => "NO".to_string(),
+        CONFIG_MAX_INPUT_SIZE => max_input_size().to_string(),
+        CONFIG_DEFAULT_INDENT_SIZE => default_indent_size().to_string(),
+        CONFIG_PRESERVE_ORDER if preserve_order() => "YES".to_string(),
+        CONFIG_PRESERVE_ORDER => "NO".to_string(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Validates `value` and sets the option `name` to it
+pub fn set(name: &str, value: Option<&str>) -> Result<(), String> {
+    match find(name) {
+        Some(CONFIG_MAX_DEPTH) => {
+            parse_max_depth(value).map(|n| MAX_DEPTH.store(n, Ordering::Relaxed))
+        }
+        Some(CONFIG_PATH_CACHE_SIZE) => {
+            parse_non_negative(value).map(|n| PATH_CACHE_SIZE.store(n, Ordering::Relaxed))
+        }
+        Some(CONFIG_DUPLICATE_KEYS) => parse_duplicate_keys(value)
+            .map(|reject| REJECT_DUPLICATE_KEYS.store(reject, Ordering::Relaxed)),
+        Some(CONFIG_NOTIFY_PATHS) => {
+            parse_yes_no(value).map(|notify| NOTIFY_PATHS.store(notify, Ordering::Relaxed))
+        }
+        Some(CONFIG_MAX_DOC_SIZE) => {
+            parse_non_negative(value).map(|n| MAX_DOC_SIZE.store(n, Ordering::Relaxed))
+        }
+        Some(CONFIG_SHARE_DOCS) => {
+            parse_yes_no(value).map(|share| SHARE_DOCS.store(share, Ordering::Relaxed))
+        }
+        Some(CONFIG_MAX_INPUT_SIZE) => {
+            parse_non_negative(value).map(|n| MAX_INPUT_SIZE.store(n, Ordering::Relaxed))
+        }
+        Some(CONFIG_DEFAULT_INDENT_SIZE) => {
+            parse_indent_size(value).map(|n| DEFAULT_INDENT_SIZE.store(n, Ordering::Relaxed))
+        }
+        Some(CONFIG_PRESERVE_ORDER) => {
+            parse_yes_no(value).map(|preserve| PRESERVE_ORDER.store(preserve, Ordering::Relaxed))
+        }
+        _ => Err("unknown configuration".to_string()),
+    }
+}
+
+/// Same as `set` once the module is loaded, the path cache is rebuilt so a new size takes effect
+/// right away
+pub fn update(name: &str, value: &str) -> Result<(), String> {
+    set(name, Some(value))?;
+    if find(name) == Some(CONFIG_PATH_CACHE_SIZE) {
+        cache::init(path_cache_size());
+    }
+    Ok(())
+}
+
+/// Applies the module arguments, `args` are pairs of a configuration name and its value
+pub fn load(ctx: &Context, args: &[RedisString]) -> Status {
+    let mut args = args.iter();
+    while let Some(name) = args.next() {
+        let name = name.to_string_lossy();
+        let value = args.next().map(|v| v.to_string_lossy());
+        if let Err(e) = set(&name, value.as_deref()) {
+            ctx.log_warning(&format!("Invalid module argument '{}': {}", name, e));
+            return Status::Err;
+        }
+    }
+    Status::Ok
+}
+
+type GetStringConfig =
+    unsafe extern "C" fn(*const c_char, *mut c_void) -> *mut raw::RedisModuleString;
+type SetStringConfig = unsafe extern "C" fn(
+    *const c_char,
+    *mut raw::RedisModuleString,
+    *mut c_void,
+    *mut *mut raw::RedisModuleString,
+) -> c_int;
+type ApplyConfig = unsafe extern "C" fn(
+    *mut raw::RedisModuleCtx,
+    *mut c_void,
+    *mut *mut raw::RedisModuleString,
+) -> c_int;
+type RegisterStringConfig = unsafe extern "C" fn(
+    *mut raw::RedisModuleCtx,
+    *const c_char,
+    *const c_char,
+    c_uint,
+    Option<GetStringConfig>,
+    Option<SetStringConfig>,
+    Option<ApplyConfig>,
+    *mut c_void,
+) -> c_int;
+type LoadConfigs = unsafe extern "C" fn(*mut raw::RedisModuleCtx) -> c_int;
+
+thread_local! {
+    // The last value `get_config` replied for each option, Redis copies it without freeing it
+    static CONFIG_VALUES: RefCell<Vec<*mut raw::RedisModuleString>> =
+        RefCell::new(vec![ptr::null_mut(); NAMES.len()]);
+}
+
+/// The name of the module configuration parameter of the option `name`, in lower case with
+/// dashes, e.g. `max-depth` for `MAX_DEPTH`. Redis prefixes it with the module name
+fn parameter_name(name: &str) -> String {
+    name.to_lowercase().replace('_', "-")
+}
+
+fn create_string(s: &str) -> *mut raw::RedisModuleString {
+    unsafe {
+        raw::RedisModule_CreateString.unwrap()(
+            ptr::null_mut(),
+            s.as_ptr() as *const c_char,
+            s.len(),
+        )
+    }
+}
+
+/// The option is the index in `NAMES` given as the private data of its parameter
+unsafe extern "C" fn get_config(
+    _name: *const c_char,
+    privdata: *mut c_void,
+) -> *mut raw::RedisModuleString {
+    let index = privdata as usize;
+    let value = create_string(&get(NAMES[index]).unwrap());
+    CONFIG_VALUES.with(|values| {
+        let previous = std::mem::replace(&mut values.borrow_mut()[index], value);
+        if !previous.is_null() {
+            raw::RedisModule_FreeString.unwrap()(ptr::null_mut(), previous);
+        }
+    });
+    value
+}
+
+unsafe extern "C" fn set_config(
+    _name: *const c_char,
+    value: *mut raw::RedisModuleString,
+    privdata: *mut c_void,
+    err: *mut *mut raw::RedisModuleString,
+) -> c_int {
+    let mut len = 0;
+    let data = raw::RedisModule_StringPtrLen.unwrap()(value, &mut len);
+    let res = str::from_utf8(slice::from_raw_parts(data as *const u8, len))
+        .map_err(|_| "invalid UTF-8".to_string())
+        .and_then(|value| update(NAMES[privdata as usize], value));
+    match res {
+        Ok(()) => raw::REDISMODULE_OK as c_int,
+        // Redis frees the error once it is replied
+        Err(e) => {
+            *err = create_string(&e);
+            raw::REDISMODULE_ERR as c_int
+        }
+    }
+}
+
+/// Registers the options as module configuration parameters, with their current value as the
+/// default, and applies the values given for them in redis.conf. The module configuration API
+/// is only available since Redis 7.0, older servers only have JSON.CONFIG and the module
+/// arguments
+pub fn register(ctx: &Context) -> Status {
+    let (register_config, load_configs) = unsafe {
+        match (
+            module_api::<RegisterStringConfig>(ctx, b"RedisModule_RegisterStringConfig\0"),
+            module_api::<LoadConfigs>(ctx, b"RedisModule_LoadConfigs\0"),
+        ) {
+            (Some(register_config), Some(load_configs)) => (register_config, load_configs),
+            _ => return Status::Ok,
+        }
+    };
+    for (index, name) in NAMES.iter().enumerate() {
+        let parameter = CString::new(parameter_name(name)).unwrap();
+        let default = CString::new(get(name).unwrap()).unwrap();
+        let status = unsafe {
+            register_config(
+                ctx.ctx,
+                parameter.as_ptr(),
+                default.as_ptr(),
+                0,
+                Some(get_config),
+                Some(set_config),
+                None,
+                index as *mut c_void,
+            )
+        };
+        if status != raw::REDISMODULE_OK as c_int {
+            ctx.log_warning(&format!("Could not register the configuration '{}'", name));
+            return Status::Err;
+        }
+    }
+    if unsafe { load_configs(ctx.ctx) } != raw::REDISMODULE_OK as c_int {
+        ctx.log_warning("Could not load the module configuration");
+        return Status::Err;
+    }
+    Status::Ok
+}
@@ -0,0 +1,89 @@
+// Differences between two values, either as a JSON Merge Patch (RFC 7386) or as the operations
+// of a JSON Patch (RFC 6902)
+//
+// Objects are walked in key order, so the same two values always produce the same patch.
+
+use crate::pointer;
+use serde_json::{json, Map, Value};
+
+/// Returns the merge patch that transforms `from` into `to`
+///
+/// Only objects are merged, any other value (including arrays) that differs is replaced as a
+/// whole. As merge patches use null to remove a member, a member changed to null can not be
+/// told apart from a removed member.
+pub fn merge_patch(from: &Value, to: &Value) -> Value {
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            let mut patch = Map::new();
+            for k in sorted_keys(from, to) {
+                match (from.get(k), to.get(k)) {
+                    (Some(old), Some(new)) if old == new => {}
+                    (Some(old), Some(new)) => {
+                        patch.insert(k.clone(), merge_patch(old, new));
+                    }
+                    (Some(_), None) => {
+                        patch.insert(k.clone(), Value::Null);
+                    }
+                    (None, Some(new)) => {
+                        patch.insert(k.clone(), new.clone());
+                    }
+                    (None, None) => unreachable!(),
+                }
+            }
+            Value::Object(patch)
+        }
+        _ => to.clone(),
+    }
+}
+
+/// The keys of both objects in ascending order, rather than in the order of their members
+fn sorted_keys<'a>(from: &'a Map<String, Value>, to: &'a Map<String, Value>) -> Vec<&'a String> {
+    let mut keys: Vec<&String> = from.keys().chain(to.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// Returns the JSON Patch operations that transform `from` into `to`
+///
+/// Objects and arrays are compared member by member. Extra array elements are removed starting
+/// from the last one, so the indices of the remaining operations stay valid.
+pub fn json_patch(from: &Value, to: &Value) -> Vec<Value> {
+    let mut ops = Vec::new();
+    diff_into(&mut String::new(), from, to, &mut ops);
+    ops
+}
+
+fn diff_into(path: &mut String, from: &Value, to: &Value, ops: &mut Vec<Value>) {
+    let len = path.len();
+    match (from, to) {
+        _ if from == to => {}
+        (Value::Object(from), Value::Object(to)) => {
+            for k in sorted_keys(from, to) {
+                path.push('/');
+                path.push_str(&pointer::escape(k));
+                match (from.get(k), to.get(k)) {
+                    (Some(old), Some(new)) => diff_into(path, old, new, ops),
+                    (Some(_), None) => ops.push(json!({"op": "remove", "path": path})),
+                    (None, Some(new)) => ops.push(json!({"op": "add", "path": path, "value": new})),
+                    (None, None) => unreachable!(),
+                }
+                path.truncate(len);
+            }
+        }
+        (Value::Array(from), Value::Array(to)) => {
+            for (i, (old, new)) in from.iter().zip(to).enumerate() {
+                path.push_str(&format!("/{}", i));
+                diff_into(path, old, new, ops);
+                path.truncate(len);
+            }
+            for i in (to.len()..from.len()).rev() {
+                ops.push(json!({"op": "remove", "path": format!("{}/{}", path, i)}));
+            }
+            for (i, new) in to.iter().enumerate().skip(from.len()) {
+                ops.push(json!({"op": "add", "path": format!("{}/{}", path, i), "value": new}));
+            }
+        }
+        _ => ops.push(json!({"op": "replace", "path": path, "value": to})),
+    }
+}
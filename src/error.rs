@@ -1,38 +1,127 @@
 use jsonpath_lib::select::JsonPathError;
+use std::fmt;
 
+/// The error of a command, displayed as the message replied to the client
 #[derive(Debug)]
-pub struct Error {
-    pub msg: String,
+pub enum Error {
+    /// The value at a path is not of the type the command works on
+    WrongType(String),
+    /// A path does not match any value
+    PathNotFound(String),
+    /// A JSON value or a path can not be parsed
+    ParseError(String),
+    /// An array index is outside of the array
+    IndexOutOfBounds(String),
+    /// Any other error
+    Custom(String),
+}
+
+impl Error {
+    pub fn wrong_type() -> Self {
+        Error::WrongType("ERR wrong type of path value".to_string())
+    }
+
+    pub fn path_not_found() -> Self {
+        Error::PathNotFound("ERR path does not exist".to_string())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::WrongType(msg)
+            | Error::PathNotFound(msg)
+            | Error::ParseError(msg)
+            | Error::IndexOutOfBounds(msg)
+            | Error::Custom(msg) => write!(f, "{}", msg),
+        }
+    }
 }
 
 impl From<String> for Error {
     fn from(e: String) -> Self {
-        Error { msg: e }
+        Error::Custom(e)
     }
 }
 
 impl From<&str> for Error {
     fn from(e: &str) -> Self {
-        Error { msg: e.to_string() }
+        Error::Custom(e.to_string())
     }
 }
 
 impl From<serde_json::Error> for Error {
     fn from(e: serde_json::Error) -> Self {
-        Error { msg: e.to_string() }
+        Error::ParseError(e.to_string())
+    }
+}
+
+#[cfg(feature = "simd")]
+impl From<simd_json::Error> for Error {
+    fn from(e: simd_json::Error) -> Self {
+        Error::ParseError(e.to_string())
     }
 }
 
 impl From<JsonPathError> for Error {
     fn from(e: JsonPathError) -> Self {
-        Error {
-            msg: format!("JSON Path error: {:?}", e).replace("\n", "\\n"),
+        let msg = format!("JSON Path error: {:?}", e).replace("\n", "\\n");
+        match e {
+            JsonPathError::Path(_) => Error::ParseError(msg),
+            _ => Error::Custom(msg),
         }
     }
 }
 
 impl From<Error> for redis_module::RedisError {
     fn from(e: Error) -> Self {
-        redis_module::RedisError::String(e.msg)
+        redis_module::RedisError::String(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            Error::wrong_type().to_string(),
+            "ERR wrong type of path value"
+        );
+        assert_eq!(
+            Error::path_not_found().to_string(),
+            "ERR path does not exist"
+        );
+        assert_eq!(Error::ParseError("bad".to_string()).to_string(), "bad");
+        assert_eq!(
+            Error::IndexOutOfBounds("out".to_string()).to_string(),
+            "out"
+        );
+        assert_eq!(Error::from("other").to_string(), "other");
+    }
+
+    #[test]
+    fn test_kinds() {
+        assert!(matches!(Error::from("msg"), Error::Custom(_)));
+        assert!(matches!(Error::from("msg".to_string()), Error::Custom(_)));
+        let e = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        assert!(matches!(Error::from(e), Error::ParseError(_)));
+        let e = JsonPathError::Path("$.[".to_string());
+        assert!(matches!(Error::from(e), Error::ParseError(_)));
+        assert!(matches!(
+            Error::from(JsonPathError::EmptyPath),
+            Error::Custom(_)
+        ));
+    }
+
+    #[test]
+    fn test_redis_error() {
+        match redis_module::RedisError::from(Error::wrong_type()) {
+            redis_module::RedisError::String(msg) => {
+                assert_eq!(msg, "ERR wrong type of path value")
+            }
+            _ => panic!("expected the message of the error"),
+        }
     }
 }
@@ -0,0 +1,205 @@
+// Conversion between nested documents and flat maps of paths to leaf values, e.g.
+// `{"a":{"b":[1]}}` and `{"a.b.0":1}`
+
+use crate::config;
+use crate::error::Error;
+use crate::pointer::{self, array_index};
+use jsonpath_lib::select::select_value::{SelectValue, SelectValueType};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashSet};
+
+pub const DEFAULT_SEPARATOR: &str = ".";
+
+/// The notation of the paths returned by `leaf_paths`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathNotation {
+    // `$.a[0]`, keys that are not identifiers are quoted as in `$['a b']`
+    JsonPath,
+    // `/a/0`
+    Pointer,
+}
+
+impl PathNotation {
+    fn root(self) -> &'static str {
+        match self {
+            PathNotation::JsonPath => "$",
+            PathNotation::Pointer => "",
+        }
+    }
+
+    fn key(self, key: &str) -> String {
+        match self {
+            PathNotation::JsonPath
+                if !key.is_empty()
+                    && !key.starts_with(|c: char| c.is_ascii_digit())
+                    && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+            {
+                format!(".{}", key)
+            }
+            PathNotation::JsonPath => {
+                format!("['{}']", key.replace('\'', "\\'"))
+            }
+            PathNotation::Pointer => format!("/{}", pointer::escape(key)),
+        }
+    }
+
+    fn index(self, index: usize) -> String {
+        match self {
+            PathNotation::JsonPath => format!("[{}]", index),
+            PathNotation::Pointer => format!("/{}", index),
+        }
+    }
+}
+
+/// Maps the path of every leaf of `root` to its value, segments are joined with `separator`
+///
+/// Scalars, empty arrays and empty objects are leaves, and array elements use their index as
+/// the segment. The value is traversed iteratively so deep documents do not exhaust the stack.
+pub fn flatten<'a, V: SelectValue>(
+    root: &'a V,
+    separator: &str,
+) -> Result<BTreeMap<String, &'a V>, Error> {
+    let mut res = BTreeMap::new();
+    let mut stack: Vec<(Option<String>, &V)> = vec![(None, root)];
+    while let Some((prefix, v)) = stack.pop() {
+        let children: Vec<(String, &V)> = match v.get_type() {
+            SelectValueType::Object => v
+                .items()
+                .unwrap()
+                .map(|(k, c)| (k.to_string(), c))
+                .collect(),
+            SelectValueType::Array => v
+                .values()
+                .unwrap()
+                .enumerate()
+                .map(|(i, c)| (i.to_string(), c))
+                .collect(),
+            _ => Vec::new(),
+        };
+        if children.is_empty() {
+            let path = prefix.unwrap_or_default();
+            if res.contains_key(&path) {
+                return Err(format!(
+                    "ERR path '{}' is ambiguous with separator '{}'",
+                    path, separator
+                )
+                .into());
+            }
+            res.insert(path, v);
+            continue;
+        }
+        for (k, c) in children {
+            let path = match &prefix {
+                Some(p) => [p.as_str(), separator, k.as_str()].concat(),
+                None => k,
+            };
+            stack.push((Some(path), c));
+        }
+    }
+    Ok(res)
+}
+
+/// Returns the path of every leaf of `root` in document order, the same leaves as `flatten`
+///
+/// The traversal is iterative and fails on values nested deeper than the MAX_DEPTH option.
+pub fn leaf_paths<V: SelectValue>(root: &V, notation: PathNotation) -> Result<Vec<String>, Error> {
+    let max_depth = config::max_depth();
+    let mut res = Vec::new();
+    let mut stack: Vec<(String, usize, &V)> = vec![(notation.root().to_string(), 0, root)];
+    while let Some((path, depth, v)) = stack.pop() {
+        let children: Vec<(String, &V)> = match v.get_type() {
+            SelectValueType::Object => v
+                .items()
+                .unwrap()
+                .map(|(k, c)| (notation.key(k), c))
+                .collect(),
+            SelectValueType::Array => v
+                .values()
+                .unwrap()
+                .enumerate()
+                .map(|(i, c)| (notation.index(i), c))
+                .collect(),
+            _ => Vec::new(),
+        };
+        if children.is_empty() {
+            res.push(path);
+            continue;
+        }
+        if depth >= max_depth {
+            return Err(format!("ERR maximum nesting depth of {} exceeded", max_depth).into());
+        }
+        // Pushed in reverse so the first child is visited first
+        for (segment, c) in children.into_iter().rev() {
+            stack.push(([path.as_str(), segment.as_str()].concat(), depth + 1, c));
+        }
+    }
+    Ok(res)
+}
+
+/// Rebuilds the nested value of a flat object, the inverse of `flatten`
+///
+/// Segments that are array indices create arrays and the others create objects, so a segment
+/// that is used both ways is an error, as is a path that is also the prefix of another path.
+/// Array elements without a path are set to null.
+pub fn unflatten(flat: Value, separator: &str) -> Result<Value, Error> {
+    let entries = match flat {
+        Value::Object(entries) => entries,
+        _ => return Err("ERR flattened value must be an object".into()),
+    };
+    let leaves: HashSet<&str> = entries.keys().map(|k| k.as_str()).collect();
+    for path in entries.keys() {
+        if let Some(prefix) = path
+            .match_indices(separator)
+            .map(|(i, _)| &path[..i])
+            .find(|prefix| leaves.contains(prefix))
+        {
+            return Err(format!("ERR path '{}' conflicts with path '{}'", prefix, path).into());
+        }
+    }
+    if entries.contains_key("") {
+        // The empty path is the value itself, it conflicts with any other path
+        return match entries.len() {
+            1 => Ok(entries.into_iter().next().unwrap().1),
+            _ => Err("ERR the empty path conflicts with the other paths".into()),
+        };
+    }
+
+    let max_index = entries.len();
+    let mut root = Value::Null;
+    for (path, leaf) in entries {
+        let mut target = &mut root;
+        for segment in path.split(separator) {
+            let index = array_index(segment);
+            if target.is_null() {
+                *target = match index {
+                    Some(_) => Value::Array(Vec::new()),
+                    None => Value::Object(Map::new()),
+                };
+            }
+            target = match (target, index) {
+                (Value::Array(arr), Some(i)) => {
+                    if i >= max_index {
+                        return Err(Error::IndexOutOfBounds(format!(
+                            "ERR array index {} of path '{}' is out of range",
+                            i, path
+                        )));
+                    }
+                    if arr.len() <= i {
+                        arr.resize(i + 1, Value::Null);
+                    }
+                    &mut arr[i]
+                }
+                (Value::Object(obj), None) => obj.entry(segment).or_insert(Value::Null),
+                _ => {
+                    return Err(format!(
+                        "ERR segment '{}' of path '{}' is used both as an object key and as an array index",
+                        segment, path
+                    )
+                    .into())
+                }
+            };
+        }
+        *target = leaf;
+    }
+    Ok(root)
+}
@@ -26,25 +26,63 @@ IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 DEALINGS IN THE SOFTWARE.
 */
 
+use jsonpath_lib::select::select_value::{SelectValue, SelectValueType};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
 use serde_json::ser::Formatter;
+use serde_json::Number;
 use std::io;
 
+/// The notation floats are written in
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NumFormat {
+    /// The shortest one, as serde_json does (see `format_float`)
+    Auto,
+    Fixed,
+    Scientific,
+}
+
+/// Writes a finite float as the shortest text that reads back to it
+///
+/// This is the Ryū algorithm serde_json serializes floats with, it does not depend on the
+/// platform's libc, so a master and its replicas write the same text for the same value.
+pub fn format_float(value: f64) -> String {
+    ryu::Buffer::new().format_finite(value).to_string()
+}
+
+/// Writes a number as it is serialized in a document, floats with `format_float`
+pub fn format_number(n: &Number) -> String {
+    match n.as_f64() {
+        Some(f) if n.is_f64() => format_float(f),
+        _ => n.to_string(),
+    }
+}
+
 pub struct RedisJsonFormatter<'a> {
     current_indent: usize,
     has_value: bool,
     indent: Option<&'a str>,
     space: Option<&'a str>,
     newline: Option<&'a str>,
+    precision: Option<usize>,
+    num_format: NumFormat,
 }
 
 impl<'a> RedisJsonFormatter<'a> {
-    pub fn new(indent: Option<&'a str>, space: Option<&'a str>, newline: Option<&'a str>) -> Self {
+    pub fn new(
+        indent: Option<&'a str>,
+        space: Option<&'a str>,
+        newline: Option<&'a str>,
+        precision: Option<usize>,
+        num_format: NumFormat,
+    ) -> Self {
         RedisJsonFormatter {
             current_indent: 0,
             has_value: false,
             indent,
             space,
             newline,
+            precision,
+            num_format,
         }
     }
 
@@ -70,6 +108,35 @@ impl<'a> RedisJsonFormatter<'a> {
 }
 
 impl Formatter for RedisJsonFormatter<'_> {
+    fn write_f64<W: ?Sized>(&mut self, writer: &mut W, value: f64) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        // Rounds to the significant digits through the scientific notation, the rounded value is
+        // then written as short as possible like any other float
+        let value = match self.precision {
+            Some(digits) => format!("{:.*e}", digits - 1, value)
+                .parse()
+                .unwrap_or(value),
+            None => value,
+        };
+        match self.num_format {
+            NumFormat::Auto => writer.write_all(format_float(value).as_bytes()),
+            // Display never uses an exponent, the decimal point keeps it a float when read back.
+            // It is implemented by core rather than libc, so it is the same on every platform
+            NumFormat::Fixed => {
+                let fixed = value.to_string();
+                writer.write_all(fixed.as_bytes())?;
+                if fixed.contains('.') {
+                    Ok(())
+                } else {
+                    writer.write_all(b".0")
+                }
+            }
+            NumFormat::Scientific => write!(writer, "{:e}", value),
+        }
+    }
+
     fn begin_array<W: ?Sized>(&mut self, writer: &mut W) -> io::Result<()>
     where
         W: io::Write,
@@ -162,3 +229,69 @@ impl Formatter for RedisJsonFormatter<'_> {
         Ok(())
     }
 }
+
+/// Serializes a value, optionally emitting object keys in sorted order
+///
+/// Keys are compared as strings, i.e. by their Unicode scalar values, and arrays keep
+/// their element order. When the value is an array, `limit` restricts it to a window of
+/// its elements, nested arrays are always serialized whole.
+pub struct SortedKeys<'a, V: SelectValue> {
+    value: &'a V,
+    sort_keys: bool,
+    // (offset, count)
+    limit: Option<(usize, usize)>,
+}
+
+impl<'a, V: SelectValue> SortedKeys<'a, V> {
+    pub fn new(value: &'a V, sort_keys: bool) -> Self {
+        SortedKeys {
+            value,
+            sort_keys,
+            limit: None,
+        }
+    }
+
+    pub fn limit(mut self, limit: Option<(usize, usize)>) -> Self {
+        self.limit = limit;
+        self
+    }
+}
+
+impl<V: SelectValue> Serialize for SortedKeys<'_, V> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if let (Some((offset, count)), SelectValueType::Array) = (self.limit, self.value.get_type())
+        {
+            let len = self.value.len().unwrap().saturating_sub(offset).min(count);
+            let mut seq = serializer.serialize_seq(Some(len))?;
+            for v in self.value.values().unwrap().skip(offset).take(count) {
+                seq.serialize_element(&SortedKeys::new(v, self.sort_keys))?;
+            }
+            return seq.end();
+        }
+        if !self.sort_keys {
+            return self.value.serialize(serializer);
+        }
+        match self.value.get_type() {
+            SelectValueType::Object => {
+                let mut items: Vec<(&str, &V)> = self.value.items().unwrap().collect();
+                items.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+                let mut map = serializer.serialize_map(Some(items.len()))?;
+                for (k, v) in items {
+                    map.serialize_entry(k, &SortedKeys::new(v, true))?;
+                }
+                map.end()
+            }
+            SelectValueType::Array => {
+                let mut seq = serializer.serialize_seq(self.value.len())?;
+                for v in self.value.values().unwrap() {
+                    seq.serialize_element(&SortedKeys::new(v, true))?;
+                }
+                seq.end()
+            }
+            _ => self.value.serialize(serializer),
+        }
+    }
+}
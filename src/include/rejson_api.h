@@ -17,9 +17,18 @@ typedef enum JSONType {
     JSONType__EOF
 } JSONType;
 
+// The names the API is shared under, to pass to `RedisModule_GetSharedAPI`. The same struct is
+// shared under both, asking for RedisJSON_V2 fails with a RedisJSON that lacks its functions
+#define RedisJSON_V1_API_NAME "RedisJSON_V1"
+#define RedisJSON_V2_API_NAME "RedisJSON_V2"
+
 typedef const void* RedisJSON;
 typedef const void* JSONResultsIterator;
 
+// Called by `visit` for each value matching the path, with its JSONType and its JSON
+// serialization of `len` bytes, only valid during the call. Return non-zero to stop visiting
+typedef int (*JSONVisitor)(JSONType type, const char *json, size_t len, void *privdata);
+
 typedef struct RedisJSONAPI_V1 {
   /* RedisJSONKey functions */
   RedisJSON (*openKey)(RedisModuleCtx *ctx, RedisModuleString *key_name);
@@ -63,6 +72,22 @@ typedef struct RedisJSONAPI_V1 {
   // Return 1 if type of key is JSON
   int (*isJSON)(RedisModuleKey *redis_key);
 
+  /* RedisJSON_V2 functions, they are only added after the existing ones */
+
+  // Return the JSON array of the values matching `path` in the JSON value of the key named `key`
+  // The caller gains ownership of `str`
+  int (*getJSONFromKey)(RedisModuleCtx *ctx, const char *key, const char *path,
+                        RedisModuleString **str);
+
+  // Call `visitor` with each value matching `path`, in document order
+  int (*visit)(RedisJSON json, const char *path, JSONVisitor visitor, void *privdata);
+
+  // Return the JSONType of the first value matching `path`
+  int (*getTypeAt)(RedisJSON json, const char *path, JSONType *type);
+
+  // Return the length of the first String/Array/Object matching `path`
+  int (*getLenAt)(RedisJSON json, const char *path, size_t *count);
+
 } RedisJSONAPI_V1;
 
 #ifdef __cplusplus
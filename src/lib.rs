@@ -1,12 +1,13 @@
 use redis_module::native_types::RedisType;
-use redis_module::raw::RedisModuleTypeMethods;
+use redis_module::raw::{self, RedisModuleTypeMethods};
 
 #[cfg(not(feature = "as-library"))]
 use crate::c_api::{
     get_llapi_ctx, json_api_free_iter, json_api_get, json_api_get_at, json_api_get_boolean,
-    json_api_get_double, json_api_get_int, json_api_get_json, json_api_get_len,
-    json_api_get_string, json_api_get_type, json_api_is_json, json_api_len, json_api_next,
-    json_api_open_key_internal, LLAPI_CTX,
+    json_api_get_double, json_api_get_int, json_api_get_json, json_api_get_json_from_key,
+    json_api_get_len, json_api_get_len_at, json_api_get_string, json_api_get_type,
+    json_api_get_type_at, json_api_is_json, json_api_len, json_api_next,
+    json_api_open_key_internal, json_api_visit, JSONVisitor, LLAPI_CTX,
 };
 
 #[cfg(not(feature = "as-library"))]
@@ -15,18 +16,33 @@ use redis_module::Status;
 #[cfg(not(feature = "as-library"))]
 use redis_module::{Context, RedisResult};
 
+pub mod acl;
 mod array_index;
 mod backward;
 pub mod c_api;
+mod cache;
 pub mod commands;
+mod compress;
+pub mod config;
+mod diff;
 pub mod error;
+mod flatten;
 mod formatter;
 pub mod manager;
 mod nodevisitor;
+mod path_function;
+mod pointer;
 pub mod redisjson;
+mod resp;
+mod schema;
+mod shared;
 
 use crate::redisjson::Format;
-pub const REDIS_JSON_TYPE_VERSION: i32 = 3;
+pub const REDIS_JSON_TYPE_VERSION: i32 = 4;
+// Version of the auxiliary data saved after the keys, since encoding version 4
+pub const REDIS_JSON_AUX_VERSION: u64 = 1;
+// Latest version of the C API shared with other modules, see c_api.rs
+pub const REDIS_JSON_API_VERSION: i32 = 2;
 
 pub static REDIS_JSON_TYPE: RedisType = RedisType::new(
     "ReJSON-RL",
@@ -39,23 +55,48 @@ pub static REDIS_JSON_TYPE: RedisType = RedisType::new(
         aof_rewrite: None, // TODO add support
         free: Some(redisjson::type_methods::free),
 
+        mem_usage: Some(redisjson::type_methods::mem_usage),
         // Currently unused by Redis
-        mem_usage: None,
         digest: None,
 
         // Auxiliary data (v2)
         aux_load: Some(redisjson::type_methods::aux_load),
-        aux_save: None,
-        aux_save_triggers: 0,
+        aux_save: Some(redisjson::type_methods::aux_save),
+        aux_save_triggers: raw::Aux::After as i32,
 
         free_effort: None,
         unlink: None,
-        copy: None,
+        copy: Some(redisjson::type_methods::copy),
         defrag: None,
     },
 );
 /////////////////////////////////////////////////////
 
+/// Creates the commands of a `redis_module!` command table, returning from the enclosing function
+/// when one can not be created, and puts them in the `json` ACL category
+#[doc(hidden)]
+#[macro_export]
+macro_rules! json_commands {
+    (
+        $ctx:expr,
+        [
+            $([
+                $name:expr,
+                $command:expr,
+                $flags:expr,
+                $firstkey:expr,
+                $lastkey:expr,
+                $keystep:expr
+            ]),* $(,)*
+        ]
+    ) => {{
+        $(
+            redis_command!($ctx.ctx, $name, $command, $flags, $firstkey, $lastkey, $keystep);
+        )*
+        $crate::acl::register($ctx, &[$(($name, $flags)),*])
+    }};
+}
+
 #[macro_export]
 macro_rules! redis_json_module_create {(
         data_types: [
@@ -78,7 +119,7 @@ macro_rules! redis_json_module_create {(
         use libc::size_t;
 
         ///
-        /// JSON.DEL <key> [path]
+        /// JSON.DEL <key> [path ...]
         ///
         fn json_del(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
             $pre_command_function_expr(ctx, &args);
@@ -92,9 +133,21 @@ macro_rules! redis_json_module_create {(
 
         ///
         /// JSON.GET <key>
+        ///         [NOESCAPE]
         ///         [INDENT indentation-string]
+        ///         [INDENTSIZE spaces]
         ///         [NEWLINE line-break-string]
         ///         [SPACE space-string]
+        ///         [SORTKEYS]
+        ///         [PRETTY]
+        ///         [REQUIRE]
+        ///         [PRECISION digits]
+        ///         [NUMFORMAT FIXED | SCI | AUTO]
+        ///         [ROOTNAME name]
+        ///         [LIMIT offset count]
+        ///         [COMPRESS GZIP]
+        ///         [FORMAT JSON | NDJSON | BSON | RESP]
+        ///         [DEFAULT json]
         ///         [path ...]
         ///
         /// TODO add support for multi path
@@ -109,7 +162,7 @@ macro_rules! redis_json_module_create {(
         }
 
         ///
-        /// JSON.SET <key> <path> <json> [NX | XX | FORMAT <format>]
+        /// JSON.SET <key> <path> <json> [NX | XX | GT | LT | FORMAT <format> | CREATEPATH | GET | COMPRESS GZIP]
         ///
         fn json_set(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
             $pre_command_function_expr(ctx, &args);
@@ -123,6 +176,13 @@ macro_rules! redis_json_module_create {(
 
         ///
         /// JSON.MGET <key> [key ...] <path>
+        ///         [INDENT indentation-string]
+        ///         [NEWLINE line-break-string]
+        ///         [SPACE space-string]
+        ///         [FORMAT format]
+        ///         [SORTKEYS]
+        ///         [PRETTY]
+        ///         [path ...]
         ///
         fn json_mget(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
             $pre_command_function_expr(ctx, &args);
@@ -135,7 +195,20 @@ macro_rules! redis_json_module_create {(
         }
 
         ///
-        /// JSON.STRLEN <key> [path]
+        /// JSON.STRREPLACE <key> <path> <search> <replacement> [COUNT n]
+        ///
+        fn json_str_replace(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_str_replace(mngr, ctx, args),
+                None => commands::command_json_str_replace(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.STRLEN <key> [path] [BYTES]
         ///
         fn json_str_len(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
             $pre_command_function_expr(ctx, &args);
@@ -148,7 +221,7 @@ macro_rules! redis_json_module_create {(
         }
 
         ///
-        /// JSON.TYPE <key> [path]
+        /// JSON.TYPE <key> [path] [NUMERIC]
         ///
         fn json_type(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
             $pre_command_function_expr(ctx, &args);
@@ -199,6 +272,58 @@ macro_rules! redis_json_module_create {(
             }
         }
 
+        ///
+        /// JSON.NUMDIVBY <key> <path> <number>
+        ///
+        fn json_num_divby(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_num_divby(mngr, ctx, args),
+                None => commands::command_json_num_divby(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.NUMMODBY <key> <path> <number>
+        ///
+        fn json_num_modby(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_num_modby(mngr, ctx, args),
+                None => commands::command_json_num_modby(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.NUMMINBY <key> <path> <number>
+        ///
+        fn json_num_minby(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_num_minby(mngr, ctx, args),
+                None => commands::command_json_num_minby(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.NUMMAXBY <key> <path> <number>
+        ///
+        fn json_num_maxby(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_num_maxby(mngr, ctx, args),
+                None => commands::command_json_num_maxby(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
         //
         /// JSON.TOGGLE <key> <path>
         fn json_bool_toggle(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
@@ -212,7 +337,7 @@ macro_rules! redis_json_module_create {(
         }
 
         ///
-        /// JSON.STRAPPEND <key> [path] <json-string>
+        /// JSON.STRAPPEND <key> [path] <json-string> [json-string ...] [BYTES]
         ///
         fn json_str_append(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
             $pre_command_function_expr(ctx, &args);
@@ -225,7 +350,7 @@ macro_rules! redis_json_module_create {(
         }
 
         ///
-        /// JSON.ARRAPPEND <key> <path> <json> [json ...]
+        /// JSON.ARRAPPEND <key> <path> [CREATE] <json> [json ...]
         ///
         fn json_arr_append(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
             $pre_command_function_expr(ctx, &args);
@@ -238,7 +363,20 @@ macro_rules! redis_json_module_create {(
         }
 
         ///
-        /// JSON.ARRINDEX <key> <path> <json-scalar> [start [stop]]
+        /// JSON.ARRAPPENDUNIQUE <key> <path> <json> [json ...]
+        ///
+        fn json_arr_append_unique(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_arr_append_unique(mngr, ctx, args),
+                None => commands::command_json_arr_append_unique(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.ARRINDEX <key> <path> <json-value> [start [stop]]
         ///
         /// scalar - number, string, Boolean (true or false), or null
         ///
@@ -252,6 +390,19 @@ macro_rules! redis_json_module_create {(
             }
         }
 
+        ///
+        /// JSON.ARRINDEXALL <key> <path> <json-value> [start [stop]]
+        ///
+        fn json_arr_index_all(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_arr_index_all(mngr, ctx, args),
+                None => commands::command_json_arr_index_all(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
         ///
         /// JSON.ARRINSERT <key> <path> <index> <json> [json ...]
         ///
@@ -303,6 +454,19 @@ macro_rules! redis_json_module_create {(
             }
         }
 
+        ///
+        /// JSON.ARRSLICE <key> <path> <start> <stop> [step]
+        ///
+        fn json_arr_slice(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_arr_slice(mngr, ctx, args),
+                None => commands::command_json_arr_slice(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
         ///
         /// JSON.OBJKEYS <key> [path]
         ///
@@ -316,6 +480,45 @@ macro_rules! redis_json_module_create {(
             }
         }
 
+        ///
+        /// JSON.OBJVALUES <key> [path]
+        ///
+        fn json_obj_values(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_obj_values(mngr, ctx, args),
+                None => commands::command_json_obj_values(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.OBJENTRIES <key> [path]
+        ///
+        fn json_obj_entries(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_obj_entries(mngr, ctx, args),
+                None => commands::command_json_obj_entries(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.ENTRIES <key> <path> <cursor> [COUNT count]
+        ///
+        fn json_entries(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_entries(mngr, ctx, args),
+                None => commands::command_json_entries(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
         ///
         /// JSON.OBJLEN <key> [path]
         ///
@@ -330,7 +533,7 @@ macro_rules! redis_json_module_create {(
         }
 
         ///
-        /// JSON.CLEAR <key> [path ...]
+        /// JSON.CLEAR <key> [path ...] [STRINGS]
         ///
         fn json_clear(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
             $pre_command_function_expr(ctx, &args);
@@ -342,11 +545,26 @@ macro_rules! redis_json_module_create {(
             }
         }
 
+        ///
+        /// JSON.MEMORY <key> [path ...]
+        ///
+        fn json_memory(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_memory(mngr, ctx, args),
+                None => commands::command_json_memory(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
         ///
         /// JSON.DEBUG <subcommand & arguments>
         ///
         /// subcommands:
-        /// MEMORY <key> [path]
+        /// MEMORY <key> [path ...]
+        /// KEYSPACE <key> [path]
+        /// VERSION <key>
         /// HELP
         ///
         fn json_debug(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
@@ -360,7 +578,7 @@ macro_rules! redis_json_module_create {(
         }
 
         ///
-        /// JSON.RESP <key> [path]
+        /// JSON.RESP <key> [path] [CANONICAL]
         ///
         fn json_resp(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
             $pre_command_function_expr(ctx, &args);
@@ -372,6 +590,139 @@ macro_rules! redis_json_module_create {(
             }
         }
 
+        ///
+        /// JSON.POINTER <key> <pointer>
+        ///
+        fn json_pointer(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_pointer(mngr, ctx, args),
+                None => commands::command_json_pointer(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.FLATTEN <key> [path] [SEPARATOR separator]
+        ///
+        fn json_flatten(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_flatten(mngr, ctx, args),
+                None => commands::command_json_flatten(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.KEYS <key> [path] [NOTATION JSONPATH | POINTER]
+        ///
+        fn json_keys(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_keys(mngr, ctx, args),
+                None => commands::command_json_keys(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.UNFLATTEN <key> <path> <flat-json> [SEPARATOR separator]
+        ///
+        fn json_unflatten(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_unflatten(mngr, ctx, args),
+                None => commands::command_json_unflatten(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.COPYPATH <key> <from-path> <to-path> [CREATE]
+        ///
+        fn json_copy_path(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_copy_path(mngr, ctx, args),
+                None => commands::command_json_copy_path(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.MOVEPATH <key> <from-path> <to-path>
+        ///
+        fn json_move_path(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_move_path(mngr, ctx, args),
+                None => commands::command_json_move_path(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.MULTIOP <key> <operations>
+        ///
+        fn json_multi_op(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_multi_op(mngr, ctx, args),
+                None => commands::command_json_multi_op(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.DIFF <key1> <key2> [path] [PATCH]
+        ///
+        fn json_diff(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_diff(mngr, ctx, args),
+                None => commands::command_json_diff(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.VALIDATE <key> [path] SCHEMA <schema-json> [ERRORS]
+        ///
+        fn json_validate(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_validate(mngr, ctx, args),
+                None => commands::command_json_validate(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON.VALIDATEPATH <path>
+        ///
+        fn json_validate_path(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_validate_path(mngr, ctx, args),
+                None => commands::command_json_validate_path(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
+        ///
+        /// JSON._CACHEINFO
+        ///
         fn json_cache_info(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
             $pre_command_function_expr(ctx, &args);
             let m = $get_manager_expr;
@@ -382,6 +733,9 @@ macro_rules! redis_json_module_create {(
             }
         }
 
+        ///
+        /// JSON._CACHEINIT [size]
+        ///
         fn json_cache_init(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
             $pre_command_function_expr(ctx, &args);
             let m = $get_manager_expr;
@@ -392,14 +746,98 @@ macro_rules! redis_json_module_create {(
             }
         }
 
+        ///
+        /// JSON.CONFIG GET <name|*>
+        /// JSON.CONFIG SET <name> <value>
+        ///
+        fn json_config(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+            $pre_command_function_expr(ctx, &args);
+            let m = $get_manager_expr;
+            match m {
+                Some(mngr) => commands::command_json_config(mngr, ctx, args),
+                None => commands::command_json_config(manager::RedisJsonKeyManager{phantom:PhantomData}, ctx, args),
+
+            }
+        }
+
         redis_json_module_export_shared_api! {
             get_manage:$get_manager_expr,
             pre_command_function: $pre_command_function_expr,
         }
 
         fn intialize(ctx: &Context, args: &Vec<RedisString>) -> Status {
+            if $crate::config::load(ctx, args) == Status::Err
+                || $crate::config::register(ctx) == Status::Err
+            {
+                return Status::Err;
+            }
             export_shared_api(ctx);
-            $init_func(ctx, args)
+            if $init_func(ctx, args) == Status::Err
+                || create_commands(ctx) != Status::Ok as c_int
+            {
+                return Status::Err;
+            }
+            Status::Ok
+        }
+
+        /// The commands are created when the module is initialized rather than by
+        /// `redis_module!`, which creates them once it is, so they can be put in the `json` ACL
+        /// category while the module is loaded
+        fn create_commands(ctx: &Context) -> c_int {
+            use std::ffi::CString;
+            $crate::json_commands!(
+                ctx,
+                [
+                    ["json.del", json_del, "write", 1,1,1],
+                    ["json.get", json_get, "readonly", 1,1,1],
+                    ["json.mget", json_mget, "readonly", 1,1,1],
+                    ["json.set", json_set, "write deny-oom", 1,1,1],
+                    ["json.type", json_type, "readonly", 1,1,1],
+                    ["json.numincrby", json_num_incrby, "write", 1,1,1],
+                    ["json.toggle", json_bool_toggle, "write deny-oom", 1,1,1],
+                    ["json.nummultby", json_num_multby, "write", 1,1,1],
+                    ["json.numpowby", json_num_powby, "write", 1,1,1],
+                    ["json.numdivby", json_num_divby, "write", 1,1,1],
+                    ["json.nummodby", json_num_modby, "write", 1,1,1],
+                    ["json.numminby", json_num_minby, "write", 1,1,1],
+                    ["json.nummaxby", json_num_maxby, "write", 1,1,1],
+                    ["json.strappend", json_str_append, "write deny-oom", 1,1,1],
+                    ["json.strreplace", json_str_replace, "write deny-oom", 1,1,1],
+                    ["json.strlen", json_str_len, "readonly", 1,1,1],
+                    ["json.arrappend", json_arr_append, "write deny-oom", 1,1,1],
+                    ["json.arrappendunique", json_arr_append_unique, "write deny-oom", 1,1,1],
+                    ["json.arrindex", json_arr_index, "readonly", 1,1,1],
+                    ["json.arrindexall", json_arr_index_all, "readonly", 1,1,1],
+                    ["json.arrinsert", json_arr_insert, "write deny-oom", 1,1,1],
+                    ["json.arrlen", json_arr_len, "readonly", 1,1,1],
+                    ["json.arrpop", json_arr_pop, "write", 1,1,1],
+                    ["json.arrtrim", json_arr_trim, "write", 1,1,1],
+                    ["json.arrslice", json_arr_slice, "readonly", 1,1,1],
+                    ["json.objkeys", json_obj_keys, "readonly", 1,1,1],
+                    ["json.objvalues", json_obj_values, "readonly", 1,1,1],
+                    ["json.objentries", json_obj_entries, "readonly", 1,1,1],
+                    ["json.entries", json_entries, "readonly", 1,1,1],
+                    ["json.objlen", json_obj_len, "readonly", 1,1,1],
+                    ["json.clear", json_clear, "write", 1,1,1],
+                    ["json.memory", json_memory, "readonly", 1,1,1],
+                    ["json.debug", json_debug, "readonly", 1,1,1],
+                    ["json.forget", json_del, "write", 1,1,1],
+                    ["json.resp", json_resp, "readonly", 1,1,1],
+                    ["json.pointer", json_pointer, "readonly", 1,1,1],
+                    ["json.flatten", json_flatten, "readonly", 1,1,1],
+                    ["json.keys", json_keys, "readonly", 1,1,1],
+                    ["json.unflatten", json_unflatten, "write deny-oom", 1,1,1],
+                    ["json.copypath", json_copy_path, "write deny-oom", 1,1,1],
+                    ["json.movepath", json_move_path, "write deny-oom", 1,1,1],
+                    ["json.multiop", json_multi_op, "write deny-oom", 1,1,1],
+                    ["json.diff", json_diff, "readonly", 1,2,1],
+                    ["json.validate", json_validate, "readonly", 1,1,1],
+                    ["json.validatepath", json_validate_path, "readonly", 0,0,0],
+                    ["json._cacheinfo", json_cache_info, "readonly", 0,0,0],
+                    ["json._cacheinit", json_cache_init, "write", 0,0,0],
+                    ["json.config", json_config, "admin", 0,0,0],
+                ]
+            ) as c_int
         }
 
         redis_module! {
@@ -407,33 +845,7 @@ macro_rules! redis_json_module_create {(
             version: $version,
             data_types: [$($data_type,)*],
             init: intialize,
-            commands: [
-                ["json.del", json_del, "write", 1,1,1],
-                ["json.get", json_get, "readonly", 1,1,1],
-                ["json.mget", json_mget, "readonly", 1,1,1],
-                ["json.set", json_set, "write deny-oom", 1,1,1],
-                ["json.type", json_type, "readonly", 1,1,1],
-                ["json.numincrby", json_num_incrby, "write", 1,1,1],
-                ["json.toggle", json_bool_toggle, "write deny-oom", 1,1,1],
-                ["json.nummultby", json_num_multby, "write", 1,1,1],
-                ["json.numpowby", json_num_powby, "write", 1,1,1],
-                ["json.strappend", json_str_append, "write deny-oom", 1,1,1],
-                ["json.strlen", json_str_len, "readonly", 1,1,1],
-                ["json.arrappend", json_arr_append, "write deny-oom", 1,1,1],
-                ["json.arrindex", json_arr_index, "readonly", 1,1,1],
-                ["json.arrinsert", json_arr_insert, "write deny-oom", 1,1,1],
-                ["json.arrlen", json_arr_len, "readonly", 1,1,1],
-                ["json.arrpop", json_arr_pop, "write", 1,1,1],
-                ["json.arrtrim", json_arr_trim, "write", 1,1,1],
-                ["json.objkeys", json_obj_keys, "readonly", 1,1,1],
-                ["json.objlen", json_obj_len, "readonly", 1,1,1],
-                ["json.clear", json_clear, "write", 1,1,1],
-                ["json.debug", json_debug, "readonly", 1,1,1],
-                ["json.forget", json_del, "write", 1,1,1],
-                ["json.resp", json_resp, "readonly", 1,1,1],
-                ["json._cacheinfo", json_cache_info, "readonly", 1,1,1],
-                ["json._cacheinit", json_cache_init, "write", 1,1,1],
-            ],
+            commands: [],
         }
     }
 }
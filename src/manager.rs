@@ -1,5 +1,4 @@
 use jsonpath_lib::select::select_value::SelectValue;
-use serde_json::map::Entry;
 use serde_json::{Number, Value};
 
 use redis_module::key::{verify_type, RedisKey, RedisKeyWritable};
@@ -7,19 +6,19 @@ use redis_module::raw::{RedisModuleKey, Status};
 use redis_module::rediserror::RedisError;
 use redis_module::{Context, NotifyEvent, RedisString};
 
+use std::cmp::Ordering;
 use std::marker::PhantomData;
 
-use crate::redisjson::RedisJSON;
+use crate::redisjson::{RedisJSON, Shape};
+use crate::resp;
 use crate::Format;
 use crate::REDIS_JSON_TYPE;
 
 use crate::error::Error;
-use bson::decode_document;
-use std::io::Cursor;
 
 use crate::array_index::ArrayIndex;
-
-use std::mem;
+use crate::config;
+use crate::pointer;
 
 pub struct SetUpdateInfo {
     pub path: Vec<String>,
@@ -37,6 +36,8 @@ pub enum UpdateInfo {
 
 pub trait ReadHolder<V: SelectValue> {
     fn get_value(&self) -> Result<Option<&V>, RedisError>;
+    /// The encoding version of the RDB the document was loaded from, see `RedisJSON::encver`
+    fn get_encver(&self) -> Result<Option<i32>, RedisError>;
 }
 
 pub trait WriteHolder<O: Clone, V: SelectValue> {
@@ -45,12 +46,32 @@ pub trait WriteHolder<O: Clone, V: SelectValue> {
     fn set_value(&mut self, path: Vec<String>, v: O) -> Result<bool, RedisError>;
     fn dict_add(&mut self, path: Vec<String>, key: &str, v: O) -> Result<bool, RedisError>;
     fn delete_path(&mut self, path: Vec<String>) -> Result<bool, RedisError>;
-    fn incr_by(&mut self, path: Vec<String>, num: &str) -> Result<Number, RedisError>;
-    fn mult_by(&mut self, path: Vec<String>, num: &str) -> Result<Number, RedisError>;
-    fn pow_by(&mut self, path: Vec<String>, num: &str) -> Result<Number, RedisError>;
+    fn incr_by(&mut self, path: Vec<String>, num: &Number) -> Result<Number, RedisError>;
+    fn mult_by(&mut self, path: Vec<String>, num: &Number) -> Result<Number, RedisError>;
+    fn pow_by(&mut self, path: Vec<String>, num: &Number) -> Result<Number, RedisError>;
+    fn div_by(&mut self, path: Vec<String>, num: &Number) -> Result<Number, RedisError>;
+    fn mod_by(&mut self, path: Vec<String>, num: &Number) -> Result<Number, RedisError>;
+    fn min_by(&mut self, path: Vec<String>, num: &Number) -> Result<Number, RedisError>;
+    fn max_by(&mut self, path: Vec<String>, num: &Number) -> Result<Number, RedisError>;
     fn bool_toggle(&mut self, path: Vec<String>) -> Result<bool, RedisError>;
-    fn str_append(&mut self, path: Vec<String>, val: String) -> Result<usize, RedisError>;
+    /// Returns the new length, in bytes with `bytes` and in Unicode scalar values otherwise
+    fn str_append(
+        &mut self,
+        path: Vec<String>,
+        val: String,
+        bytes: bool,
+    ) -> Result<usize, RedisError>;
+    /// Returns the new length in Unicode scalar values and the number of occurrences replaced
+    fn str_replace(
+        &mut self,
+        path: Vec<String>,
+        search: &str,
+        replacement: &str,
+        count: Option<usize>,
+    ) -> Result<(usize, usize), RedisError>;
     fn arr_append(&mut self, path: Vec<String>, args: Vec<O>) -> Result<usize, RedisError>;
+    /// Appends the items that are not in the array yet, each one once
+    fn arr_append_unique(&mut self, path: Vec<String>, args: Vec<O>) -> Result<usize, RedisError>;
     fn arr_insert(
         &mut self,
         path: Vec<String>,
@@ -59,8 +80,30 @@ pub trait WriteHolder<O: Clone, V: SelectValue> {
     ) -> Result<usize, RedisError>;
     fn arr_pop(&mut self, path: Vec<String>, index: i64) -> Result<Option<String>, RedisError>;
     fn arr_trim(&mut self, path: Vec<String>, start: i64, stop: i64) -> Result<usize, RedisError>;
-    fn clear(&mut self, path: Vec<String>) -> Result<usize, RedisError>;
+    /// Empties the array, object or string (with `strings`) at `path`, or sets the number to 0.
+    /// Booleans and nulls are left as they are. Returns 1 if the value changed, 0 otherwise
+    fn clear(&mut self, path: Vec<String>, strings: bool) -> Result<usize, RedisError>;
     fn apply_changes(&mut self, ctx: &Context, command: &str) -> Result<(), RedisError>;
+    /// A copy of the document, `None` when the key does not exist, to put it back with `restore`
+    fn snapshot(&mut self) -> Result<Option<O>, RedisError>;
+    /// Puts back a document taken with `snapshot` as it was, deleting the key for `None`. It is
+    /// not checked again, so it is restored even when the configuration changed since
+    fn restore(&mut self, doc: Option<O>) -> Result<(), RedisError>;
+
+    /// Same as `apply_changes`, but with the NOTIFY_PATHS configuration the event is named
+    /// `<command>:<path>` so subscribers can tell which part of the document changed
+    fn apply_changes_at(
+        &mut self,
+        ctx: &Context,
+        command: &str,
+        path: &str,
+    ) -> Result<(), RedisError> {
+        if config::notify_paths() {
+            self.apply_changes(ctx, &format!("{}:{}", command, path))
+        } else {
+            self.apply_changes(ctx, command)
+        }
+    }
 }
 
 pub trait Manager {
@@ -84,18 +127,104 @@ pub trait Manager {
         key: RedisString,
     ) -> Result<Self::WriteHolder, RedisError>;
     fn from_str(&self, val: &str, format: Format) -> Result<Self::O, Error>;
+    /// Same as `from_str`, for formats that are binary such as BSON
+    fn from_bytes(&self, val: &[u8], format: Format) -> Result<Self::O, Error>;
     fn get_memory(&self, v: &Self::V) -> Result<usize, RedisError>;
+    fn get_shape(&self, v: &Self::V) -> Shape;
     fn is_json(&self, key: *mut RedisModuleKey) -> Result<bool, RedisError>;
 }
 
+/// The error core Redis replies when a command is used on a key holding another type
+const WRONGTYPE_ERR: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+/// Opening a key only fails when it holds another type than JSON
+fn wrong_type(_e: RedisError) -> RedisError {
+    RedisError::Str(WRONGTYPE_ERR)
+}
+
 fn err_json(value: &Value, expected_value: &'static str) -> Error {
-    Error::from(format!(
+    Error::WrongType(format!(
         "ERR wrong type of path value - expected {} but found {}",
         expected_value,
         RedisJSON::value_name(value)
     ))
 }
 
+/// Rejects a zero divisor, which would result in NaN or Infinity (not valid JSON) or a panic
+fn check_divisor(num: &Number) -> Result<(), Error> {
+    if num.as_f64() == Some(0.0) {
+        Err("ERR division by zero".into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Verifies that `value` does not exceed the maximum nesting depth when nested in `base` containers
+fn check_depth(base: usize, value: &Value) -> Result<(), Error> {
+    let max_depth = config::max_depth();
+    let mut stack = vec![(base, value)];
+    while let Some((depth, v)) = stack.pop() {
+        let children: Box<dyn Iterator<Item = &Value>> = match v {
+            Value::Array(arr) => Box::new(arr.iter()),
+            Value::Object(obj) => Box::new(obj.values()),
+            _ => continue,
+        };
+        if depth >= max_depth {
+            return Err(format!("ERR maximum nesting depth of {} exceeded", max_depth).into());
+        }
+        stack.extend(children.map(|c| (depth + 1, c)));
+    }
+    Ok(())
+}
+
+/// Counts the bytes written to it, to measure a serialized size without building the string
+struct ByteCounter(usize);
+
+impl std::io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The number of bytes `value` is serialized in as compact JSON
+fn serialized_size<T: serde::Serialize + ?Sized>(value: &T) -> usize {
+    let mut counter = ByteCounter(0);
+    // Values and strings always serialize, and the counter never fails
+    let _ = serde_json::to_writer(&mut counter, value);
+    counter.0
+}
+
+/// The number of bytes the array `arr` grows by when `items` are added to it, with the commas
+/// that separate them
+fn appended_size<'v, I: Iterator<Item = &'v Value>>(arr: &[Value], items: I) -> usize {
+    let (count, size) = items.fold((0, 0), |(count, size), item| {
+        (count + 1, size + serialized_size(item))
+    });
+    if count == 0 {
+        0
+    } else {
+        size + count - usize::from(arr.is_empty())
+    }
+}
+
+/// Fails when a document of `size` bytes exceeds the MAX_DOC_SIZE configuration
+fn check_doc_size(size: usize) -> Result<(), RedisError> {
+    let limit = config::max_doc_size();
+    if limit > 0 && size > limit {
+        Err(RedisError::String(format!(
+            "ERR document size of {} bytes exceeds the limit of {} bytes",
+            size, limit
+        )))
+    } else {
+        Ok(())
+    }
+}
+
 pub struct KeyHolderWrite<'a> {
     key: RedisKeyWritable,
     key_name: RedisString,
@@ -116,12 +245,14 @@ fn update<F: FnMut(Value) -> Result<Option<Value>, Error>>(
         let target_opt = match *target_once {
             Value::Object(ref mut map) => {
                 if is_last {
-                    if let Entry::Occupied(mut e) = map.entry(token) {
-                        let v = e.insert(Value::Null);
-                        if let Some(res) = (func)(v)? {
-                            e.insert(res);
-                        } else {
-                            e.remove();
+                    if let Some(v) = map.get_mut(token) {
+                        match (func)(v.take())? {
+                            Some(res) => *v = res,
+                            None => {
+                                // Shifting the members after it rather than moving the last
+                                // member to its place, so the member order is kept
+                                map.shift_remove(token);
+                            }
                         }
                     }
                     return Ok(());
@@ -131,11 +262,14 @@ fn update<F: FnMut(Value) -> Result<Option<Value>, Error>>(
             Value::Array(ref mut vec) => {
                 if let Ok(x) = token.parse::<usize>() {
                     if is_last {
-                        let v = std::mem::replace(&mut vec[x], Value::Null);
-                        if let Some(res) = (func)(v)? {
-                            vec[x] = res;
-                        } else {
-                            vec.remove(x);
+                        // An index past the end is skipped, like a missing member
+                        if let Some(target) = vec.get_mut(x) {
+                            match (func)(target.take())? {
+                                Some(res) => *target = res,
+                                None => {
+                                    vec.remove(x);
+                                }
+                            }
                         }
                         return Ok(());
                     }
@@ -157,7 +291,55 @@ fn update<F: FnMut(Value) -> Result<Option<Value>, Error>>(
     Ok(())
 }
 
+/// Calls `func` on the value at `path` to change it in place. Unlike with `update` the value
+/// is not moved out of the document, so it is left as is when `func` fails
+fn update_in_place<F: FnMut(&mut Value) -> Result<(), Error>>(
+    path: &[String],
+    root: &mut Value,
+    mut func: F,
+) -> Result<(), Error> {
+    let mut target = root;
+    for token in path {
+        let next = match target {
+            Value::Object(map) => map.get_mut(token),
+            Value::Array(vec) => match token.parse::<usize>() {
+                Ok(x) => vec.get_mut(x),
+                Err(_) => None,
+            },
+            _ => None,
+        };
+        match next {
+            Some(t) => target = t,
+            None => return Ok(()),
+        }
+    }
+    (func)(target)
+}
+
 impl<'a> KeyHolderWrite<'a> {
+    /// Checks the MAX_DOC_SIZE configuration before the value at `path` is changed, with
+    /// `new_size` giving the size it is serialized in once changed. Every write that can grow
+    /// the document goes through it, and nothing is serialized when there is no limit
+    fn check_size<F>(&mut self, path: &[String], new_size: F) -> Result<(), RedisError>
+    where
+        F: FnOnce(&Value) -> usize,
+    {
+        if config::max_doc_size() == 0 {
+            return Ok(());
+        }
+        let root = match self.get_value()? {
+            Some(root) => root,
+            None => return Ok(()),
+        };
+        match pointer::resolve(&*root, path) {
+            Some(target) => {
+                let size = serialized_size(&*root) - serialized_size(target) + new_size(target);
+                check_doc_size(size)
+            }
+            None => Ok(()),
+        }
+    }
+
     fn do_op<F>(&mut self, paths: Vec<String>, mut op_fun: F) -> Result<(), RedisError>
     where
         F: FnMut(Value) -> Result<Option<Value>, Error>,
@@ -174,10 +356,18 @@ impl<'a> KeyHolderWrite<'a> {
         Ok(())
     }
 
+    fn do_op_in_place<F>(&mut self, paths: Vec<String>, op_fun: F) -> Result<(), RedisError>
+    where
+        F: FnMut(&mut Value) -> Result<(), Error>,
+    {
+        update_in_place(&paths, self.get_value()?.unwrap(), op_fun)?;
+        Ok(())
+    }
+
     fn do_num_op<F1, F2>(
         &mut self,
         path: Vec<String>,
-        num: &str,
+        in_value: &Number,
         mut op1_fun: F1,
         mut op2_fun: F2,
     ) -> Result<Number, RedisError>
@@ -185,36 +375,77 @@ impl<'a> KeyHolderWrite<'a> {
         F1: FnMut(i64, i64) -> i64,
         F2: FnMut(f64, f64) -> f64,
     {
-        let in_value = &serde_json::from_str(num)?;
-        if let Value::Number(in_value) = in_value {
-            let mut res = None;
-            self.do_op(path, |v| {
-                let num_res = match (v.as_i64(), in_value.as_i64()) {
-                    (Some(num1), Some(num2)) => ((op1_fun)(num1, num2)).into(),
-                    _ => {
-                        let num1 = v.as_f64().unwrap();
-                        let num2 = in_value.as_f64().unwrap();
-                        Number::from_f64((op2_fun)(num1, num2)).unwrap()
+        let mut res = None;
+        let mut not_finite = false;
+        // The number is changed where it is, so nothing around it is moved or rebuilt
+        self.do_op_in_place(path, |v| {
+            let num_res = match (v.as_i64(), in_value.as_i64()) {
+                (Some(num1), Some(num2)) => ((op1_fun)(num1, num2)).into(),
+                _ => {
+                    let num1 = v.as_f64().unwrap();
+                    let num2 = in_value.as_f64().unwrap();
+                    match Number::from_f64((op2_fun)(num1, num2)) {
+                        Some(n) => n,
+                        // NaN and infinity have no JSON representation, keep the old value
+                        None => {
+                            not_finite = true;
+                            return Ok(());
+                        }
                     }
-                };
-                res = Some(Value::Number(num_res));
-                Ok(res.clone())
-            })?;
-            match res {
-                None => Err(RedisError::Str("path does not exists")),
-                Some(n) => match n {
-                    Value::Number(n) => Ok(n),
-                    _ => Err(RedisError::Str("return value is not a number")),
-                },
-            }
-        } else {
-            Err(RedisError::Str("bad input number"))
+                }
+            };
+            *v = Value::Number(num_res.clone());
+            res = Some(num_res);
+            Ok(())
+        })?;
+        if not_finite {
+            return Err(RedisError::Str("ERR result is not a valid JSON number"));
         }
+        res.ok_or(RedisError::Str("path does not exists"))
+    }
+
+    /// Replaces the number with `in_value` when it is not already `kept` compared with it, the
+    /// stored number is otherwise left as it is, with its type
+    fn do_min_max_op(
+        &mut self,
+        path: Vec<String>,
+        in_value: &Number,
+        kept: Ordering,
+    ) -> Result<Number, RedisError> {
+        let mut res = None;
+        self.do_op_in_place(path, |v| {
+            let order = match (v.as_i64(), in_value.as_i64()) {
+                (Some(num1), Some(num2)) => num1.cmp(&num2),
+                _ => v
+                    .as_f64()
+                    .unwrap()
+                    .partial_cmp(&in_value.as_f64().unwrap())
+                    .unwrap_or(Ordering::Equal),
+            };
+            let num_res = match v {
+                Value::Number(n) if order == kept || order == Ordering::Equal => {
+                    res = Some(n.clone());
+                    return Ok(());
+                }
+                // An integer replaced by a float, or a float by an integer, results in a float
+                Value::Number(n) if !(n.is_i64() && in_value.is_i64()) => {
+                    Number::from_f64(in_value.as_f64().unwrap()).unwrap_or_else(|| in_value.clone())
+                }
+                _ => in_value.clone(),
+            };
+            *v = Value::Number(num_res.clone());
+            res = Some(num_res);
+            Ok(())
+        })?;
+        res.ok_or(RedisError::Str("path does not exists"))
     }
 
     fn get_json_holder(&mut self) -> Result<(), RedisError> {
         if self.val.is_none() {
-            self.val = self.key.get_value::<RedisJSON>(&REDIS_JSON_TYPE)?;
+            self.val = self
+                .key
+                .get_value::<RedisJSON>(&REDIS_JSON_TYPE)
+                .map_err(wrong_type)?;
         }
         Ok(())
     }
@@ -224,10 +455,10 @@ impl<'a> KeyHolderWrite<'a> {
             Some(inner) => {
                 self.get_json_holder()?;
                 match &mut self.val {
-                    Some(v) => v.data = inner,
+                    Some(v) => v.set_data_shared(inner),
                     None => self
                         .key
-                        .set_value(&REDIS_JSON_TYPE, RedisJSON { data: inner })?,
+                        .set_value(&REDIS_JSON_TYPE, RedisJSON::new_shared(inner))?,
                 }
             }
             None => {
@@ -254,16 +485,41 @@ impl<'a> WriteHolder<Value, Value> for KeyHolderWrite<'a> {
         Ok(())
     }
 
+    fn snapshot(&mut self) -> Result<Option<Value>, RedisError> {
+        Ok(self.get_value()?.map(|doc| doc.clone()))
+    }
+
+    fn restore(&mut self, doc: Option<Value>) -> Result<(), RedisError> {
+        match doc {
+            Some(doc) => {
+                self.get_json_holder()?;
+                match &mut self.val {
+                    Some(v) => v.set_data(doc),
+                    None => self.key.set_value(&REDIS_JSON_TYPE, RedisJSON::new(doc))?,
+                }
+            }
+            None if self.get_value()?.is_some() => self.set_root(None)?,
+            None => {}
+        }
+        Ok(())
+    }
+
     fn get_value(&mut self) -> Result<Option<&mut Value>, RedisError> {
         self.get_json_holder()?;
 
         match &mut self.val {
-            Some(v) => Ok(Some(&mut (*v).data)),
+            Some(v) => Ok(Some((*v).data_mut())),
             None => Ok(None),
         }
     }
 
     fn set_value(&mut self, path: Vec<String>, mut v: Value) -> Result<bool, RedisError> {
+        check_depth(path.len(), &v)?;
+        if path.is_empty() {
+            check_doc_size(serialized_size(&v))?;
+        } else {
+            self.check_size(&path, |_| serialized_size(&v))?;
+        }
         let mut updated = false;
         if path.is_empty() {
             // update the root
@@ -279,6 +535,18 @@ impl<'a> WriteHolder<Value, Value> for KeyHolderWrite<'a> {
     }
 
     fn dict_add(&mut self, path: Vec<String>, key: &str, mut v: Value) -> Result<bool, RedisError> {
+        check_depth(path.len() + 1, &v)?;
+        self.check_size(&path, |target| match target {
+            // The member adds its key, a colon and a comma unless it is the only one
+            Value::Object(obj) if !obj.contains_key(key) => {
+                serialized_size(target)
+                    + serialized_size(key)
+                    + 1
+                    + serialized_size(&v)
+                    + usize::from(!obj.is_empty())
+            }
+            _ => serialized_size(target),
+        })?;
         let mut updated = false;
         if path.is_empty() {
             // update the root
@@ -321,18 +589,36 @@ impl<'a> WriteHolder<Value, Value> for KeyHolderWrite<'a> {
         Ok(deleted)
     }
 
-    fn incr_by(&mut self, path: Vec<String>, num: &str) -> Result<Number, RedisError> {
+    fn incr_by(&mut self, path: Vec<String>, num: &Number) -> Result<Number, RedisError> {
         self.do_num_op(path, num, |i1, i2| i1 + i2, |f1, f2| f1 + f2)
     }
 
-    fn mult_by(&mut self, path: Vec<String>, num: &str) -> Result<Number, RedisError> {
+    fn mult_by(&mut self, path: Vec<String>, num: &Number) -> Result<Number, RedisError> {
         self.do_num_op(path, num, |i1, i2| i1 * i2, |f1, f2| f1 * f2)
     }
 
-    fn pow_by(&mut self, path: Vec<String>, num: &str) -> Result<Number, RedisError> {
+    fn pow_by(&mut self, path: Vec<String>, num: &Number) -> Result<Number, RedisError> {
         self.do_num_op(path, num, |i1, i2| i1.pow(i2 as u32), |f1, f2| f1.powf(f2))
     }
 
+    fn div_by(&mut self, path: Vec<String>, num: &Number) -> Result<Number, RedisError> {
+        check_divisor(num)?;
+        self.do_num_op(path, num, |i1, i2| i1.wrapping_div(i2), |f1, f2| f1 / f2)
+    }
+
+    fn mod_by(&mut self, path: Vec<String>, num: &Number) -> Result<Number, RedisError> {
+        check_divisor(num)?;
+        self.do_num_op(path, num, |i1, i2| i1.wrapping_rem(i2), |f1, f2| f1 % f2)
+    }
+
+    fn min_by(&mut self, path: Vec<String>, num: &Number) -> Result<Number, RedisError> {
+        self.do_min_max_op(path, num, Ordering::Less)
+    }
+
+    fn max_by(&mut self, path: Vec<String>, num: &Number) -> Result<Number, RedisError> {
+        self.do_min_max_op(path, num, Ordering::Greater)
+    }
+
     fn bool_toggle(&mut self, path: Vec<String>) -> Result<bool, RedisError> {
         let mut res = None;
         self.do_op(path, |v| {
@@ -346,13 +632,26 @@ impl<'a> WriteHolder<Value, Value> for KeyHolderWrite<'a> {
         }
     }
 
-    fn str_append(&mut self, path: Vec<String>, val: String) -> Result<usize, RedisError> {
+    fn str_append(
+        &mut self,
+        path: Vec<String>,
+        val: String,
+        bytes: bool,
+    ) -> Result<usize, RedisError> {
         let json = serde_json::from_str(&val)?;
         if let Value::String(s) = json {
+            // Escaped the same way once appended, without its quotes
+            self.check_size(&path, |target| {
+                serialized_size(target) + serialized_size(s.as_str()) - 2
+            })?;
             let mut res = None;
             self.do_op(path, |v| {
                 let new_str = [v.as_str().unwrap(), s.as_str()].concat();
-                res = Some(new_str.len());
+                res = Some(if bytes {
+                    new_str.len()
+                } else {
+                    new_str.chars().count()
+                });
                 Ok(Some(Value::String(new_str)))
             })?;
             match res {
@@ -367,18 +666,92 @@ impl<'a> WriteHolder<Value, Value> for KeyHolderWrite<'a> {
         }
     }
 
+    fn str_replace(
+        &mut self,
+        path: Vec<String>,
+        search: &str,
+        replacement: &str,
+        count: Option<usize>,
+    ) -> Result<(usize, usize), RedisError> {
+        let replace = |s: &str| match count {
+            Some(n) => s.replacen(search, replacement, n),
+            None => s.replace(search, replacement),
+        };
+        self.check_size(&path, |target| match target.as_str() {
+            Some(s) => serialized_size(replace(s).as_str()),
+            None => serialized_size(target),
+        })?;
+        let mut res = None;
+        // A string without any occurrence is left untouched
+        self.do_op_in_place(path, |v| {
+            if let Value::String(s) = v {
+                let replaced = s.matches(search).take(count.unwrap_or(usize::MAX)).count();
+                if replaced > 0 {
+                    *s = replace(s);
+                }
+                res = Some((s.chars().count(), replaced));
+            }
+            Ok(())
+        })?;
+        res.ok_or(RedisError::Str("path does not exists"))
+    }
+
     fn arr_append(&mut self, path: Vec<String>, mut args: Vec<Value>) -> Result<usize, RedisError> {
+        for v in &args {
+            check_depth(path.len() + 1, v)?;
+        }
+        self.check_size(&path, |target| match target.as_array() {
+            Some(arr) => serialized_size(target) + appended_size(arr, args.iter()),
+            None => serialized_size(target),
+        })?;
         let mut res = None;
-        self.do_op(path, |mut v| {
+        // Appended where the array is, so its elements are never moved out of the document
+        self.do_op_in_place(path, |v| {
             let arr = v.as_array_mut().unwrap();
             arr.append(&mut args);
             res = Some(arr.len());
-            Ok(Some(v))
+            Ok(())
         })?;
-        match res {
-            None => Err(RedisError::Str("path does not exists")),
-            Some(n) => Ok(n),
+        res.ok_or(RedisError::Str("path does not exists"))
+    }
+
+    fn arr_append_unique(
+        &mut self,
+        path: Vec<String>,
+        args: Vec<Value>,
+    ) -> Result<usize, RedisError> {
+        for v in &args {
+            check_depth(path.len() + 1, v)?;
         }
+        self.check_size(&path, |target| match target.as_array() {
+            Some(arr) => {
+                let mut added: Vec<&Value> = Vec::new();
+                for item in &args {
+                    if !arr.contains(item) && !added.contains(&item) {
+                        added.push(item);
+                    }
+                }
+                serialized_size(target) + appended_size(arr, added.into_iter())
+            }
+            None => serialized_size(target),
+        })?;
+        let mut res = None;
+        self.do_op_in_place(path, |v| {
+            if let Some(arr) = v.as_array_mut() {
+                // Serde's equality is the deep one of JSON.ARRINDEX: object members in any order,
+                // and integers never equal to floats
+                for item in &args {
+                    if !arr.contains(item) {
+                        arr.push(item.clone());
+                    }
+                }
+                res = Some(arr.len());
+                Ok(())
+            } else {
+                Err(err_json(v, "array"))
+            }
+        })?;
+        res.ok_or(RedisError::Str("path does not exists"))
     }
 
     fn arr_insert(
@@ -387,20 +760,30 @@ impl<'a> WriteHolder<Value, Value> for KeyHolderWrite<'a> {
         args: &Vec<Value>,
         index: i64,
     ) -> Result<usize, RedisError> {
+        for v in args {
+            check_depth(paths.len() + 1, v)?;
+        }
+        self.check_size(&paths, |target| match target.as_array() {
+            Some(arr) => serialized_size(target) + appended_size(arr, args.iter()),
+            None => serialized_size(target),
+        })?;
         let mut res = None;
-        self.do_op(paths, |mut v| {
-            // Verify legal index in bounds
-            let len = v.len().unwrap() as i64;
+        // The array is changed where it is, so it is left untouched when the index is rejected
+        self.do_op_in_place(paths, |v| {
+            let curr = v.as_array_mut().unwrap();
+            // Like Python's list.insert, a negative index counts from the end: -1 inserts before
+            // the last element, `len` appends. Unlike it, an index out of -len..=len is an error
+            let len = curr.len() as i64;
             let index = if index < 0 { len + index } else { index };
             if !(0..=len).contains(&index) {
-                return Err("ERR index out of bounds".into());
+                return Err(Error::IndexOutOfBounds(
+                    "ERR index out of bounds".to_string(),
+                ));
             }
             let index = index as usize;
-            let mut new_value = v.take();
-            let curr = new_value.as_array_mut().unwrap();
-            curr.splice(index..index, args.clone());
+            curr.splice(index..index, args.iter().cloned());
             res = Some(curr.len());
-            Ok(Some(new_value))
+            Ok(())
         })?;
         match res {
             None => Err(RedisError::Str("path does not exists")),
@@ -410,25 +793,24 @@ impl<'a> WriteHolder<Value, Value> for KeyHolderWrite<'a> {
 
     fn arr_pop(&mut self, path: Vec<String>, index: i64) -> Result<Option<String>, RedisError> {
         let mut res = None;
-        self.do_op(path, |mut v| {
-            if let Some(array) = v.as_array() {
+        self.do_op_in_place(path, |v| {
+            if let Some(array) = v.as_array_mut() {
                 if array.is_empty() {
-                    return Ok(Some(v));
+                    return Ok(());
                 }
-                // Verify legel index in bounds
+                // Verify legal index in bounds
                 let len = array.len() as i64;
-                let index = if index < 0 {
-                    0.max(len + index)
-                } else {
-                    index.min(len - 1)
-                } as usize;
+                let index = if index < 0 { len + index } else { index };
+                if !(0..len).contains(&index) {
+                    return Err(Error::IndexOutOfBounds(
+                        "ERR index out of range".to_string(),
+                    ));
+                }
 
-                let mut new_value = v.take();
-                let curr = new_value.as_array_mut().unwrap();
-                res = Some(curr.remove(index as usize));
-                Ok(Some(new_value))
+                res = Some(array.remove(index as usize));
+                Ok(())
             } else {
-                Err(err_json(&v, "array"))
+                Err(err_json(v, "array"))
             }
         })?;
         match res {
@@ -466,19 +848,28 @@ impl<'a> WriteHolder<Value, Value> for KeyHolderWrite<'a> {
         }
     }
 
-    fn clear(&mut self, path: Vec<String>) -> Result<usize, RedisError> {
+    fn clear(&mut self, path: Vec<String>, strings: bool) -> Result<usize, RedisError> {
         let mut cleared = 0;
         self.do_op(path, |v| match v {
-            Value::Object(mut obj) => {
+            // Only the values that change are counted, so clearing twice counts nothing
+            Value::Object(mut obj) if !obj.is_empty() => {
                 obj.clear();
                 cleared += 1;
                 Ok(Some(Value::from(obj)))
             }
-            Value::Array(mut arr) => {
+            Value::Array(mut arr) if !arr.is_empty() => {
                 arr.clear();
                 cleared += 1;
                 Ok(Some(Value::from(arr)))
             }
+            Value::Number(n) if n.as_f64() != Some(0.0) => {
+                cleared += 1;
+                Ok(Some(Value::from(0)))
+            }
+            Value::String(s) if strings && !s.is_empty() => {
+                cleared += 1;
+                Ok(Some(Value::from("")))
+            }
             _ => Ok(Some(v)),
         })?;
         Ok(cleared)
@@ -491,12 +882,23 @@ pub struct KeyHolderRead {
 
 impl ReadHolder<Value> for KeyHolderRead {
     fn get_value(&self) -> Result<Option<&Value>, RedisError> {
-        let key_value = self.key.get_value::<RedisJSON>(&REDIS_JSON_TYPE)?;
+        let key_value = self
+            .key
+            .get_value::<RedisJSON>(&REDIS_JSON_TYPE)
+            .map_err(wrong_type)?;
         match key_value {
-            Some(v) => Ok(Some(&v.data)),
+            Some(v) => Ok(Some(v.data())),
             None => Ok(None),
         }
     }
+
+    fn get_encver(&self) -> Result<Option<i32>, RedisError> {
+        let key_value = self
+            .key
+            .get_value::<RedisJSON>(&REDIS_JSON_TYPE)
+            .map_err(wrong_type)?;
+        Ok(key_value.map(RedisJSON::encver))
+    }
 }
 
 pub struct RedisJsonKeyManager<'a> {
@@ -528,33 +930,32 @@ impl<'a> Manager for RedisJsonKeyManager<'a> {
     }
 
     fn from_str(&self, val: &str, format: Format) -> Result<Value, Error> {
-        match format {
-            Format::JSON => Ok(serde_json::from_str(val)?),
-            Format::BSON => decode_document(&mut Cursor::new(val.as_bytes()))
-                .map(|docs| {
-                    let v = if !docs.is_empty() {
-                        docs.iter()
-                            .next()
-                            .map_or_else(|| Value::Null, |(_, b)| b.clone().into())
-                    } else {
-                        Value::Null
-                    };
-                    Ok(v)
-                })
-                .unwrap_or_else(|e| Err(e.to_string().into())),
+        let value = match format {
+            Format::JSON => RedisJSON::parse_input(val)?,
+            Format::BSON => RedisJSON::parse_bson(val.as_bytes())?,
+            Format::RESP => resp::parse(val)?,
+        };
+        check_depth(0, &value)?;
+        Ok(value)
+    }
+
+    fn from_bytes(&self, val: &[u8], format: Format) -> Result<Value, Error> {
+        if format != Format::BSON {
+            let val = std::str::from_utf8(val)
+                .map_err(|_| Error::ParseError("invalid UTF-8".to_string()))?;
+            return self.from_str(val, format);
         }
+        let value = RedisJSON::parse_bson(val)?;
+        check_depth(0, &value)?;
+        Ok(value)
     }
 
     fn get_memory(&self, v: &Value) -> Result<usize, RedisError> {
-        let res = match v {
-            Value::Null => 0,
-            Value::Bool(v) => mem::size_of_val(v),
-            Value::Number(v) => mem::size_of_val(v),
-            Value::String(v) => mem::size_of_val(v),
-            Value::Array(v) => mem::size_of_val(v),
-            Value::Object(v) => mem::size_of_val(v),
-        };
-        Ok(res)
+        Ok(RedisJSON::value_memory(v))
+    }
+
+    fn get_shape(&self, v: &Value) -> Shape {
+        RedisJSON::value_shape(v)
     }
 
     fn is_json(&self, key: *mut RedisModuleKey) -> Result<bool, RedisError> {
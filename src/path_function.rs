@@ -0,0 +1,122 @@
+// Functions ending a path, e.g. `$.items.length()` or `$.nums.sum()`
+//
+// A function is applied to each value matched by the rest of the path, and the results are
+// replied in place of the values. The jsonpath selector does not know about them, the function
+// is removed from the path before it is compiled.
+
+use crate::error::Error;
+use jsonpath_lib::select::select_value::{SelectValue, SelectValueType};
+use serde_json::{Number, Value};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PathFunction {
+    /// The number of elements of an array, members of an object or characters of a string
+    Length,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl PathFunction {
+    /// Splits `path` into the path of the values and the function it ends with, if any
+    pub fn split(path: &str) -> Option<(&str, PathFunction)> {
+        let (values, name) = path.strip_suffix("()")?.rsplit_once('.')?;
+        let function = match name {
+            "length" => PathFunction::Length,
+            "sum" => PathFunction::Sum,
+            "avg" => PathFunction::Avg,
+            "min" => PathFunction::Min,
+            "max" => PathFunction::Max,
+            _ => return None,
+        };
+        Some((values, function))
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            PathFunction::Length => "length",
+            PathFunction::Sum => "sum",
+            PathFunction::Avg => "avg",
+            PathFunction::Min => "min",
+            PathFunction::Max => "max",
+        }
+    }
+
+    /// The result of the function for a matched value
+    ///
+    /// The aggregations of an empty array are null, except for its sum which is 0.
+    pub fn apply<V: SelectValue>(&self, value: &V) -> Result<Value, Error> {
+        if *self == PathFunction::Length {
+            return match value.get_type() {
+                SelectValueType::Array | SelectValueType::Object => {
+                    Ok(value.len().unwrap_or(0).into())
+                }
+                SelectValueType::String => Ok(value.as_str().chars().count().into()),
+                _ => Err(Error::WrongType(
+                    "ERR length() requires an array, an object or a string".to_string(),
+                )),
+            };
+        }
+
+        if value.get_type() != SelectValueType::Array {
+            return Err(self.wrong_type());
+        }
+        let numbers = value
+            .values()
+            .unwrap()
+            .map(|v| match v.get_type() {
+                SelectValueType::Long => Ok(Number::from(v.get_long())),
+                SelectValueType::Double => {
+                    Number::from_f64(v.get_double()).ok_or_else(|| self.wrong_type())
+                }
+                _ => Err(self.wrong_type()),
+            })
+            .collect::<Result<Vec<Number>, Error>>()?;
+
+        let res = match self {
+            PathFunction::Sum => {
+                // Integers add up to an integer, as long as the sum fits in one
+                let ints = numbers
+                    .iter()
+                    .map(Number::as_i64)
+                    .try_fold(0i64, |sum, n| sum.checked_add(n?));
+                match ints {
+                    Some(sum) => return Ok(sum.into()),
+                    None => numbers.iter().map(|n| n.as_f64().unwrap()).sum(),
+                }
+            }
+            _ if numbers.is_empty() => return Ok(Value::Null),
+            PathFunction::Avg => {
+                numbers.iter().map(|n| n.as_f64().unwrap()).sum::<f64>() / numbers.len() as f64
+            }
+            // The matched number itself, so an integer stays one
+            _ => {
+                let ordered = numbers.into_iter().reduce(|a, b| {
+                    let (x, y) = (a.as_f64().unwrap(), b.as_f64().unwrap());
+                    if (*self == PathFunction::Min && y < x)
+                        || (*self == PathFunction::Max && y > x)
+                    {
+                        b
+                    } else {
+                        a
+                    }
+                });
+                return Ok(ordered.map_or(Value::Null, Value::Number));
+            }
+        };
+        Number::from_f64(res).map(Value::Number).ok_or_else(|| {
+            Error::Custom(format!(
+                "ERR {}() result is not a finite number",
+                self.name()
+            ))
+        })
+    }
+
+    fn wrong_type(&self) -> Error {
+        Error::WrongType(format!(
+            "ERR {}() requires an array of numbers",
+            self.name()
+        ))
+    }
+}
@@ -0,0 +1,103 @@
+// JSON Pointer (RFC 6901) resolver, e.g. `/store/book/0/title`
+//
+// Unlike JSONPath a pointer identifies at most one value, so it is resolved by walking the
+// document directly instead of going through the jsonpath selector.
+
+use crate::error::Error;
+use jsonpath_lib::select::select_value::{SelectValue, SelectValueType};
+
+/// Splits a pointer into its unescaped reference tokens
+pub fn parse(pointer: &str) -> Result<Vec<String>, Error> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !pointer.starts_with('/') {
+        return Err(Error::ParseError(format!(
+            "ERR invalid JSON Pointer '{}': must start with '/'",
+            pointer
+        )));
+    }
+    pointer[1..]
+        .split('/')
+        .map(|token| {
+            let mut res = String::with_capacity(token.len());
+            let mut chars = token.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '~' => match chars.next() {
+                        Some('0') => res.push('~'),
+                        Some('1') => res.push('/'),
+                        _ => {
+                            return Err(Error::ParseError(format!(
+                                "ERR invalid JSON Pointer '{}': '~' must be followed by '0' or '1'",
+                                pointer
+                            )))
+                        }
+                    },
+                    c => res.push(c),
+                }
+            }
+            Ok(res)
+        })
+        .collect()
+}
+
+/// Escapes `~` and `/` in a reference token, the inverse of what `parse` does for each token
+pub fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// Parses an array index token, leading zeros and `-` (past the last element) never match
+pub(crate) fn array_index(token: &str) -> Option<usize> {
+    if token.is_empty()
+        || (token.len() > 1 && token.starts_with('0'))
+        || !token.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// Returns the value referenced by `tokens` or None if there is no such value
+pub fn resolve<'a, V: SelectValue>(root: &'a V, tokens: &[String]) -> Option<&'a V> {
+    tokens
+        .iter()
+        .try_fold(root, |value, token| match value.get_type() {
+            SelectValueType::Object => value.get_key(token),
+            SelectValueType::Array => array_index(token).and_then(|i| value.get_index(i)),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("").unwrap(), Vec::<String>::new());
+        assert_eq!(parse("/").unwrap(), vec![""]);
+        assert_eq!(parse("/a/0").unwrap(), vec!["a", "0"]);
+        assert_eq!(parse("/a~1b/m~0n/~01").unwrap(), vec!["a/b", "m~n", "~1"]);
+        assert!(parse("a").is_err());
+        assert!(parse("/a~").is_err());
+        assert!(parse("/a~2").is_err());
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a"), "a");
+        assert_eq!(escape("a/b~c"), "a~1b~0c");
+        assert_eq!(parse(&format!("/{}", escape("~1/"))).unwrap(), vec!["~1/"]);
+    }
+
+    #[test]
+    fn test_array_index() {
+        assert_eq!(array_index("0"), Some(0));
+        assert_eq!(array_index("10"), Some(10));
+        assert_eq!(array_index("01"), None);
+        assert_eq!(array_index("-"), None);
+        assert_eq!(array_index("-1"), None);
+        assert_eq!(array_index(""), None);
+    }
+}
@@ -6,22 +6,43 @@
 
 use crate::backward;
 use crate::c_api::JSONType;
+use crate::cache;
+use crate::config;
 use crate::error::Error;
 use crate::nodevisitor::{StaticPathElement, StaticPathParser, VisitStatus};
-use crate::REDIS_JSON_TYPE_VERSION;
+use crate::resp;
+use crate::shared;
+use crate::{REDIS_JSON_AUX_VERSION, REDIS_JSON_TYPE_VERSION};
 use jsonpath_lib::select::json_node::JsonValueUpdater;
 use jsonpath_lib::select::{Selector, SelectorMut};
 
 use bson::decode_document;
 use redis_module::raw::{self, Status};
-use serde_json::Value;
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_json::{Map, Number, Value};
+use std::cell::{OnceCell, RefCell};
 use std::io::Cursor;
 use std::mem;
 use std::os::raw::{c_int, c_void};
+use std::sync::Arc;
 
 use std::fmt;
 use std::fmt::Display;
 
+/// The number of values of each type in a document, reported by `JSON.DEBUG KEYSPACE`
+#[derive(Debug, Default, PartialEq)]
+pub struct Shape {
+    pub objects: usize,
+    pub arrays: usize,
+    pub strings: usize,
+    pub numbers: usize,
+    pub booleans: usize,
+    pub nulls: usize,
+    /// Nesting depth of the arrays and objects, 0 for a scalar
+    pub max_depth: usize,
+    pub leaves: usize,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum SetOptions {
     NotExists,
@@ -29,16 +50,19 @@ pub enum SetOptions {
     None,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Format {
     JSON,
     BSON,
+    // The RESP form of JSON.RESP, only accepted as input
+    RESP,
 }
 impl Format {
     pub fn from_str(s: &str) -> Result<Format, Error> {
         match s {
             "JSON" => Ok(Format::JSON),
             "BSON" => Ok(Format::BSON),
+            "RESP" => Ok(Format::RESP),
             _ => Err("ERR wrong format".into()),
         }
     }
@@ -98,32 +122,246 @@ impl Display for Path<'_> {
 
 #[derive(Debug)]
 pub struct RedisJSON {
-    //FIXME: make private and expose array/object Values without requiring a path
-    pub data: Value,
+    //FIXME: expose array/object Values without requiring a path
+    /// Possibly shared with other keys holding the same document, it is copied before it
+    /// is modified
+    data: OnceCell<Arc<Value>>,
+    /// The document as saved in the RDB, until it is first accessed
+    loaded: RefCell<Option<String>>,
+    /// Encoding version of the RDB the document was loaded from, the current version for a
+    /// document set since
+    encver: i32,
+}
+
+/// A JSON value that fails to deserialize if an object has the same key twice
+struct UniqueKeysValue(Value);
+
+impl<'de> Deserialize<'de> for UniqueKeysValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_any(UniqueKeysVisitor)
+            .map(UniqueKeysValue)
+    }
+}
+
+struct UniqueKeysVisitor;
+
+impl<'de> Visitor<'de> for UniqueKeysVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any valid JSON value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Number::from_f64(v).map_or(Value::Null, Value::Number))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut arr = Vec::new();
+        while let Some(UniqueKeysValue(v)) = seq.next_element()? {
+            arr.push(v);
+        }
+        Ok(Value::Array(arr))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut obj = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if obj.contains_key(&key) {
+                return Err(de::Error::custom(format!("duplicate key '{}'", key)));
+            }
+            let UniqueKeysValue(v) = map.next_value()?;
+            obj.insert(key, v);
+        }
+        Ok(Value::Object(obj))
+    }
+}
+
+#[cfg(not(feature = "simd"))]
+fn deserialize_input<T: DeserializeOwned>(data: &str) -> Result<T, Error> {
+    Ok(serde_json::from_str(data)?)
+}
+
+/// Inputs from this size on are parsed with simd-json, smaller ones are not worth copying
+#[cfg(feature = "simd")]
+const SIMD_MIN_INPUT_SIZE: usize = 4096;
+
+#[cfg(feature = "simd")]
+fn deserialize_input<T: DeserializeOwned>(data: &str) -> Result<T, Error> {
+    if data.len() < SIMD_MIN_INPUT_SIZE {
+        return Ok(serde_json::from_str(data)?);
+    }
+    // serde_json's recursion limit does not apply, and building the value is recursive
+    check_input_depth(data.as_bytes())?;
+    // simd-json parses in place, so it works on a copy
+    let mut bytes = data.as_bytes().to_vec();
+    Ok(simd_json::serde::from_slice(&mut bytes)?)
+}
+
+/// Fails like serde_json when arrays and objects in `data` are nested more than 127 levels
+#[cfg(feature = "simd")]
+fn check_input_depth(data: &[u8]) -> Result<(), Error> {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for &b in data {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match b {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > config::DEFAULT_MAX_DEPTH {
+                    return Err(Error::ParseError("recursion limit exceeded".to_string()));
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    Ok(())
 }
 
 impl RedisJSON {
+    /// Parses JSON sent by a client, when an object has the same key twice the last value is
+    /// kept, unless the DUPLICATE_KEYS configuration is ERROR
+    pub fn parse_input(data: &str) -> Result<Value, Error> {
+        if config::reject_duplicate_keys() {
+            Ok(deserialize_input::<UniqueKeysValue>(data)?.0)
+        } else {
+            deserialize_input(data)
+        }
+    }
+
     pub fn parse_str(data: &str, format: Format) -> Result<Value, Error> {
         match format {
             Format::JSON => Ok(serde_json::from_str(data)?),
-            Format::BSON => decode_document(&mut Cursor::new(data.as_bytes()))
-                .map(|docs| {
-                    let v = if !docs.is_empty() {
-                        docs.iter()
-                            .next()
-                            .map_or_else(|| Value::Null, |(_, b)| b.clone().into())
-                    } else {
-                        Value::Null
-                    };
-                    Ok(v)
-                })
-                .unwrap_or_else(|e| Err(e.to_string().into())),
+            Format::BSON => RedisJSON::parse_bson(data.as_bytes()),
+            Format::RESP => resp::parse(data),
         }
     }
 
+    /// Parses a BSON document, its value is that of its first member
+    pub fn parse_bson(data: &[u8]) -> Result<Value, Error> {
+        let doc = decode_document(&mut Cursor::new(data))
+            .map_err(|e| Error::ParseError(e.to_string()))?;
+        let value = doc
+            .iter()
+            .next()
+            .map_or(Value::Null, |(_, b)| b.clone().into());
+        Ok(value)
+    }
+
     pub fn from_str(data: &str, format: Format) -> Result<Self, Error> {
         let value = RedisJSON::parse_str(data, format)?;
-        Ok(Self { data: value })
+        Ok(Self::new(value))
+    }
+
+    pub fn new(data: Value) -> Self {
+        Self::from_shared(Arc::new(data))
+    }
+
+    /// Shares the document with the other keys holding the same document, if any, when the
+    /// SHARE_DOCS configuration is enabled
+    pub fn new_shared(data: Value) -> Self {
+        if config::share_docs() {
+            Self::from_shared(shared::share(data))
+        } else {
+            Self::new(data)
+        }
+    }
+
+    fn from_shared(data: Arc<Value>) -> Self {
+        RedisJSON {
+            data: OnceCell::from(data),
+            loaded: RefCell::new(None),
+            encver: REDIS_JSON_TYPE_VERSION,
+        }
+    }
+
+    /// A copy of the document that shares its memory until one of them is modified
+    pub fn share(&self) -> Self {
+        let mut res = match self.data.get() {
+            Some(data) => Self::from_shared(Arc::clone(data)),
+            None => Self::lazy(self.loaded.borrow().as_ref().unwrap().clone()),
+        };
+        res.encver = self.encver;
+        res
+    }
+
+    /// Keeps `json`, as saved by `rdb_save`, to parse it only when the document is first
+    /// accessed, so loading large datasets does not pay for documents that are not used
+    fn lazy(json: String) -> Self {
+        RedisJSON {
+            data: OnceCell::new(),
+            loaded: RefCell::new(Some(json)),
+            encver: REDIS_JSON_TYPE_VERSION,
+        }
+    }
+
+    /// The encoding version of the RDB the document was loaded from, which is kept when it is
+    /// modified but not when it is set again at the root
+    pub fn encver(&self) -> i32 {
+        self.encver
+    }
+
+    pub fn data(&self) -> &Value {
+        self.data.get_or_init(|| {
+            let json = self.loaded.borrow_mut().take().unwrap_or_default();
+            // `rdb_load` only keeps documents that parse, so this falls back to null only if
+            // the loaded document went missing
+            Arc::new(serde_json::from_str(&json).unwrap_or(Value::Null))
+        })
+    }
+
+    pub fn data_mut(&mut self) -> &mut Value {
+        self.data();
+        // Copied if another key shares it, so the change is not seen there
+        Arc::make_mut(self.data.get_mut().unwrap())
+    }
+
+    pub fn set_data(&mut self, data: Value) {
+        *self = Self::new(data);
+    }
+
+    pub fn set_data_shared(&mut self, data: Value) {
+        *self = Self::new_shared(data);
     }
 
     fn add_value(&mut self, path: &str, value: Value) -> Result<bool, Error> {
@@ -142,7 +380,7 @@ impl RedisJSON {
             if let StaticPathElement::Root = parsed_static_path.static_path_elements.last().unwrap()
             {
                 // Adding to the root, can't use jsonpath_lib::replace_with
-                let mut current_data = self.data.take();
+                let mut current_data = self.data_mut().take();
                 let res = if let Value::Object(ref mut map) = current_data {
                     if map.contains_key(&key) {
                         false
@@ -153,7 +391,7 @@ impl RedisJSON {
                 } else {
                     false
                 };
-                self.data = current_data;
+                self.set_data(current_data);
                 Ok(res)
             } else {
                 // Adding somewhere in existing object, use jsonpath_lib::replace_with
@@ -169,7 +407,7 @@ impl RedisJSON {
                 ) {
                     return Err(e.into());
                 }
-                selector.value(&mut self.data);
+                selector.value(self.data_mut());
                 let mut updater = JsonValueUpdater::new(|mut ret| {
                     if let Value::Object(ref mut map) = ret {
                         if map.contains_key(&key) {
@@ -201,16 +439,17 @@ impl RedisJSON {
             if SetOptions::NotExists == *option {
                 Ok(false)
             } else {
-                self.data = json;
+                self.set_data(json);
                 Ok(true)
             }
         } else {
             let mut replaced = false;
             if SetOptions::NotExists != *option {
-                self.data = jsonpath_lib::replace_with(self.data.take(), path, |_v| {
+                let data = jsonpath_lib::replace_with(self.data_mut().take(), path, |_v| {
                     replaced = true;
                     Some(json.clone())
                 })?;
+                self.set_data(data);
             }
             if replaced {
                 Ok(true)
@@ -224,17 +463,18 @@ impl RedisJSON {
 
     pub fn delete_path(&mut self, path: &str) -> Result<usize, Error> {
         let mut deleted = 0;
-        self.data = jsonpath_lib::replace_with(self.data.take(), path, |v| {
+        let data = jsonpath_lib::replace_with(self.data_mut().take(), path, |v| {
             if !v.is_null() {
                 deleted += 1; // might delete more than a single value
             }
             None
         })?;
+        self.set_data(data);
         Ok(deleted)
     }
 
     pub fn clear(&mut self, path: &str) -> Result<usize, Error> {
-        let current_data = self.data.take();
+        let current_data = self.data_mut().take();
         let mut cleared = 0;
 
         let clear_func = &mut |v| match v {
@@ -251,11 +491,12 @@ impl RedisJSON {
             _ => Some(v),
         };
 
-        self.data = if path == "$" {
+        let data = if path == "$" {
             clear_func(current_data).unwrap()
         } else {
             jsonpath_lib::replace_with(current_data, path, clear_func)?
         };
+        self.set_data(data);
         Ok(cleared)
     }
     pub fn to_string(&self, path: &str, format: Format) -> Result<String, Error> {
@@ -267,6 +508,7 @@ impl RedisJSON {
         let res = match format {
             Format::JSON => serde_json::to_string(results)?,
             Format::BSON => return Err("Soon to come...".into()), //results.into() as Bson,
+            Format::RESP => return Err("ERR FORMAT RESP is only supported by JSON.SET".into()),
         };
         Ok(res)
     }
@@ -274,28 +516,28 @@ impl RedisJSON {
     pub fn str_len(&self, path: &str) -> Result<usize, Error> {
         self.get_first(path)?
             .as_str()
-            .ok_or_else(|| "ERR wrong type of path value".into())
+            .ok_or_else(Error::wrong_type)
             .map(|s| s.len())
     }
 
     pub fn arr_len(&self, path: &str) -> Result<usize, Error> {
         self.get_first(path)?
             .as_array()
-            .ok_or_else(|| "ERR wrong type of path value".into())
+            .ok_or_else(Error::wrong_type)
             .map(|arr| arr.len())
     }
 
     pub fn obj_len(&self, path: &str) -> Result<usize, Error> {
         self.get_first(path)?
             .as_object()
-            .ok_or_else(|| "ERR wrong type of path value".into())
+            .ok_or_else(Error::wrong_type)
             .map(|obj| obj.len())
     }
 
     pub fn obj_keys<'a>(&'a self, path: &'a str) -> Result<Vec<&'a String>, Error> {
         self.get_first(path)?
             .as_object()
-            .ok_or_else(|| "ERR wrong type of path value".into())
+            .ok_or_else(Error::wrong_type)
             .map(|obj| obj.keys().collect())
     }
 
@@ -407,12 +649,13 @@ impl RedisJSON {
 
         if path == "$" {
             // root needs special handling
-            self.data = collect_fun(self.data.take());
+            let data = collect_fun(self.data_mut().take());
+            self.set_data(data);
         } else {
             match SelectorMut::new().str_path(path) {
                 Ok(selector) => {
                     let mut updater = JsonValueUpdater::new(|v| Some(collect_fun(v)));
-                    let replace_result = selector.value(&mut self.data).replace_with(&mut updater);
+                    let replace_result = selector.value(self.data_mut()).replace_with(&mut updater);
 
                     if let Err(e) = replace_result {
                         errors.push(e.into());
@@ -427,38 +670,125 @@ impl RedisJSON {
         match errors.len() {
             0 => match result {
                 Some(r) => Ok(r),
-                None => Err(format!("Path '{}' does not exist", path).into()),
+                None => Err(Error::PathNotFound(format!(
+                    "Path '{}' does not exist",
+                    path
+                ))),
             },
             1 => Err(errors.remove(0)),
-            _ => Err(errors.into_iter().map(|e| e.msg).collect::<String>().into()),
+            _ => Err(errors
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<String>()
+                .into()),
         }
     }
 
     pub fn get_memory<'a>(&'a self, path: &'a str) -> Result<usize, Error> {
-        // TODO add better calculation, handle wrappers, internals and length
-        let res = match self.get_first(path)? {
-            Value::Null => 0,
-            Value::Bool(v) => mem::size_of_val(v),
-            Value::Number(v) => mem::size_of_val(v),
-            Value::String(v) => mem::size_of_val(v),
-            Value::Array(v) => mem::size_of_val(v),
-            Value::Object(v) => mem::size_of_val(v),
-        };
-        Ok(res)
+        Ok(Self::value_memory(self.get_first(path)?))
+    }
+
+    /// The memory held by `value`: the `Value` itself and everything it allocates, counting
+    /// the capacity of strings and arrays rather than their length
+    pub fn value_memory(value: &Value) -> usize {
+        // The members of an object are kept in a vector of entries, along with a hash and an
+        // index in the hash table (which has a control byte per slot)
+        const OBJECT_ENTRY_SIZE: usize =
+            mem::size_of::<(u64, String, Value)>() + mem::size_of::<usize>() + 1;
+
+        let mut res = mem::size_of::<Value>();
+        let mut stack = vec![value];
+        while let Some(v) = stack.pop() {
+            match v {
+                Value::Null | Value::Bool(_) | Value::Number(_) => {}
+                Value::String(s) => res += s.capacity(),
+                Value::Array(arr) => {
+                    // The elements are in the vector's buffer, their size is its capacity
+                    res += arr.capacity() * mem::size_of::<Value>();
+                    stack.extend(arr.iter());
+                }
+                Value::Object(obj) => {
+                    res += obj.len() * OBJECT_ENTRY_SIZE;
+                    for (k, v) in obj {
+                        res += k.capacity();
+                        stack.push(v);
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    /// Counts the values of each type in `value`, along with its nesting depth and number of
+    /// leaves (values without members or elements, the ones `JSON.FLATTEN` returns)
+    pub fn value_shape(value: &Value) -> Shape {
+        let mut res = Shape::default();
+        let mut stack = vec![(0, value)];
+        while let Some((depth, v)) = stack.pop() {
+            let children = match v {
+                Value::Null => {
+                    res.nulls += 1;
+                    0
+                }
+                Value::Bool(_) => {
+                    res.booleans += 1;
+                    0
+                }
+                Value::Number(_) => {
+                    res.numbers += 1;
+                    0
+                }
+                Value::String(_) => {
+                    res.strings += 1;
+                    0
+                }
+                Value::Array(arr) => {
+                    res.arrays += 1;
+                    res.max_depth = res.max_depth.max(depth + 1);
+                    stack.extend(arr.iter().map(|c| (depth + 1, c)));
+                    arr.len()
+                }
+                Value::Object(obj) => {
+                    res.objects += 1;
+                    res.max_depth = res.max_depth.max(depth + 1);
+                    stack.extend(obj.values().map(|c| (depth + 1, c)));
+                    obj.len()
+                }
+            };
+            if children == 0 {
+                res.leaves += 1;
+            }
+        }
+        res
+    }
+
+    /// The memory held by the document, when it is not parsed yet that of its serialization.
+    /// A document shared by several keys is split evenly between them
+    pub fn memory(&self) -> usize {
+        let res = mem::size_of::<Self>();
+        match self.data.get() {
+            // The Arc's allocation holds the value along with its two reference counts
+            Some(data) => {
+                let shared = Self::value_memory(data) + 2 * mem::size_of::<usize>();
+                res + shared / Arc::strong_count(data)
+            }
+            None => res + self.loaded.borrow().as_ref().map_or(0, |s| s.capacity()),
+        }
     }
 
     pub fn get_first<'a>(&'a self, path: &'a str) -> Result<&'a Value, Error> {
         let results = self.get_values(path)?;
         match results.first() {
             Some(s) => Ok(s),
-            None => Err("ERR path does not exist".into()),
+            None => Err(Error::path_not_found()),
         }
     }
 
     pub fn get_values<'a>(&'a self, path: &'a str) -> Result<Vec<&'a Value>, Error> {
+        let node = cache::compile(path)?;
         let mut selector = Selector::new();
-        selector.str_path(path)?;
-        selector.value(&self.data);
+        selector.compiled_path(&node);
+        selector.value(self.data());
         let results = selector.select()?;
         Ok(results)
     }
@@ -466,32 +796,79 @@ impl RedisJSON {
 
 pub mod type_methods {
     use super::*;
+    use std::ffi::CString;
+    use std::os::raw::c_char;
 
     #[allow(non_snake_case, unused)]
     pub extern "C" fn rdb_load(rdb: *mut raw::RedisModuleIO, encver: c_int) -> *mut c_void {
-        let json = match encver {
-            0 => RedisJSON {
-                data: backward::json_rdb_load(rdb),
+        let mut json = match encver {
+            0 => match backward::json_rdb_load(rdb) {
+                Ok(value) => RedisJSON::new(value),
+                Err(e) => {
+                    log_io_error(rdb, &format!("Can't load old RedisJSON RDB: {}", e));
+                    return std::ptr::null_mut();
+                }
             },
-            2 => {
+            2..=4 => {
                 let data = raw::load_string(rdb);
-                // Backward support for modules that had AUX field for RediSarch
-                // TODO remove in future versions
-                if raw::load_unsigned(rdb) > 0 {
-                    raw::load_string(rdb);
-                    raw::load_string(rdb);
+                if encver == 2 {
+                    // Backward support for modules that had AUX field for RediSarch
+                    // TODO remove in future versions
+                    if raw::load_unsigned(rdb) > 0 {
+                        raw::load_string(rdb);
+                        raw::load_string(rdb);
+                    }
+                }
+                // Checked without building the document, so a corrupt one fails loading
+                // instead of its first access
+                if let Err(e) = serde_json::from_str::<de::IgnoredAny>(&data) {
+                    log_io_error(rdb, &format!("Can't load RedisJSON document: {}", e));
+                    return std::ptr::null_mut();
                 }
-                RedisJSON::from_str(&data, Format::JSON).unwrap()
+                RedisJSON::lazy(data)
             }
-            3 => {
-                let data = raw::load_string(rdb);
-                RedisJSON::from_str(&data, Format::JSON).unwrap()
+            _ => {
+                log_io_error(
+                    rdb,
+                    &format!("Unsupported RedisJSON encoding version {}", encver),
+                );
+                return std::ptr::null_mut();
             }
-            _ => panic!("Can't load old RedisJSON RDB"),
         };
+        json.encver = encver;
         Box::into_raw(Box::new(json)) as *mut c_void
     }
 
+    /// Reports a value that can't be loaded, Redis then fails loading the RDB
+    fn log_io_error(rdb: *mut raw::RedisModuleIO, msg: &str) {
+        let msg = CString::new(msg).unwrap_or_default();
+        unsafe {
+            raw::RedisModule_LogIOError.unwrap()(
+                rdb,
+                "warning\0".as_ptr() as *const c_char,
+                "%s\0".as_ptr() as *const c_char,
+                msg.as_ptr(),
+            );
+        }
+    }
+
+    /// COPY shares the document with the new key
+    #[allow(non_snake_case, unused)]
+    pub unsafe extern "C" fn copy(
+        fromkey: *mut raw::RedisModuleString,
+        tokey: *mut raw::RedisModuleString,
+        value: *const c_void,
+    ) -> *mut c_void {
+        let json = &*(value as *const RedisJSON);
+        Box::into_raw(Box::new(json.share())) as *mut c_void
+    }
+
+    #[allow(non_snake_case, unused)]
+    pub unsafe extern "C" fn mem_usage(value: *const c_void) -> usize {
+        let json = &*(value as *const RedisJSON);
+        json.memory()
+    }
+
     #[allow(non_snake_case, unused)]
     pub unsafe extern "C" fn free(value: *mut c_void) {
         let json = value as *mut RedisJSON;
@@ -503,7 +880,11 @@ pub mod type_methods {
     #[allow(non_snake_case, unused)]
     pub unsafe extern "C" fn rdb_save(rdb: *mut raw::RedisModuleIO, value: *mut c_void) {
         let json = &*(value as *mut RedisJSON);
-        raw::save_string(rdb, &json.data.to_string());
+        match json.data.get() {
+            Some(data) => raw::save_string(rdb, &data.to_string()),
+            // Not accessed since it was loaded, so it is saved as it was
+            None => raw::save_string(rdb, json.loaded.borrow().as_ref().unwrap()),
+        }
     }
 
     #[allow(non_snake_case, unused)]
@@ -527,6 +908,76 @@ pub mod type_methods {
             }
         }
 
+        // Since version 4 the keys are followed by the version of this auxiliary data, a version
+        // with data this module does not know of can not be skipped
+        if (encver >= 4 && when == raw::Aux::After as i32) {
+            let version = raw::load_unsigned(rdb);
+            if version != REDIS_JSON_AUX_VERSION {
+                log_io_error(
+                    rdb,
+                    &format!("Unsupported RedisJSON auxiliary data version {}", version),
+                );
+                return Status::Err as i32;
+            }
+        }
+
         Status::Ok as i32
     }
+
+    /// Saves the version of the auxiliary data after the keys, there is no other data yet
+    #[allow(non_snake_case, unused)]
+    pub unsafe extern "C" fn aux_save(rdb: *mut raw::RedisModuleIO, when: c_int) {
+        if when == raw::Aux::After as c_int {
+            raw::save_unsigned(rdb, REDIS_JSON_AUX_VERSION);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "simd"))]
+mod tests {
+    use super::*;
+
+    /// An array starting with `value`, large enough to be parsed with simd-json
+    fn large(value: &str) -> String {
+        format!("[{}{}]", value, ",0".repeat(SIMD_MIN_INPUT_SIZE))
+    }
+
+    #[test]
+    fn test_simd_same_as_serde_json() {
+        for value in &[
+            r#"{"a":[1,2.5,-0.1,1e300,18446744073709551615,-9223372036854775808],"b":{}}"#,
+            r#""é\"[{\\""#,
+            "null",
+            "true",
+        ] {
+            let input = large(value);
+            assert_eq!(
+                deserialize_input::<Value>(&input).unwrap(),
+                serde_json::from_str::<Value>(&input).unwrap(),
+                "{}",
+                value
+            );
+        }
+        assert!(deserialize_input::<Value>(&large("[1,")).is_err());
+    }
+
+    #[test]
+    fn test_simd_depth() {
+        let nested = |n: usize| large(&format!("{}{}", "[".repeat(n), "]".repeat(n)));
+        assert!(deserialize_input::<Value>(&nested(126)).is_ok());
+        assert!(deserialize_input::<Value>(&nested(127)).is_err());
+        // brackets in strings are not nesting
+        let input = large(&format!(r#""{}\"{}""#, "[".repeat(200), "{".repeat(200)));
+        assert!(deserialize_input::<Value>(&input).is_ok());
+    }
+
+    #[test]
+    fn test_simd_duplicate_keys() {
+        let input = large(r#"{"a":1,"a":2}"#);
+        assert!(deserialize_input::<UniqueKeysValue>(&input).is_err());
+        assert_eq!(
+            deserialize_input::<Value>(&input).unwrap()[0],
+            serde_json::json!({"a": 2})
+        );
+    }
 }
@@ -0,0 +1,244 @@
+// Parser of values given in the RESP form of JSON.RESP, for JSON.SET ... FORMAT RESP
+//
+// The input is the RESP encoding of the reply of JSON.RESP: an array starts with the simple
+// string `[` followed by its elements, an object with `{` followed by its keys and values,
+// booleans and floats (in RESP2) are simple strings, integers are integers and strings are bulk
+// strings. RESP3 maps, doubles, booleans and nulls are accepted as well, as JSON.RESP replies
+// objects as maps to RESP3 clients, and so are arrays without the `[` marker, the way it replies
+// arrays to them. `serialize` writes a value in the RESP2 form, for JSON.GET ... FORMAT RESP.
+
+use crate::config;
+use crate::error::Error;
+use crate::formatter;
+use serde_json::{Map, Number, Value};
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+/// A RESP element, an array is followed by its elements
+enum Element<'a> {
+    Simple(&'a str),
+    Bulk(&'a str),
+    Value(Value),
+    Array(usize),
+    Map(usize),
+}
+
+fn parse_error(msg: &str) -> Error {
+    Error::ParseError(format!("invalid RESP value: {}", msg))
+}
+
+impl<'a> Parser<'a> {
+    fn line(&mut self) -> Result<&'a str, Error> {
+        let rest = &self.data[self.pos..];
+        let end = rest
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(|| parse_error("missing CRLF"))?;
+        self.pos += end + 2;
+        std::str::from_utf8(&rest[..end]).map_err(|_| parse_error("invalid UTF-8"))
+    }
+
+    fn length(&mut self) -> Result<Option<usize>, Error> {
+        match self.line()? {
+            "-1" => Ok(None),
+            len => len
+                .parse()
+                .map(Some)
+                .map_err(|_| parse_error("invalid length")),
+        }
+    }
+
+    fn element(&mut self) -> Result<Element<'a>, Error> {
+        let kind = *self
+            .data
+            .get(self.pos)
+            .ok_or_else(|| parse_error("unexpected end of input"))?;
+        self.pos += 1;
+        Ok(match kind {
+            b'+' => Element::Simple(self.line()?),
+            b'$' => match self.length()? {
+                None => Element::Value(Value::Null),
+                Some(len) => {
+                    let start = self.pos;
+                    let end = start.saturating_add(len);
+                    if self.data.get(end..end.saturating_add(2)) != Some(b"\r\n") {
+                        return Err(parse_error("truncated bulk string"));
+                    }
+                    self.pos = end + 2;
+                    let s = std::str::from_utf8(&self.data[start..end])
+                        .map_err(|_| parse_error("invalid UTF-8"))?;
+                    Element::Bulk(s)
+                }
+            },
+            b':' => Element::Value(
+                self.line()?
+                    .parse::<i64>()
+                    .map_err(|_| parse_error("invalid integer"))?
+                    .into(),
+            ),
+            b',' => Element::Value(
+                self.line()?
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(Number::from_f64)
+                    .map(Value::Number)
+                    .ok_or_else(|| parse_error("invalid double"))?,
+            ),
+            b'#' => match self.line()? {
+                "t" => Element::Value(Value::Bool(true)),
+                "f" => Element::Value(Value::Bool(false)),
+                _ => return Err(parse_error("invalid boolean")),
+            },
+            b'_' => {
+                self.line()?;
+                Element::Value(Value::Null)
+            }
+            b'*' => match self.length()? {
+                None => Element::Value(Value::Null),
+                Some(len) => Element::Array(len),
+            },
+            b'%' => match self.length()? {
+                None => return Err(parse_error("invalid length")),
+                Some(len) => Element::Map(len),
+            },
+            _ => return Err(parse_error("unsupported type")),
+        })
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, Error> {
+        let len = match self.element()? {
+            Element::Simple("true") => return Ok(Value::Bool(true)),
+            Element::Simple("false") => return Ok(Value::Bool(false)),
+            // Floats are simple strings in RESP2, their JSON representation keeps them floats
+            Element::Simple(s) => {
+                return match serde_json::from_str(s) {
+                    Ok(Value::Number(n)) => Ok(Value::Number(n)),
+                    _ => Err(parse_error("unexpected simple string")),
+                }
+            }
+            Element::Bulk(s) => return Ok(Value::String(s.to_string())),
+            Element::Value(v) => return Ok(v),
+            Element::Array(len) => len,
+            Element::Map(len) => {
+                self.check_depth(depth)?;
+                return self.members(len, depth);
+            }
+        };
+        self.check_depth(depth)?;
+        if len == 0 {
+            return Ok(Value::Array(Vec::new()));
+        }
+        let start = self.pos;
+        match self.element()? {
+            Element::Simple("[") => self.elements(len - 1, depth),
+            Element::Simple("{") if len % 2 == 1 => self.members(len / 2, depth),
+            Element::Simple("{") => Err(parse_error("objects must have keys and values")),
+            // A RESP3 array, its first element is read again as a value
+            _ => {
+                self.pos = start;
+                self.elements(len, depth)
+            }
+        }
+    }
+
+    /// Reads the `len` elements of an array
+    fn elements(&mut self, len: usize, depth: usize) -> Result<Value, Error> {
+        // The length is not trusted to reserve memory, the elements may be missing
+        let mut arr = Vec::new();
+        for _ in 0..len {
+            arr.push(self.value(depth + 1)?);
+        }
+        Ok(Value::Array(arr))
+    }
+
+    fn check_depth(&self, depth: usize) -> Result<(), Error> {
+        if depth >= config::max_depth() {
+            return Err(format!(
+                "ERR maximum nesting depth of {} exceeded",
+                config::max_depth()
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Reads the `len` keys and values of an object
+    fn members(&mut self, len: usize, depth: usize) -> Result<Value, Error> {
+        let mut obj = Map::new();
+        for _ in 0..len {
+            let key = match self.element()? {
+                Element::Bulk(k) | Element::Simple(k) => k.to_string(),
+                _ => return Err(parse_error("object keys must be strings")),
+            };
+            let value = self.value(depth + 1)?;
+            if obj.insert(key, value).is_some() && config::reject_duplicate_keys() {
+                return Err(parse_error("duplicate key"));
+            }
+        }
+        Ok(Value::Object(obj))
+    }
+}
+
+/// Builds the value encoded in `data`, the inverse of JSON.RESP
+pub fn parse(data: &str) -> Result<Value, Error> {
+    let mut parser = Parser {
+        data: data.as_bytes(),
+        pos: 0,
+    };
+    let value = parser.value(0)?;
+    if parser.pos != parser.data.len() {
+        return Err(parse_error("unexpected data after the value"));
+    }
+    Ok(value)
+}
+
+/// Writes `value` in the RESP2 form of the reply of JSON.RESP, the inverse of `parse`
+pub fn serialize(value: &Value) -> String {
+    let mut res = String::new();
+    write_value(&mut res, value);
+    res
+}
+
+fn write_value(res: &mut String, value: &Value) {
+    match value {
+        Value::Null => res.push_str("$-1\r\n"),
+        Value::Bool(b) => write_simple(res, if *b { "true" } else { "false" }),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => res.push_str(&format!(":{}\r\n", i)),
+            // Integers too large for a RESP integer are read back from their JSON representation
+            None if n.is_u64() => write_simple(res, &n.to_string()),
+            None => write_simple(res, &formatter::format_float(n.as_f64().unwrap())),
+        },
+        Value::String(s) => write_bulk(res, s),
+        Value::Array(arr) => {
+            res.push_str(&format!("*{}\r\n", arr.len() + 1));
+            write_simple(res, "[");
+            for v in arr {
+                write_value(res, v);
+            }
+        }
+        Value::Object(obj) => {
+            res.push_str(&format!("*{}\r\n", obj.len() * 2 + 1));
+            write_simple(res, "{");
+            for (k, v) in obj {
+                write_bulk(res, k);
+                write_value(res, v);
+            }
+        }
+    }
+}
+
+fn write_simple(res: &mut String, s: &str) {
+    res.push('+');
+    res.push_str(s);
+    res.push_str("\r\n");
+}
+
+fn write_bulk(res: &mut String, s: &str) {
+    res.push_str(&format!("${}\r\n", s.len()));
+    res.push_str(s);
+    res.push_str("\r\n");
+}
@@ -0,0 +1,475 @@
+// JSON Schema validator, supporting a subset of draft 7
+//
+// The supported keywords are the ones that do not need references or regular expressions:
+// type, enum, const, the numeric, string, array and object limits, properties,
+// additionalProperties, required, propertyNames, items, additionalItems, contains, allOf,
+// anyOf, oneOf, not and if/then/else. Annotations such as title or format are ignored. When the
+// validation reaches a keyword we can not check yet it fails, rather than passing a value that
+// was only partly checked.
+
+use crate::error::Error;
+use crate::pointer;
+use serde_json::{Map, Value};
+
+const UNSUPPORTED_KEYWORDS: &[&str] = &["$ref", "pattern", "patternProperties", "dependencies"];
+
+/// A value that does not conform to the schema, `path` is the JSON Pointer of the value
+pub struct ValidationError {
+    pub path: String,
+    pub message: String,
+}
+
+/// Validates `value` against `schema` and returns the errors found, none if it conforms
+pub fn validate(schema: &Value, value: &Value) -> Result<Vec<ValidationError>, Error> {
+    let mut validator = Validator {
+        path: String::new(),
+        errors: Vec::new(),
+    };
+    validator.validate(schema, value)?;
+    Ok(validator.errors)
+}
+
+struct Validator {
+    path: String,
+    errors: Vec<ValidationError>,
+}
+
+fn invalid_schema(keyword: &str, expected: &str) -> Error {
+    format!("ERR invalid schema: '{}' must be {}", keyword, expected).into()
+}
+
+fn get_number(schema: &Map<String, Value>, keyword: &str) -> Result<Option<f64>, Error> {
+    schema
+        .get(keyword)
+        .map(|v| {
+            v.as_f64()
+                .ok_or_else(|| invalid_schema(keyword, "a number"))
+        })
+        .transpose()
+}
+
+fn get_count(schema: &Map<String, Value>, keyword: &str) -> Result<Option<usize>, Error> {
+    schema
+        .get(keyword)
+        .map(|v| {
+            v.as_u64()
+                .map(|n| n as usize)
+                .ok_or_else(|| invalid_schema(keyword, "a non-negative integer"))
+        })
+        .transpose()
+}
+
+fn get_schemas<'a>(schema: &'a Map<String, Value>, keyword: &str) -> Result<&'a [Value], Error> {
+    match schema.get(keyword) {
+        Some(Value::Array(schemas)) if !schemas.is_empty() => Ok(schemas),
+        Some(_) => Err(invalid_schema(keyword, "a non-empty array")),
+        None => Ok(&[]),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn is_type(value: &Value, name: &str) -> Result<bool, Error> {
+    Ok(match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "number" => value.is_number(),
+        // Numbers with a zero fractional part are integers too, e.g. 1.0
+        "integer" => value.as_f64().map_or(false, |n| n.fract() == 0.0),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => return Err(format!("ERR invalid schema: unknown type '{}'", name).into()),
+    })
+}
+
+/// Compares values the way JSON Schema does, where numbers are equal if their values are
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(k, a)| b.get(k).map_or(false, |b| equal(a, b)))
+        }
+        _ => a == b,
+    }
+}
+
+impl Validator {
+    fn error(&mut self, message: String) {
+        self.errors.push(ValidationError {
+            path: self.path.clone(),
+            message,
+        });
+    }
+
+    /// Validates a child of the current value, `token` is its key or index
+    fn validate_child(&mut self, schema: &Value, token: &str, value: &Value) -> Result<(), Error> {
+        let len = self.path.len();
+        self.path.push('/');
+        self.path.push_str(&pointer::escape(token));
+        let res = self.validate(schema, value);
+        self.path.truncate(len);
+        res
+    }
+
+    /// Whether `value` conforms to `schema`, without recording its errors
+    fn is_valid(&mut self, schema: &Value, value: &Value) -> Result<bool, Error> {
+        let errors = self.errors.len();
+        self.validate(schema, value)?;
+        let valid = self.errors.len() == errors;
+        self.errors.truncate(errors);
+        Ok(valid)
+    }
+
+    fn validate(&mut self, schema: &Value, value: &Value) -> Result<(), Error> {
+        let schema = match schema {
+            Value::Bool(true) => return Ok(()),
+            Value::Bool(false) => {
+                self.error("no value is allowed".to_string());
+                return Ok(());
+            }
+            Value::Object(schema) => schema,
+            _ => return Err("ERR invalid schema: a schema must be an object or a boolean".into()),
+        };
+        if let Some(keyword) = UNSUPPORTED_KEYWORDS
+            .iter()
+            .find(|k| schema.contains_key(**k))
+        {
+            return Err(format!("ERR schema keyword '{}' is not supported", keyword).into());
+        }
+
+        self.validate_generic(schema, value)?;
+        self.validate_combinators(schema, value)?;
+        match value {
+            Value::Number(_) => self.validate_number(schema, value.as_f64().unwrap()),
+            Value::String(s) => self.validate_string(schema, s),
+            Value::Array(arr) => self.validate_array(schema, arr),
+            Value::Object(obj) => self.validate_object(schema, obj),
+            _ => Ok(()),
+        }
+    }
+
+    fn validate_generic(
+        &mut self,
+        schema: &Map<String, Value>,
+        value: &Value,
+    ) -> Result<(), Error> {
+        match schema.get("type") {
+            Some(Value::String(name)) => {
+                if !is_type(value, name)? {
+                    self.error(format!("expected {}, got {}", name, type_name(value)));
+                }
+            }
+            Some(Value::Array(names)) => {
+                let mut valid = false;
+                for name in names {
+                    let name = name
+                        .as_str()
+                        .ok_or_else(|| invalid_schema("type", "a string or an array of strings"))?;
+                    valid |= is_type(value, name)?;
+                }
+                if !valid {
+                    let names: Vec<&str> = names.iter().filter_map(|n| n.as_str()).collect();
+                    self.error(format!(
+                        "expected one of {}, got {}",
+                        names.join(", "),
+                        type_name(value)
+                    ));
+                }
+            }
+            Some(_) => return Err(invalid_schema("type", "a string or an array of strings")),
+            None => {}
+        }
+        match schema.get("enum") {
+            Some(Value::Array(values)) => {
+                if !values.iter().any(|v| equal(v, value)) {
+                    self.error("value is not one of the enumerated values".to_string());
+                }
+            }
+            Some(_) => return Err(invalid_schema("enum", "an array")),
+            None => {}
+        }
+        if let Some(expected) = schema.get("const") {
+            if !equal(expected, value) {
+                self.error(format!("expected {}", expected));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_combinators(
+        &mut self,
+        schema: &Map<String, Value>,
+        value: &Value,
+    ) -> Result<(), Error> {
+        for sub in get_schemas(schema, "allOf")? {
+            self.validate(sub, value)?;
+        }
+        let any_of = get_schemas(schema, "anyOf")?;
+        if !any_of.is_empty() {
+            let mut valid = false;
+            for sub in any_of {
+                valid |= self.is_valid(sub, value)?;
+            }
+            if !valid {
+                self.error("value does not match any of the schemas of 'anyOf'".to_string());
+            }
+        }
+        let one_of = get_schemas(schema, "oneOf")?;
+        if !one_of.is_empty() {
+            let mut matches = 0;
+            for sub in one_of {
+                matches += self.is_valid(sub, value)? as usize;
+            }
+            if matches != 1 {
+                self.error(format!(
+                    "value matches {} of the schemas of 'oneOf' instead of exactly one",
+                    matches
+                ));
+            }
+        }
+        if let Some(sub) = schema.get("not") {
+            if self.is_valid(sub, value)? {
+                self.error("value must not match the schema of 'not'".to_string());
+            }
+        }
+        if let Some(condition) = schema.get("if") {
+            let branch = if self.is_valid(condition, value)? {
+                "then"
+            } else {
+                "else"
+            };
+            if let Some(sub) = schema.get(branch) {
+                self.validate(sub, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_number(&mut self, schema: &Map<String, Value>, n: f64) -> Result<(), Error> {
+        if let Some(min) = get_number(schema, "minimum")? {
+            if n < min {
+                self.error(format!("{} is less than the minimum of {}", n, min));
+            }
+        }
+        if let Some(max) = get_number(schema, "maximum")? {
+            if n > max {
+                self.error(format!("{} is greater than the maximum of {}", n, max));
+            }
+        }
+        if let Some(min) = get_number(schema, "exclusiveMinimum")? {
+            if n <= min {
+                self.error(format!("{} is not greater than {}", n, min));
+            }
+        }
+        if let Some(max) = get_number(schema, "exclusiveMaximum")? {
+            if n >= max {
+                self.error(format!("{} is not less than {}", n, max));
+            }
+        }
+        if let Some(m) = get_number(schema, "multipleOf")? {
+            if m <= 0.0 {
+                return Err(invalid_schema("multipleOf", "greater than 0"));
+            }
+            if (n / m).fract() != 0.0 {
+                self.error(format!("{} is not a multiple of {}", n, m));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_string(&mut self, schema: &Map<String, Value>, s: &str) -> Result<(), Error> {
+        // Lengths are in characters, not bytes
+        let len = s.chars().count();
+        if let Some(min) = get_count(schema, "minLength")? {
+            if len < min {
+                self.error(format!("string is shorter than {} characters", min));
+            }
+        }
+        if let Some(max) = get_count(schema, "maxLength")? {
+            if len > max {
+                self.error(format!("string is longer than {} characters", max));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_array(&mut self, schema: &Map<String, Value>, arr: &[Value]) -> Result<(), Error> {
+        if let Some(min) = get_count(schema, "minItems")? {
+            if arr.len() < min {
+                self.error(format!("array has fewer than {} items", min));
+            }
+        }
+        if let Some(max) = get_count(schema, "maxItems")? {
+            if arr.len() > max {
+                self.error(format!("array has more than {} items", max));
+            }
+        }
+        match schema.get("uniqueItems") {
+            Some(Value::Bool(true)) => {
+                let duplicate =
+                    (1..arr.len()).find(|&i| arr[..i].iter().any(|v| equal(v, &arr[i])));
+                if let Some(i) = duplicate {
+                    self.error(format!("item {} is a duplicate", i));
+                }
+            }
+            Some(Value::Bool(false)) | None => {}
+            Some(_) => return Err(invalid_schema("uniqueItems", "a boolean")),
+        }
+        match schema.get("items") {
+            Some(Value::Array(items)) => {
+                for (i, (sub, v)) in items.iter().zip(arr).enumerate() {
+                    self.validate_child(sub, &i.to_string(), v)?;
+                }
+                if let Some(additional) = schema.get("additionalItems") {
+                    for (i, v) in arr.iter().enumerate().skip(items.len()) {
+                        self.validate_child(additional, &i.to_string(), v)?;
+                    }
+                }
+            }
+            Some(sub) => {
+                for (i, v) in arr.iter().enumerate() {
+                    self.validate_child(sub, &i.to_string(), v)?;
+                }
+            }
+            None => {}
+        }
+        if let Some(sub) = schema.get("contains") {
+            let mut found = false;
+            for v in arr {
+                found |= self.is_valid(sub, v)?;
+            }
+            if !found {
+                self.error("no item matches the schema of 'contains'".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_object(
+        &mut self,
+        schema: &Map<String, Value>,
+        obj: &Map<String, Value>,
+    ) -> Result<(), Error> {
+        if let Some(min) = get_count(schema, "minProperties")? {
+            if obj.len() < min {
+                self.error(format!("object has fewer than {} properties", min));
+            }
+        }
+        if let Some(max) = get_count(schema, "maxProperties")? {
+            if obj.len() > max {
+                self.error(format!("object has more than {} properties", max));
+            }
+        }
+        match schema.get("required") {
+            Some(Value::Array(names)) => {
+                for name in names {
+                    let name = name
+                        .as_str()
+                        .ok_or_else(|| invalid_schema("required", "an array of strings"))?;
+                    if !obj.contains_key(name) {
+                        self.error(format!("missing required property '{}'", name));
+                    }
+                }
+            }
+            Some(_) => return Err(invalid_schema("required", "an array of strings")),
+            None => {}
+        }
+        let properties = match schema.get("properties") {
+            Some(Value::Object(properties)) => Some(properties),
+            Some(_) => return Err(invalid_schema("properties", "an object")),
+            None => None,
+        };
+        for (k, v) in obj {
+            if let Some(names) = schema.get("propertyNames") {
+                if !self.is_valid(names, &Value::String(k.clone()))? {
+                    self.error(format!(
+                        "property name '{}' does not match the schema of 'propertyNames'",
+                        k
+                    ));
+                }
+            }
+            match properties.and_then(|p| p.get(k)) {
+                Some(sub) => self.validate_child(sub, k, v)?,
+                None => {
+                    if let Some(additional) = schema.get("additionalProperties") {
+                        self.validate_child(additional, k, v)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn errors(schema: Value, value: Value) -> Vec<(String, String)> {
+        validate(&schema, &value)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.path, e.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_types() {
+        assert!(errors(json!({"type": "integer"}), json!(1.0)).is_empty());
+        assert!(errors(json!({"type": ["string", "null"]}), json!(null)).is_empty());
+        assert_eq!(
+            errors(json!({"type": "integer"}), json!(1.5)),
+            vec![("".to_string(), "expected integer, got number".to_string())]
+        );
+        assert!(errors(json!({"enum": [1, "a"]}), json!(1.0)).is_empty());
+        assert_eq!(errors(json!(false), json!(1)).len(), 1);
+    }
+
+    #[test]
+    fn test_paths() {
+        let schema = json!({
+            "properties": {"a/b": {"items": {"type": "string"}}},
+            "additionalProperties": false,
+            "required": ["c"]
+        });
+        let res = errors(schema, json!({"a/b": ["x", 1], "d": 2}));
+        let paths: Vec<&str> = res.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(paths, vec!["", "/a~1b/1", "/d"]);
+    }
+
+    #[test]
+    fn test_combinators() {
+        let schema = json!({"oneOf": [{"minimum": 0}, {"maximum": 10}]});
+        assert!(errors(schema.clone(), json!(-1)).is_empty());
+        assert_eq!(errors(schema, json!(5)).len(), 1);
+        let schema =
+            json!({"if": {"type": "string"}, "then": {"minLength": 2}, "else": {"const": 0}});
+        assert!(errors(schema.clone(), json!("ab")).is_empty());
+        assert_eq!(errors(schema.clone(), json!("a")).len(), 1);
+        assert_eq!(errors(schema, json!(1)).len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_schema() {
+        assert!(validate(&json!({"type": 1}), &json!(1)).is_err());
+        assert!(validate(&json!({"minLength": -1}), &json!("a")).is_err());
+        assert!(validate(&json!({"items": {"$ref": "#"}}), &json!([1])).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+// Sharing of identical documents between keys
+//
+// Documents set as a whole are looked up among the recently set ones, and a key set to the same
+// document as another key shares its memory. A shared document is copied when one of its keys
+// is modified (see `RedisJSON::data_mut`), so the keys never see each other's changes.
+//
+// Looking a document up costs a pass over it to hash it, on every JSON.SET of a whole document,
+// and another one to compare it when a document with the same hash is found. It is only done
+// with the SHARE_DOCS configuration, for datasets where many keys hold the same document.
+
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Weak};
+
+// Number of documents that can be looked up, the entries of dropped documents are removed
+// when it is reached
+const SHARED_DOCS_CAPACITY: usize = 1024;
+
+thread_local! {
+    // Hash of a document -> the document, as long as a key holds it
+    static SHARED_DOCS: RefCell<HashMap<u64, Weak<Value>>> = RefCell::new(HashMap::new());
+}
+
+/// Returns `value` as a shared document, the one of another key if it is identical
+pub fn share(value: Value) -> Arc<Value> {
+    let hash = hash_value(&value);
+    SHARED_DOCS.with(|docs| {
+        let mut docs = docs.borrow_mut();
+        if let Some(doc) = docs.get(&hash).and_then(Weak::upgrade) {
+            if identical(&doc, &value) {
+                return doc;
+            }
+        }
+        if docs.len() >= SHARED_DOCS_CAPACITY {
+            docs.retain(|_, doc| doc.strong_count() > 0);
+            if docs.len() >= SHARED_DOCS_CAPACITY {
+                docs.clear();
+            }
+        }
+        let doc = Arc::new(value);
+        docs.insert(hash, Arc::downgrade(&doc));
+        doc
+    })
+}
+
+/// Hashes a value iteratively, object members in order
+fn hash_value(value: &Value) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let mut stack = vec![value];
+    while let Some(v) = stack.pop() {
+        match v {
+            Value::Null => 0u8.hash(&mut hasher),
+            Value::Bool(b) => (1u8, b).hash(&mut hasher),
+            Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => (2u8, i).hash(&mut hasher),
+                (None, Some(u)) => (3u8, u).hash(&mut hasher),
+                _ => (4u8, n.as_f64().unwrap().to_bits()).hash(&mut hasher),
+            },
+            Value::String(s) => (5u8, s).hash(&mut hasher),
+            Value::Array(arr) => {
+                (6u8, arr.len()).hash(&mut hasher);
+                stack.extend(arr.iter().rev());
+            }
+            Value::Object(obj) => {
+                (7u8, obj.len()).hash(&mut hasher);
+                for (k, v) in obj.iter().rev() {
+                    k.hash(&mut hasher);
+                    stack.push(v);
+                }
+            }
+        }
+    }
+    hasher.finish()
+}
+
+/// Whether two values are the same, including the order of their object members which `==`
+/// ignores, so sharing a document never reorders it
+fn identical(a: &Value, b: &Value) -> bool {
+    let mut stack = vec![(a, b)];
+    while let Some(pair) = stack.pop() {
+        match pair {
+            (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+                stack.extend(a.iter().zip(b.iter()))
+            }
+            (Value::Object(a), Value::Object(b)) if a.len() == b.len() => {
+                for ((ka, va), (kb, vb)) in a.iter().zip(b.iter()) {
+                    if ka != kb {
+                        return false;
+                    }
+                    stack.push((va, vb));
+                }
+            }
+            (Value::Array(_), _) | (Value::Object(_), _) => return false,
+            (a, b) if a != b => return false,
+            _ => {}
+        }
+    }
+    true
+}
@@ -4,6 +4,9 @@ import sys
 import os
 import redis
 import json
+import gzip
+import random
+import socket
 from RLTest import Env
 from includes import *
 
@@ -75,13 +78,13 @@ docs = {
 
 #----------------------------------------------------------------------------------------------
 
-# def getCacheInfo(env):
-#     r = env
-#     res = r.cmd('JSON._CACHEINFO')
-#     ret = {}
-#     for x in range(0, len(res), 2):
-#         ret[res[x]] = res[x+1]
-#     return ret
+def getCacheInfo(env):
+    r = env
+    res = r.execute_command('JSON._CACHEINFO')
+    ret = {}
+    for x in range(0, len(res), 2):
+        ret[res[x]] = res[x+1]
+    return ret
 
 
 def assertOk(r, x, msg=None):
@@ -212,6 +215,89 @@ def testSetWithPathErrors(env):
     r.expect('JSON.SET', 'x', '$[0]', 1).raiseError()
     # r.assertEqual(str(e.exception), 'Err: path not an object')
 
+def testSetCreatePath(env):
+    """Test JSON.SET with CREATEPATH"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'x', '.', '{"a":{},"arr":[],"s":"str"}'))
+    r.expect('JSON.SET', 'x', '$.a.b.c', 1).raiseError()
+    r.assertOk(r.execute_command('JSON.SET', 'x', '$.a.b.c', 1, 'CREATEPATH'))
+    r.assertOk(r.execute_command('JSON.SET', 'x', '.m.n', '"v"', 'NX', 'CREATEPATH'))
+    r.assertOk(r.execute_command('JSON.SET', 'x', '.a.b.d', 2, 'createpath'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'x')),
+                  {'a': {'b': {'c': 1, 'd': 2}}, 'arr': [], 's': 'str', 'm': {'n': 'v'}})
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '.p.q', 1, 'XX', 'CREATEPATH'), None)
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '.a.b.c', 3, 'NX', 'CREATEPATH'), None)
+
+    # array elements and members of non-objects can not be created, and nothing is left behind
+    r.expect('JSON.SET', 'x', '.y.z[0].w', 1, 'CREATEPATH').error().contains("cannot create array element")
+    r.expect('JSON.SET', 'x', '.arr[0].w', 1, 'CREATEPATH').error().contains("cannot create array element")
+    r.expect('JSON.SET', 'x', '.s.t.u', 1, 'CREATEPATH').error().contains("non-object")
+    r.expect('JSON.SET', 'x', '$..b.e', 1, 'CREATEPATH').error().contains("static path")
+    r.assertEqual(r.execute_command('JSON.TYPE', 'x', '.y'), None)
+    # nor when the value can not be set once the parents are created
+    r.expect('JSON.SET', 'x', '.g.h', '"str"', 'GT', 'CREATEPATH').error().contains('require a number')
+    r.assertEqual(r.execute_command('JSON.TYPE', 'x', '.g'), None)
+    r.expect('JSON.COPYPATH', 'x', '.arr', '.g.h[0]', 'CREATE').raiseError()
+    r.assertEqual(r.execute_command('JSON.TYPE', 'x', '.g'), None)
+    r.expect('JSON.SET', 'other', '.g', '"str"', 'LT', 'CREATEPATH').error().contains('require a number')
+    r.assertEqual(r.execute_command('EXISTS', 'other'), 0)
+
+    # a new key starts as an object
+    r.assertOk(r.execute_command('JSON.SET', 'new', '.a.b', '[1]', 'CREATEPATH'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'new')), {'a': {'b': [1]}})
+    r.expect('JSON.SET', 'other', '.a[0]', 1, 'CREATEPATH').raiseError()
+    r.assertEqual(r.execute_command('EXISTS', 'other'), 0)
+
+def testSetGreaterLess(env):
+    """Test JSON.SET with GT and LT"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'x', '.', '{"score":10,"f":1.5,"s":"str","arr":[1,5]}'))
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '.score', 5, 'GT'), None)
+    r.assertOk(r.execute_command('JSON.SET', 'x', '.score', 20, 'GT'))
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '.score', 20, 'GT'), None)
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '.score', 30, 'LT'), None)
+    r.assertOk(r.execute_command('JSON.SET', 'x', '.score', 2.5, 'lt'))
+    r.assertOk(r.execute_command('JSON.SET', 'x', '.f', 2, 'GT', 'XX'))
+    r.assertEqual(r.execute_command('JSON.GET', 'x', '.score'), '2.5')
+    r.assertEqual(r.execute_command('JSON.GET', 'x', '.f'), '2')
+
+    # only the matches the condition holds for are replaced
+    r.assertOk(r.execute_command('JSON.SET', 'x', '$.arr[*]', 3, 'GT'))
+    r.assertEqual(r.execute_command('JSON.GET', 'x', '.arr'), '[3,5]')
+
+    # a missing value is added unless XX is given
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '.new', 1, 'XX', 'GT'), None)
+    r.assertOk(r.execute_command('JSON.SET', 'x', '.new', 1, 'GT'))
+    r.assertEqual(r.execute_command('JSON.GET', 'x', '.new'), '1')
+
+    r.expect('JSON.SET', 'x', '.s', 1, 'GT').error().contains("current value to be a number")
+    r.expect('JSON.SET', 'x', '.score', '"a"', 'GT').error().contains("require a number")
+    r.expect('JSON.SET', 'x', '.score', 1, 'GT', 'LT').error().contains("syntax error")
+    r.expect('JSON.SET', 'x', '.score', 1, 'NX', 'GT').error().contains("syntax error")
+    r.expect('JSON.SET', 'x', '.score', 1, 'LT', 'NX').error().contains("syntax error")
+
+def testSetGet(env):
+    """Test JSON.SET with GET"""
+    r = env
+
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '.', '{"a":1,"b":[1]}', 'GET'), None)
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '.a', '2', 'GET'), '1')
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '.c', '3', 'get'), None)
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '$.b', '"s"', 'GET'), '[[1]]')
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '$.d', '4', 'GET'), None)
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'x')), {'a': 2, 'b': 's', 'c': 3, 'd': 4})
+
+    # the previous value is replied even when the conditions prevent setting
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '.a', '5', 'NX', 'GET'), '2')
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '.e', '5', 'XX', 'GET'), None)
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '.a', '1', 'GT', 'GET'), '2')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'x')), {'a': 2, 'b': 's', 'c': 3, 'd': 4})
+
+    r.assertEqual(r.execute_command('JSON.SET', 'x', '.', '[]', 'GET'), '{"a":2,"b":"s","c":3,"d":4}')
+    r.expect('JSON.SET', 'x', '.a', 1, 'GET', 'nope').error().contains("syntax error")
+
 def testGetNonExistantPathsFromBasicDocumentShouldFail(env):
     """Test failure of getting non-existing values"""
 
@@ -265,6 +351,265 @@ def testGetFormatting(env):
                     res = r.execute_command('JSON.GET', list(o.keys()).pop(), 'INDENT', indent, 'NEWLINE', newline, 'SPACE', space)
                     r.assertEqual(res, f.format(newline=newline, space=space, indent=indent))
 
+def testGetLargeReplies(env):
+    """Test that replies larger than the reused serialization buffer are replied whole"""
+    r = env
+
+    big = {'s': 'x' * (3 << 20), 'arr': list(range(1000))}
+    r.assertOk(r.execute_command('JSON.SET', 'big', '$', json.dumps(big)))
+    r.assertOk(r.execute_command('JSON.SET', 'small', '$', '{"a":[1]}'))
+    for _ in range(2):
+        r.assertEqual(json.loads(r.execute_command('JSON.GET', 'big')), big)
+        r.assertEqual(r.execute_command('JSON.GET', 'small', 'INDENT', ' ', 'NEWLINE', '\n'), '{\n "a":[\n  1\n ]\n}')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'big', '$.arr', '$.s')), {'$.arr': [big['arr']], '$.s': [big['s']]})
+
+def testPreserveKeyOrder(env):
+    """Test that objects keep their members in insertion order"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"b":1,"a":2}'))
+    r.assertEqual(r.execute_command('JSON.OBJKEYS', 'test', '.'), ['b', 'a'])
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"b":1,"a":2}')
+
+    # new members are added last and replaced members keep their place
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.c', '{"z":0,"y":[{"n":1,"m":2}]}'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.b', '3'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"b":3,"a":2,"c":{"z":0,"y":[{"n":1,"m":2}]}}')
+
+    # removing a member does not move the others
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.d', '4'))
+    r.assertEqual(1, r.execute_command('JSON.DEL', 'test', '.b'))
+    r.assertEqual(r.execute_command('JSON.OBJKEYS', 'test', '.'), ['a', 'c', 'd'])
+    r.assertEqual([json.loads(v) for v in r.execute_command('JSON.OBJVALUES', 'test')],
+                  [2, {'z': 0, 'y': [{'n': 1, 'm': 2}]}, 4])
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'INDENT', ' ', 'NEWLINE', '\n', '.c'),
+                  '{\n "z":0,\n "y":[\n  {\n   "n":1,\n   "m":2\n  }\n ]\n}')
+
+    # the order survives a reload
+    for _ in r.retry_with_rdb_reload():
+        r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"a":2,"c":{"z":0,"y":[{"n":1,"m":2}]},"d":4}')
+
+def testGetSortKeys(env):
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"b":{"y":1,"x":[{"d":1,"c":2}]},"a":1,"\u00e9":2,"z":3}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'SORTKEYS'),
+                  '{"a":1,"b":{"x":[{"c":2,"d":1}],"y":1},"z":3,"\u00e9":2}')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.b', 'SORTKEYS', 'SPACE', ' '),
+                  '[{"x": [{"c": 2,"d": 1}],"y": 1}]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'sortkeys', '$.z', '$.a'), '{"$.a":[1],"$.z":[3]}')
+    r.assertEqual(r.execute_command('JSON.MGET', 'test', '.b.x', 'SORTKEYS'), ['[{"c":2,"d":1}]'])
+
+def testGetPretty(env):
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":[1,2],"b":{}}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'PRETTY'), '{\n\t"a": [\n\t\t1,\n\t\t2\n\t],\n\t"b": {}\n}')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'PRETTY'),
+                  r.execute_command('JSON.GET', 'test', 'INDENT', '\t', 'NEWLINE', '\n', 'SPACE', ' '))
+    # Explicit options win regardless of their position
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'INDENT', '  ', 'pretty', '$.a'), '[\n  [\n    1,\n    2\n  ]\n]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'PRETTY', 'SPACE', '', 'NEWLINE', '', '$.b'), '[\t{}]')
+    r.assertEqual(r.execute_command('JSON.MGET', 'test', '$.a', 'PRETTY'), ['[\n\t1,\n\t2\n]'])
+
+def testGetIndentSize(env):
+    """Test JSON.GET INDENTSIZE"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":[1,2],"b":{}}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'INDENTSIZE', 2, 'NEWLINE', '\n'),
+                  r.execute_command('JSON.GET', 'test', 'INDENT', '  ', 'NEWLINE', '\n'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'indentsize', 4, 'PRETTY', '$.a'), '[\n    [\n        1,\n        2\n    ]\n]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'INDENTSIZE', 0, 'PRETTY', '.a'), '[\n1,\n2\n]')
+    r.assertEqual(r.execute_command('JSON.MGET', 'test', '.a', 'INDENTSIZE', 1, 'NEWLINE', '\n'), ['[\n 1,\n 2\n]'])
+    r.expect('JSON.GET', 'test', 'INDENTSIZE', 2, 'INDENT', ' ').error().contains('can not be combined')
+    r.expect('JSON.GET', 'test', 'INDENT', ' ', 'INDENTSIZE', 2).error().contains('can not be combined')
+    r.expect('JSON.GET', 'test', 'INDENTSIZE', 11).error().contains('between 0 and 10')
+    r.expect('JSON.GET', 'test', 'INDENTSIZE', -1).raiseError()
+    r.expect('JSON.GET', 'test', 'INDENTSIZE', 'two').raiseError()
+
+def testGetRootName(env):
+    """Test JSON.GET ROOTNAME"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"id":1,"tags":["a"]}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'ROOTNAME', 'user'), '{"user":{"id":1,"tags":["a"]}}')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'ROOTNAME', 'ids', '$..id'), '{"ids":[1]}')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', 'rootname', 'r', '.id', '.tags')), {'r': {'.id': 1, '.tags': ['a']}})
+    # The wrapping object is formatted as the rest of the reply
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'ROOTNAME', 'tags', 'INDENT', '  ', 'NEWLINE', '\n', 'SPACE', ' ', '.tags'),
+                  '{\n  "tags": [\n    "a"\n  ]\n}')
+    r.assertEqual(r.execute_command('JSON.MGET', 'test', '.id', 'ROOTNAME', 'id'), ['{"id":1}'])
+    r.expect('JSON.GET', 'test', 'ROOTNAME').raiseError()
+
+def testGetLimit(env):
+    """Test JSON.GET LIMIT"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"arr":[0,1,2,3,[4,5,6]],"obj":{"a":1},"n":7}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'LIMIT', 1, 2, '.arr'), '[1,2]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'limit', 3, 10, '.arr'), '[3,[4,5,6]]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'LIMIT', 5, 1, '.arr'), '[]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'LIMIT', 0, 0, '.arr'), '[]')
+    # each match of a JSONPath is windowed
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'LIMIT', 0, 1, '$..arr'), '[[0]]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'LIMIT', 0, 1, '$.arr[4]'), '[[4]]')
+    # ignored for anything but arrays
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'LIMIT', 0, 1, '.obj'), '{"a":1}')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'LIMIT', 0, 1, '.n'), '7')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', 'LIMIT', 1, 1, '.arr', '.n')), {'.arr': [1], '.n': 7})
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'LIMIT', 1, 1, 'INDENT', ' ', 'NEWLINE', '\n', '.arr'), '[\n 1\n]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.arr'), '[0,1,2,3,[4,5,6]]')
+
+    r.expect('JSON.GET', 'test', 'LIMIT', 1).raiseError()
+    r.expect('JSON.GET', 'test', 'LIMIT', -1, 2, '.arr').raiseError()
+
+def testCompressGzip(env):
+    """Test JSON.GET and JSON.SET with COMPRESS GZIP"""
+    r = env
+    conn = r.getConnection()
+
+    doc = {'a': [1, 2, 3], 'b': {'c': 'x' * 100}}
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', gzip.compress(json.dumps(doc).encode()), 'COMPRESS', 'GZIP'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test')), doc)
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.a[0]', gzip.compress(b'10'), 'FORMAT', 'JSON', 'compress', 'gzip'))
+
+    reply = conn.execute_command('JSON.GET', 'test', 'COMPRESS', 'GZIP', NEVER_DECODE=True)
+    r.assertEqual(reply[:2], b'\x1f\x8b')
+    r.assertEqual(gzip.decompress(reply).decode(), r.execute_command('JSON.GET', 'test'))
+    reply = conn.execute_command('JSON.GET', 'test', 'INDENT', ' ', 'COMPRESS', 'GZIP', '.a', '.b.c', NEVER_DECODE=True)
+    r.assertEqual(gzip.decompress(reply).decode(), r.execute_command('JSON.GET', 'test', 'INDENT', ' ', '.a', '.b.c'))
+    reply = conn.execute_command('JSON.MGET', 'test', 'missing', '.a', 'COMPRESS', 'GZIP', NEVER_DECODE=True)
+    r.assertEqual(gzip.decompress(reply[0]), b'[10,2,3]')
+    r.assertEqual(reply[1], None)
+    r.assertEqual(r.execute_command('JSON.GET', 'missing', 'COMPRESS', 'GZIP'), None)
+
+    r.expect('JSON.GET', 'test', 'COMPRESS', 'ZSTD').error().contains('COMPRESS must be GZIP')
+    r.expect('JSON.SET', 'test', '.', '{"a":1}', 'COMPRESS', 'GZIP').error().contains('invalid gzip data')
+    r.expect('JSON.SET', 'test', '.', gzip.compress(b'\xff'), 'COMPRESS', 'GZIP').error().contains('not valid UTF-8')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '.a')), [10, 2, 3])
+
+def testGetNdjson(env):
+    """Test JSON.GET FORMAT NDJSON"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"arr":[{"b":2,"a":1},[1,2],"s",null],"n":1,"o":{"arr":[true]}}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'FORMAT', 'NDJSON', '.arr'), '{"b":2,"a":1}\n[1,2]\n"s"\nnull\n')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'format', 'ndjson', 'SORTKEYS', 'LIMIT', 0, 2, '.arr'), '{"a":1,"b":2}\n[1,2]\n')
+    # a JSONPath writes each match on its own line
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'FORMAT', 'NDJSON', '$..arr'), '[{"b":2,"a":1},[1,2],"s",null]\n[true]\n')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'FORMAT', 'NDJSON', '$.missing'), '')
+    r.assertEqual(r.execute_command('JSON.MGET', 'test', '.o.arr', 'FORMAT', 'NDJSON'), ['true\n'])
+    # the last FORMAT wins
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'FORMAT', 'NDJSON', 'FORMAT', 'JSON', '.o.arr'), '[true]')
+
+    r.expect('JSON.GET', 'test', 'FORMAT', 'NDJSON', '.n').error().contains('requires the value to be an array')
+    r.expect('JSON.GET', 'test', 'FORMAT', 'NDJSON', '.arr', '.n').error().contains('requires a single path')
+    r.expect('JSON.GET', 'test', 'FORMAT', 'NDJSON', 'PRETTY', '.arr').error().contains('NEWLINE')
+    r.expect('JSON.SET', 'test', '.n', '2', 'FORMAT', 'NDJSON').raiseError()
+
+def testGetExclude(env):
+    """Test JSON.GET EXCLUDE"""
+    r = env
+
+    doc = {'name': 'a', 'password': 'secret', 'tokens': [{'id': 1, 'key': 'k1'}, {'id': 2, 'key': 'k2'}]}
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', json.dumps(doc)))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', 'EXCLUDE', '$.password')),
+                  {'name': 'a', 'tokens': [{'id': 1, 'key': 'k1'}, {'id': 2, 'key': 'k2'}]})
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', 'EXCLUDE', '$.password', 'EXCLUDE', '$..key', '$.tokens')),
+                  [[{'id': 1}, {'id': 2}]])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', 'EXCLUDE', '$.tokens[*]', '.tokens')), [])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', 'EXCLUDE', '$.nope', '.password')), 'secret')
+    # the members left keep their order
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'EXCLUDE', '$.name'),
+                  '{"password":"secret","tokens":[{"id":1,"key":"k1"},{"id":2,"key":"k2"}]}')
+
+    # the stored document is not changed
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test')), doc)
+
+    r.assertOk(r.execute_command('JSON.SET', 'other', '$', '{"password":"p"}'))
+    r.assertEqual(r.execute_command('JSON.MGET', 'test', 'other', 'missing', '$', 'EXCLUDE', '$.password'),
+                  [json.dumps([{'name': 'a', 'tokens': doc['tokens']}], separators=(',', ':')), '[{}]', None])
+
+    r.expect('JSON.GET', 'test', 'EXCLUDE', '$').raiseError()
+    r.expect('JSON.GET', 'test', 'EXCLUDE', '$[').raiseError()
+    r.expect('JSON.GET', 'test', 'EXCLUDE').raiseError()
+
+def testGetDefault(env):
+    """Test JSON.GET DEFAULT"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":1,"n":null}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'DEFAULT', '0', '.missing'), '0')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'DEFAULT', '0', '.a'), '1')
+    # an existing null is not missing
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'DEFAULT', '0', '.n'), 'null')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'default', '{"b":2,"a":1}', 'SORTKEYS', '$.missing'), '[{"a":1,"b":2}]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'DEFAULT', '"x"', '$.a'), '[1]')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', 'DEFAULT', '[]', '.a', '.b', '$.c')),
+                  {'.a': 1, '.b': [], '$.c': []})
+    r.assertEqual(r.execute_command('JSON.MGET', 'test', 'missing', '.b', 'DEFAULT', 'false'), ['false', None])
+    r.assertEqual(r.execute_command('JSON.GET', 'missing', 'DEFAULT', '0'), None)
+
+    r.expect('JSON.GET', 'test', 'DEFAULT', '{"a":', '.missing').error().contains('failed to parse DEFAULT')
+    r.expect('JSON.GET', 'test', 'DEFAULT', '0', 'REQUIRE', '.missing').error().contains('REQUIRE')
+    r.expect('JSON.GET', 'test', 'DEFAULT').raiseError()
+
+def testGetNoEscape(env):
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"emoji":"\U0001F600 caf\u00e9","ctrl":"a\\n\\"b"}'))
+    for args in [[], ['NOESCAPE'], ['noescape', 'INDENT', '']]:
+        r.assertEqual(r.execute_command('JSON.GET', 'test', *(args + ['$.emoji'])), '["\U0001F600 caf\u00e9"]')
+        r.assertEqual(r.execute_command('JSON.GET', 'test', *(args + ['$.ctrl'])), '["a\\n\\"b"]')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', 'NOESCAPE')), {'emoji': '\U0001F600 caf\u00e9', 'ctrl': 'a\n"b'})
+
+    # control characters, quotes and backslashes are always escaped, so the reply stays valid JSON
+    value = 'nl\n tab\t quote" backslash\\ nul\u0000 us\u001f del\u007f \u00e9'
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', json.dumps(value, ensure_ascii=False)))
+    for args in [[], ['NOESCAPE'], ['NOESCAPE', 'PRETTY']]:
+        res = r.execute_command('JSON.GET', 'test', *args)
+        r.assertEqual(res, '"nl\\n tab\\t quote\\" backslash\\\\ nul\\u0000 us\\u001f del\u007f \u00e9"', message=args)
+        r.assertFalse(any(ord(c) < 0x20 for c in res), message=args)
+        r.assertEqual(json.loads(res), value)
+
+def testUnicodeInput(env):
+    """Test that escaped surrogate pairs are combined, and that lone surrogates and invalid UTF-8
+    are rejected instead of stored"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '["\\uD83D\\uDE00", "\\u00e9"]'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '["\U0001F600","\u00e9"]')
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '[0]'), 1)
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '[0]', 'BYTES'), 4)
+
+    for value in ['"\\uD83D"', '"\\uDE00"', '"\\uDE00\\uD83D"', '"\\uD83D\\u0041"', '"\\uD83D\\uD83D"',
+                  '"\\uD83"', '"\\u"', '"\\uZZZZ"', '"\\x41"', '"\\', '"\\uD83D\\']:
+        r.expect('JSON.SET', 'test', '$', value).raiseError()
+        r.expect('JSON.ARRAPPEND', 'test', '$', value).raiseError()
+    for value in [b'"\xff"', b'"\xc3"', b'"\xed\xa0\x80"', b'"\xf4\x90\x80\x80"', b'["a", "\x80"]']:
+        r.expect('JSON.SET', 'test', '$', value).error().contains('invalid UTF-8')
+        r.expect('JSON.ARRAPPEND', 'test', '$', '1', value).error().contains('(item 1)')
+        r.expect('JSON.ARRINSERT', 'test', '$', 0, value).raiseError()
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '["\U0001F600","\u00e9"]')
+
+    # whatever is accepted is stored as Python decodes it, and is replied as valid JSON
+    rnd = random.Random(871)
+    pieces = ['\\uD83D', '\\uDE00', '\\u0041', '\\u00', '\\', '\\n', '\\"', 'a', '\u00e9', '\U0001F600', '\\u']
+    for _ in range(500):
+        value = '"' + ''.join(rnd.choice(pieces) for _ in range(rnd.randint(1, 6))) + '"'
+        try:
+            expected = json.loads(value)
+            expected.encode('utf-8')
+        except (ValueError, UnicodeEncodeError):
+            expected = None
+        try:
+            r.execute_command('JSON.SET', 'fuzz', '$', value)
+        except redis.exceptions.ResponseError:
+            r.assertIsNone(expected, message=value)
+            continue
+        r.assertEqual(json.loads(r.execute_command('JSON.GET', 'fuzz')), expected, message=value)
+
 def testBackwardRDB(env):
     env.skipOnCluster() 
     dbFileName = env.cmd('config', 'get', 'dbfilename')[1]
@@ -280,8 +625,20 @@ def testBackwardRDB(env):
     env.start()
 
     r = env
+    expected = {"a":{"b":[{"c":{"d":[1,'2'],"e":None}},True],"a":'a'},"b":1,"c":True,"d":None}
     data = json.loads(r.execute_command('JSON.GET', 'complex'))
-    r.assertEqual(data, {"a":{"b":[{"c":{"d":[1,'2'],"e":None}},True],"a":'a'},"b":1,"c":True,"d":None})
+    r.assertEqual(data, expected)
+    # Saved by RedisJSON 1.x, and still reported so once it is modified
+    r.assertEqual(r.execute_command('JSON.DEBUG', 'VERSION', 'complex'), 0)
+    r.assertOk(r.execute_command('JSON.SET', 'complex', '.b', '1'))
+    r.assertEqual(r.execute_command('JSON.DEBUG', 'VERSION', 'complex'), 0)
+
+    # Saved again in the current encoding, and loaded back from it
+    for _ in r.retry_with_rdb_reload():
+        data = json.loads(r.execute_command('JSON.GET', 'complex'))
+        r.assertEqual(data, expected)
+        r.assertEqual(r.execute_command('JSON.TYPE', 'complex', '.a.b[1]'), 'boolean')
+        r.assertEqual(r.execute_command('JSON.DEBUG', 'VERSION', 'complex'), 4)
 
 def testSetBSON(env):
     r = env
@@ -289,6 +646,72 @@ def testSetBSON(env):
     r.assertOk(r.execute_command('JSON.SET', 'test', '.', bson, 'FORMAT', 'BSON'))
     data = json.loads(r.execute_command('JSON.GET', 'test', *docs['values'].keys()))
 
+def testFormatRoundTrip(env):
+    """Test that the reply of JSON.GET in each FORMAT is set back by JSON.SET with the same FORMAT"""
+    r = env
+    conn = r.getConnection()
+
+    doc = {'a': [1, 2.0, -3.5e-7, 'x', '2.0', True, False, None, [], {}], 'b': {'c': {'d': 'caf\u00e9'}}, 'e': ''}
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', json.dumps(doc)))
+    expected = r.execute_command('JSON.GET', 'test')
+    for get_format in ['JSON', 'BSON', 'RESP']:
+        for path in ['.', '$', '.b']:
+            reply = conn.execute_command('JSON.GET', 'test', 'FORMAT', get_format, path, NEVER_DECODE=True)
+            r.assertOk(r.execute_command('JSON.SET', 'copy', '.', reply, 'FORMAT', get_format), message=get_format)
+            r.assertEqual(r.execute_command('JSON.GET', 'copy'), r.execute_command('JSON.GET', 'test', path),
+                          message=(get_format, path))
+        for set_format in ['JSON', 'BSON', 'RESP']:
+            # each format is set, then replied in every format and set back as JSON
+            reply = conn.execute_command('JSON.GET', 'test', 'FORMAT', set_format, NEVER_DECODE=True)
+            r.assertOk(r.execute_command('JSON.SET', 'copy', '.', reply, 'FORMAT', set_format))
+            reply = conn.execute_command('JSON.GET', 'copy', 'FORMAT', get_format, NEVER_DECODE=True)
+            r.assertOk(r.execute_command('JSON.SET', 'copy', '.', reply, 'FORMAT', get_format))
+            r.assertEqual(r.execute_command('JSON.GET', 'copy'), expected, message=(set_format, get_format))
+
+    # several paths are replied as an object keyed by the paths
+    reply = conn.execute_command('JSON.GET', 'test', 'FORMAT', 'BSON', '.e', '.b.c', NEVER_DECODE=True)
+    r.assertOk(r.execute_command('JSON.SET', 'copy', '.', reply, 'FORMAT', 'BSON'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'copy')), {'.e': '', '.b.c': {'d': 'caf\u00e9'}})
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'FORMAT', 'RESP', '.a[1]'), '+2.0\r\n')
+    r.assertEqual(r.execute_command('JSON.MGET', 'test', 'missing', '.e', 'FORMAT', 'RESP'), ['$0\r\n\r\n', None])
+
+    # with GET the previous value is replied as BSON too
+    bson = conn.execute_command('JSON.GET', 'test', 'FORMAT', 'BSON', '.b', NEVER_DECODE=True)
+    reply = conn.execute_command('JSON.SET', 'copy', '.', bson, 'FORMAT', 'BSON', 'GET', NEVER_DECODE=True)
+    r.assertOk(r.execute_command('JSON.SET', 'other', '.', reply, 'FORMAT', 'BSON'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'other')), {'.e': '', '.b.c': {'d': 'caf\u00e9'}})
+
+def testPathFunctions(env):
+    """Test the functions a JSON.GET path can end with"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"items":[1,"a",{}],"obj":{"a":1,"b":2},"s":"caf\u00e9",'
+                                 '"nums":[3,1.5,-2,4],"ints":[3,1,2],"empty":[],"sub":[{"n":[1,2]},{"n":[5]}]}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.items.length()'), '[3]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.obj.length()'), '2')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.s.length()'), '4')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.nums.sum()'), '6.5')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.ints.sum()'), '6')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.ints.avg()'), '2.0')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.nums.min()'), '-2')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.nums.max()'), '4')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.empty.sum()'), '[0]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.empty.avg()'), '[null]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.sub[*].n.max()'), '[2,5]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.missing.length()'), '[]')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '.ints.sum()', '.obj.length()')),
+                  {'.ints.sum()': 6, '.obj.length()': 2})
+    r.assertEqual(r.execute_command('JSON.MGET', 'test', 'missing', '.ints.max()'), ['3', None])
+    r.assertEqual(r.execute_command('JSON.MGET', 'missing', '$.sub[*].n.max()'), [None])
+    r.assertEqual(r.execute_command('JSON.MGET', 'test', 'missing', '$.sub[*].n.max()'), ['[2,5]', None])
+    r.assertOk(r.execute_command('JSON.VALIDATEPATH', '$.items.length()'))
+    r.assertOk(r.execute_command('JSON.VALIDATEPATH', '.nums.sum()'))
+
+    r.expect('JSON.GET', 'test', '.items.sum()').error().contains('array of numbers')
+    r.expect('JSON.GET', 'test', '.s.avg()').error().contains('array of numbers')
+    r.expect('JSON.GET', 'test', '.ints[0].length()').error().contains('length()')
+    r.expect('JSON.GET', 'test', '.missing.length()').raiseError()
+
 def testMgetCommand(env):
     """Test REJSON.MGET command"""
     r = env
@@ -330,6 +753,54 @@ def testMgetCommand(env):
     r.assertEqual(raw[1], None)
     r.assertTrue(json.loads(raw[2]))
 
+def testMgetFormattingAndMultiPath(env):
+    """Test JSON.MGET formatting options and multiple paths"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'doc:1', '.', '{"a":[1],"b":"x"}'))
+    r.assertOk(r.execute_command('JSON.SET', 'doc:2', '.', '{"a":[2]}'))
+
+    # formatting options follow the path
+    res = r.execute_command('JSON.MGET', 'doc:1', 'doc:2', 'missing', '.a', 'INDENT', '\t', 'NEWLINE', '\n')
+    r.assertEqual(res, ['[\n\t1\n]', '[\n\t2\n]', None])
+    res = r.execute_command('JSON.MGET', 'doc:1', '.', 'SPACE', ' ', 'INDENT', '')
+    r.assertEqual(res, ['{"a": [1],"b": "x"}'])
+
+    # additional paths reply an object per key
+    res = r.execute_command('JSON.MGET', 'doc:1', 'doc:2', 'missing', '.a', 'FORMAT', 'JSON', '.b')
+    r.assertEqual(len(res), 3)
+    r.assertEqual(json.loads(res[0]), {'.a': [1], '.b': 'x'})
+    r.assertEqual(json.loads(res[1]), {'.a': [2], '.b': None})
+    r.assertEqual(res[2], None)
+
+    r.expect('JSON.MGET', 'doc:1', '.', 'INDENT').raiseError()
+    r.expect('JSON.MGET', 'doc:1', '.', 'FORMAT', 'XML').raiseError()
+
+    # a key named like an option is only read as a key in the first two arguments
+    r.assertOk(r.execute_command('JSON.SET', 'indent', '.', '{"a":[3]}'))
+    r.assertEqual(r.execute_command('JSON.MGET', 'indent', 'doc:1', '.a'), ['[3]', '[1]'])
+    r.assertEqual(r.execute_command('JSON.MGET', 'doc:1', 'indent', '.a'), ['[1]', '[3]'])
+    r.assertEqual(r.execute_command('JSON.MGET', 'doc:1', 'b', 'indent', '.a'), ['"x"'])
+
+def testMgetMissingKeyOrPath(env):
+    """Test that JSON.MGET tells a missing key apart from a path that matches nothing or is invalid"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'doc:1', '.', '{"a":1}'))
+    r.assertOk(r.execute_command('JSON.SET', 'doc:2', '.', '{"b":2}'))
+
+    # a matching path, a path that matches nothing in an existing key and a missing key
+    r.assertEqual(r.execute_command('JSON.MGET', 'doc:1', 'doc:2', 'missing', '.a'), ['1', None, None])
+    r.assertEqual(r.execute_command('JSON.MGET', 'doc:1', 'doc:2', 'missing', '$.a'), ['[1]', '[]', None])
+    r.assertEqual(r.execute_command('JSON.MGET', 'doc:1', 'doc:2', 'missing', '.a', 'DEFAULT', '"none"'),
+                  ['1', '"none"', None])
+    r.expect('JSON.MGET', 'doc:1', 'doc:2', '.a', 'REQUIRE').error().contains("does not exist")
+    r.assertEqual(r.execute_command('JSON.MGET', 'doc:1', 'missing', '.a', 'REQUIRE'), ['1', None])
+
+    # an invalid path is an error, not a missing value
+    r.expect('JSON.MGET', 'doc:1', 'doc:2', '$.a[').raiseError()
+    r.expect('JSON.MGET', 'missing', '$.a[').raiseError()
+
 def testToggleCommand(env):
     """Test REJSON.TOGGLE command"""
     r = env
@@ -337,6 +808,12 @@ def testToggleCommand(env):
     r.assertEqual(r.execute_command('JSON.TOGGLE','test','.foo'), 'false')
     r.assertEqual(r.execute_command('JSON.TOGGLE','test','.foo'), 'true')
 
+    # multiple matches toggle every boolean and skip the other values
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"arr":[true,false,1,true]}'))
+    r.assertEqual(r.execute_command('JSON.TOGGLE','test','$.arr[*]'), ['false', 'true', None, 'false'])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET','test','.arr')), [False, True, 1, False])
+    r.expect('JSON.TOGGLE','test','$.arr[2]').raiseError()
+
     # Test Toggeling Empty Path
     r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"foo":"bar"}'))
     r.expect('JSON.TOGGLE', 'test', '.bar').raiseError()
@@ -345,6 +822,25 @@ def testToggleCommand(env):
     r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"foo":"bar"}'))
     r.expect('JSON.TOGGLE','test','.foo').raiseError()
 
+def testToggleCommandResp3(env):
+    """Test that JSON.TOGGLE replies RESP3 booleans to RESP3 clients"""
+    r = env
+    # RedisModule_ReplyWithBool is only available since Redis 7.0
+    version = r.execute_command('INFO', 'server')['redis_version']
+    if tuple(int(n) for n in version.split('.')[:2]) < (7, 0):
+        env.skip()
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"foo":true,"arr":[true,1]}'))
+    sock, reply = raw_connection(r)
+    r.assertTrue(reply('HELLO', '3').startswith(b'%'))
+    r.assertEqual(reply('JSON.TOGGLE', 'test', '.foo'), b'#f\r\n')
+    r.assertEqual(reply('JSON.TOGGLE', 'test', '$.arr[*]'), b'*2\r\n#f\r\n_\r\n')
+    sock.close()
+
+    # RESP2 replies are unchanged
+    r.assertEqual(r.execute_command('JSON.TOGGLE', 'test', '.foo'), 'true')
+    r.assertEqual(r.execute_command('JSON.TOGGLE', 'test', '$.arr[*]'), ['true', None])
+
 def testDelCommand(env):
     """Test REJSON.DEL command"""
     r = env
@@ -388,6 +884,21 @@ def testDelCommand(env):
     r.assertEqual(r.execute_command('JSON.DEL', 'test', '.'), 1)
     r.assertIsNone(r.execute_command('JSON.GET', 'test'))
 
+def testDelMultiplePaths(env):
+    """Test JSON.DEL with multiple paths"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":{"b":1},"c":2,"arr":[0,1,2,3,4]}'))
+    # overlapping and repeated paths are deleted once
+    r.assertEqual(r.execute_command('JSON.DEL', 'test', '$.a.b', '$.a', '$.a', '$.nosuchpath'), 1)
+    # array indices refer to the array before any deletion
+    r.assertEqual(r.execute_command('JSON.DEL', 'test', '$.arr[1]', '$.arr[3]', '$.arr[0]'), 3)
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$')), [{'c': 2, 'arr': [2, 4]}])
+    r.assertEqual(r.execute_command('JSON.DEL', 'test', '$.a', '$.x'), 0)
+    # the root among the paths deletes the key
+    r.assertEqual(r.execute_command('JSON.DEL', 'test', '$.c', '$'), 1)
+    assertNotExists(r, 'test')
+
 def testObjectCRUD(env):
     r = env
 
@@ -484,14 +995,47 @@ def testClear(env):
     # Clear root
     r.expect('JSON.SET', 'test', '.', r'{"n":42,"s":"42","arr":[{"n":44},"s",{"n":{"a":1,"b":2}},{"n2":{"x":3.02,"n":["to","be","cleared",4],"y":4.91}}]}') \
         .ok()
-    # TODO: switch order of the following paths and expect .equals(2) when supporting multi-paths in JSON.CLEAR
-    r.expect('JSON.CLEAR', 'test', '$', '$.arr[2].n').equal(1)
+    # Overlapping paths are counted once
+    r.expect('JSON.CLEAR', 'test', '$.arr[2].n', '$', '$.arr[2].n').equal(1)
     r.expect('JSON.GET', 'test', '$').equal('[{}]')
+    # Descendants of a cleared array are not visited once it is empty
+    r.expect('JSON.SET', 'test', '$', r'{"arr":[[1],2,{"a":3}]}').ok()
+    r.expect('JSON.CLEAR', 'test', '$.arr', '$.arr[0]').equal(1)
+    r.expect('JSON.GET', 'test', '$').equal(r'[{"arr":[]}]')
+    r.expect('JSON.SET', 'test', '$', r'{"a":[[1,[2]],3],"b":{"c":[4]},"n":5}').ok()
+    r.expect('JSON.CLEAR', 'test', '$..*').equal(3)
+    r.expect('JSON.GET', 'test', '$').equal(r'[{"a":[],"b":{},"n":0}]')
+    r.expect('JSON.CLEAR', 'test', '$..*').equal(0)
+
+    # Clear disjoint subtrees
+    r.expect('JSON.SET', 'test', '$', r'{"a":{"x":1},"b":[1,2],"c":[3]}').ok()
+    r.expect('JSON.CLEAR', 'test', '$.a', '$.b').equal(2)
+    r.expect('JSON.GET', 'test', '$').equal(r'[{"a":{},"b":[],"c":[3]}]')
 
     r.expect('JSON.SET', 'test', '$', obj_content_legacy).ok()
     r.expect('JSON.CLEAR', 'test').equal(1)
     r.expect('JSON.GET', 'test', '$').equal('[{}]')
 
+    # Each type of value is cleared to its empty form, strings only with STRINGS
+    r.expect('JSON.SET', 'test', '$', r'{"a":[1],"o":{"x":1},"i":42,"f":3.5,"s":"str","t":true,"z":null}').ok()
+    r.expect('JSON.CLEAR', 'test', '$.*').equal(4)
+    r.expect('JSON.GET', 'test', '$').equal(r'[{"a":[],"o":{},"i":0,"f":0,"s":"str","t":true,"z":null}]')
+    r.expect('JSON.CLEAR', 'test', '$.s', '$.t', '$.z').equal(0)
+    r.expect('JSON.CLEAR', 'test', '$.s', 'strings').equal(1)
+    r.expect('JSON.GET', 'test', '$.s').equal('[""]')
+    r.expect('JSON.SET', 'test', '$.s', '"str"').ok()
+    r.expect('JSON.CLEAR', 'test', 'STRINGS').equal(1)
+    r.expect('JSON.GET', 'test', '$').equal('[{}]')
+
+    # Only the values that change are counted, clearing twice counts nothing
+    r.expect('JSON.SET', 'test', '$', r'{"a":[1],"b":[],"o":{},"i":0,"f":0.0,"n":-1.5,"s":"","t":"x"}').ok()
+    r.expect('JSON.CLEAR', 'test', '$.*', 'STRINGS').equal(3)
+    r.expect('JSON.GET', 'test', '$').equal(r'[{"a":[],"b":[],"o":{},"i":0,"f":0.0,"n":0,"s":"","t":""}]')
+    r.expect('JSON.CLEAR', 'test', '$.*', 'STRINGS').equal(0)
+    r.expect('JSON.CLEAR', 'test', '.a').equal(0)
+    r.expect('JSON.CLEAR', 'test').equal(1)
+    r.expect('JSON.CLEAR', 'test').equal(0)
+
 def testArrayCRUD(env):
     """Test JSON Array CRUDness"""
 
@@ -557,6 +1101,55 @@ def testArrayCRUD(env):
     r.assertEqual(1, r.execute_command('JSON.ARRLEN', 'test', '.'))
     r.assertEqual('true', r.execute_command('JSON.GET', 'test', '[0]'))
 
+def testArrAppendUniqueCommand(env):
+    """Test JSON.ARRAPPENDUNIQUE command"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"tags":["a","b"],"objs":[{"x":1,"y":2}],"n":[1],"s":"x"}'))
+    r.assertEqual(r.execute_command('JSON.ARRAPPENDUNIQUE', 'test', '.tags', '"b"', '"c"', '"c"', '"a"', '"d"'), 4)
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '.tags')), ['a', 'b', 'c', 'd'])
+    r.assertEqual(r.execute_command('JSON.ARRAPPENDUNIQUE', 'test', '.tags', '"a"'), 4)
+    r.assertEqual(r.execute_command('JSON.ARRAPPENDUNIQUE', 'test', '.objs', '{"y":2,"x":1}', '{"x":1}'), 2)
+    r.assertEqual(r.execute_command('JSON.ARRAPPENDUNIQUE', 'test', '.n', '1.0', '1'), 2)
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '.n')), [1, 1.0])
+
+    r.assertEqual(r.execute_command('JSON.ARRAPPENDUNIQUE', 'test', '$.*', '"a"'), [4, 3, 3, None])
+    r.expect('JSON.ARRAPPENDUNIQUE', 'test', '.s', '"a"').error().contains('expected array but found string')
+    r.expect('JSON.ARRAPPENDUNIQUE', 'test', '.nope', '"a"').error().contains('does not exist')
+    r.expect('JSON.ARRAPPENDUNIQUE', 'test', '.tags').raiseError()
+    r.expect('JSON.ARRAPPENDUNIQUE', 'test', '.tags', '{').raiseError()
+
+def testArrAppendCreate(env):
+    """Test JSON.ARRAPPEND with CREATE"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":{},"b":[1],"c":"str"}'))
+    r.expect('JSON.ARRAPPEND', 'test', '.list', 1).error().contains("does not exist")
+    r.assertEqual(2, r.execute_command('JSON.ARRAPPEND', 'test', '.list', 'CREATE', 1, 2))
+    r.assertEqual(1, r.execute_command('JSON.ARRAPPEND', 'test', '$.a.list', 'CREATE', '"x"'))
+    r.assertEqual(2, r.execute_command('JSON.ARRAPPEND', 'test', '.b', 'create', 2))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test')),
+                  {'a': {'list': ['x']}, 'b': [1, 2], 'c': 'str', 'list': [1, 2]})
+
+    # an existing value that is not an array is left untouched
+    r.expect('JSON.ARRAPPEND', 'test', '.c', 'CREATE', 1).error().contains("does not exist")
+    r.assertEqual('"str"', r.execute_command('JSON.GET', 'test', '.c'))
+    # only the last member is created
+    r.expect('JSON.ARRAPPEND', 'test', '.x.y', 'CREATE', 1).error().contains("does not exist")
+    r.expect('JSON.ARRAPPEND', 'test', '.d', 'CREATE', 'nope').error().contains("argument 4 for path '.d'")
+    r.expect('JSON.ARRAPPEND', 'test', '.d', 'CREATE').raiseError()
+
+def testArrItemParseError(env):
+    """Test that a malformed item is reported by index and nothing is added"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":[1],"b":{"c":[2]}}'))
+    r.expect('JSON.ARRAPPEND', 'test', '$.a', '3', '{"x":', '4').error().contains("argument 4 for path '$.a' (item 1)")
+    r.expect('JSON.ARRAPPEND', 'test', '$.a', 'CREATE', '3', '4', 'nope').error().contains("argument 6 for path '$.a' (item 2)")
+    r.expect('JSON.ARRINSERT', 'test', '$..c', 0, '[', '3').error().contains("argument 4 for path '$..c' (item 0)")
+    r.expect('JSON.ARRINSERT', 'test', '.a', 1, '3', '4', '5', '6]').error().contains("(item 3)")
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"a":[1],"b":{"c":[2]}}')
+
 def testArrIndexCommand(env):
     """Test JSON.ARRINDEX command"""
     r = env
@@ -579,6 +1172,40 @@ def testArrIndexCommand(env):
     r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '.arr', 2, 3), 5)
     r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '.arr', '[4]'), 4)
 
+def testArrIndexDeepEquality(env):
+    """Test JSON.ARRINDEX with arrays and objects"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.',
+                                 '{"items": [{"a":1}, {"a":1,"b":[2,{"c":3}]}, [1,[2]], {"b":[2,{"c":3}],"a":1}, 1.0, {}]}'))
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.items', '{"a":1}'), 0)
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.items', '{"b":[2,{"c":3}],"a":1}'), 1)
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.items', '{"a":1,"b":[2,{"c":3}]}', 2), 3)
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.items', '{"a":1,"b":[{"c":3},2]}'), -1)
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.items', '{"a":1,"b":[2,{"c":3}],"d":4}'), -1)
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.items', '[1,[2]]'), 2)
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.items', '[1,2]'), -1)
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.items', '{}'), 5)
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.items', '1.0'), 4)
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.items', '1'), -1)
+
+def testArrIndexAllCommand(env):
+    """Test JSON.ARRINDEXALL command"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"arr": [0, 1, 2, 3, 2, 1, 0], "empty": [], "s": "x"}'))
+    r.assertEqual(r.execute_command('JSON.ARRINDEXALL', 'test', '.arr', 0), [0, 6])
+    r.assertEqual(r.execute_command('JSON.ARRINDEXALL', 'test', '.arr', 2), [2, 4])
+    r.assertEqual(r.execute_command('JSON.ARRINDEXALL', 'test', '.arr', 4), [])
+    r.assertEqual(r.execute_command('JSON.ARRINDEXALL', 'test', '.arr', 1, 2), [5])
+    r.assertEqual(r.execute_command('JSON.ARRINDEXALL', 'test', '$.arr', 1, -3), [5])
+    r.assertEqual(r.execute_command('JSON.ARRINDEXALL', 'test', '.arr', 0, 0, -1), [0])
+    r.assertEqual(r.execute_command('JSON.ARRINDEXALL', 'test', '.arr', 0, 5, 2), [])
+    r.assertEqual(r.execute_command('JSON.ARRINDEXALL', 'test', '.empty', 0), [])
+    r.expect('JSON.ARRINDEXALL', 'test', '.s', '"x"').error().contains("wrong type")
+    r.expect('JSON.ARRINDEXALL', 'test', '.arr', 0, 0, 0, 0).raiseError()
+    r.assertEqual(r.execute_command('JSON.ARRINDEXALL', 'missing', '.arr', 0), None)
+
 def testArrInsertCommand(env):
     """Test JSON.ARRINSERT command"""
     r = env
@@ -600,6 +1227,23 @@ def testArrInsertCommand(env):
     r.expect('JSON.ARRINSERT', 'test', '.arr', -10, '10').raiseError()
     r.expect('JSON.ARRINSERT', 'test', '.arr', 10, '10').raiseError()
 
+def testArrInsertIndexBounds(env):
+    """Test that JSON.ARRINSERT inserts at the same slot as Python's list.insert"""
+    r = env
+
+    for index in [0, 1, 3, 4, -1, -3, -4]:
+        expected = [1, 2, 3, 4]
+        expected.insert(index, 0)
+        r.assertOk(r.execute_command('JSON.SET', 'test', '$', '[1,2,3,4]'))
+        r.assertEqual(r.execute_command('JSON.ARRINSERT', 'test', '$', index, '0'), 5)
+        r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$')), [expected], message=index)
+
+    # out of -len..=len is an error, and the array is left as is
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '[1,2,3,4]'))
+    r.expect('JSON.ARRINSERT', 'test', '$', -5, '0').error().contains("index out of bounds")
+    r.expect('JSON.ARRINSERT', 'test', '.', 5, '0').error().contains("index out of bounds")
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.'), '[1,2,3,4]')
+
 def testArrIndexMixCommand(env):
     """Test JSON.ARRINDEX command with mixed values"""
     r = env
@@ -655,8 +1299,8 @@ def testArrPopCommand(env):
     r.assertEqual('5', r.execute_command('JSON.ARRPOP', 'test', '.', -2))
     r.assertEqual('1', r.execute_command('JSON.ARRPOP', 'test', '.', 0))
     r.assertEqual('4', r.execute_command('JSON.ARRPOP', 'test', '.', 2))
-    r.assertEqual('6', r.execute_command('JSON.ARRPOP', 'test', '.', 99))
-    r.assertEqual('2', r.execute_command('JSON.ARRPOP', 'test', '.', -99))
+    r.assertEqual('6', r.execute_command('JSON.ARRPOP', 'test'))
+    r.assertEqual('2', r.execute_command('JSON.ARRPOP', 'test', '.', 0))
     r.assertEqual('3', r.execute_command('JSON.ARRPOP', 'test'))
     r.assertIsNone(r.execute_command('JSON.ARRPOP', 'test'))
     r.assertIsNone(r.execute_command('JSON.ARRPOP', 'test', '.'))
@@ -668,12 +1312,68 @@ def testArrPopErrors(env):
     r.assertOk(r.execute_command('JSON.SET', 'test','.', '1'))
     r.expect('JSON.ARRPOP', 'test').error().contains("not an array")
 
+    # explicit indices are not clamped into the array's range
+    r.assertOk(r.execute_command('JSON.SET', 'test','.', '[1,2,3]'))
+    r.expect('JSON.ARRPOP', 'test', '.', 3).error().contains("index out of range")
+    r.expect('JSON.ARRPOP', 'test', '.', 99).error().contains("index out of range")
+    r.expect('JSON.ARRPOP', 'test', '.', -4).error().contains("index out of range")
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.'), '[1,2,3]')
+    r.assertEqual('1', r.execute_command('JSON.ARRPOP', 'test', '.', -3))
+
+    # a failure leaves nested arrays as they were
+    r.assertOk(r.execute_command('JSON.SET', 'test','.', '{"a":[1,[2,3]],"b":"str"}'))
+    r.expect('JSON.ARRPOP', 'test', '.a', 2).error().contains("index out of range")
+    r.expect('JSON.ARRPOP', 'test', '.a[1]', -3).error().contains("index out of range")
+    r.expect('JSON.ARRPOP', 'test', '.b').error().contains("not an array")
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"a":[1,[2,3]],"b":"str"}')
+    r.assertEqual('3', r.execute_command('JSON.ARRPOP', 'test', '.a[1]'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"a":[1,[2]],"b":"str"}')
+
+    # an index out of range in one of the matches pops nothing from the others
+    r.assertOk(r.execute_command('JSON.SET', 'test','.', '{"x":{"a":[1,2,3,4,5]},"y":{"a":[6,7]}}'))
+    r.expect('JSON.ARRPOP', 'test', '$..a', 3).error().contains("index out of range")
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"x":{"a":[1,2,3,4,5]},"y":{"a":[6,7]}}')
+    r.assertEqual('7', r.execute_command('JSON.ARRPOP', 'test', '$..a', 1))
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"x":{"a":[1,3,4,5]},"y":{"a":[6]}}')
+
+    # the last match being empty does not undo the pops from the others
+    r.assertOk(r.execute_command('JSON.SET', 'test','.', '{"x":{"a":[1]},"y":{"a":[]}}'))
+    r.assertIsNone(r.execute_command('JSON.ARRPOP', 'test', '$..a'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"x":{"a":[]},"y":{"a":[]}}')
+
+    # an explicit index must be an integer
+    r.assertOk(r.execute_command('JSON.SET', 'test','.', '[1,2,3]'))
+    r.expect('JSON.ARRPOP', 'test', '.', 'abc').raiseError()
+    r.expect('JSON.ARRPOP', 'test', '.', '1.5').raiseError()
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '[1,2,3]')
+
 def testArrTrimErrors(env):
     r = env
 
     r.assertOk(r.execute_command('JSON.SET', 'test','.', '1'))
     r.expect('JSON.ARRTRIM', 'test', '.', '0', '1').error().contains("not an array")
 
+def testArrSliceCommand(env):
+    """Test JSON.ARRSLICE command"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"arr":[0,1,2,3,4,5]}'))
+    r.assertEqual(r.execute_command('JSON.ARRSLICE', 'test', '.arr', '1', '3'), '[1,2]')
+    r.assertEqual(r.execute_command('JSON.ARRSLICE', 'test', '.arr', '-2', '6'), '[4,5]')
+    r.assertEqual(r.execute_command('JSON.ARRSLICE', 'test', '.arr', '0', '-1', '2'), '[0,2,4]')
+    r.assertEqual(r.execute_command('JSON.ARRSLICE', 'test', '.arr', '0', '6', '0'), '[0,1,2,3,4,5]')
+    # Out of range bounds are clamped
+    r.assertEqual(r.execute_command('JSON.ARRSLICE', 'test', '$.arr', '-100', '100'), '[0,1,2,3,4,5]')
+    r.assertEqual(r.execute_command('JSON.ARRSLICE', 'test', '.arr', '4', '2'), '[]')
+    r.assertEqual(r.execute_command('JSON.ARRSLICE', 'test', '.arr', '10', '20'), '[]')
+    # The array is left untouched
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.arr'), '[0,1,2,3,4,5]')
+
+    r.expect('JSON.ARRSLICE', 'test', '.arr', '0', '1', '-1').raiseError()
+    r.expect('JSON.ARRSLICE', 'test', '.arr', '0').raiseError()
+    r.expect('JSON.ARRSLICE', 'test', '.', '0', '1').error().contains("wrong type")
+    r.assertEqual(r.execute_command('JSON.ARRSLICE', 'missing', '.', '0', '1'), None)
+
 def testTypeCommand(env):
     """Test JSON.TYPE command"""
     r = env
@@ -683,6 +1383,58 @@ def testTypeCommand(env):
         reply = r.execute_command('JSON.TYPE', 'test', '.')
         r.assertEqual(reply, k)
 
+def testTypeIntegerOrNumber(env):
+    """Test that JSON.TYPE tells integers from floating point numbers as they are stored"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"int":1,"neg":-1,"float":1.0,"exp":1e10,"frac":0.5}'))
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '.int'), 'integer')
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '.neg'), 'integer')
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '.float'), 'number')
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '.exp'), 'number')
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '$.*'), ['integer', 'integer', 'number', 'number', 'number'])
+    # the representation is kept when the value is read back
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.float'), '1.0')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.int'), '1')
+
+    # arithmetic keeps integers as integers until a float is involved
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '.int', 2), '3')
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '.int'), 'integer')
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '.float', 1), '2.0')
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '.float'), 'number')
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '.int', 0.5), '3.5')
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '.int'), 'number')
+
+def testTypeCommandMultiPath(env):
+    """Test JSON.TYPE with paths that match several values"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":1,"b":"s","c":[true,null,1.5],"d":{"a":{}}}'))
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '$.*'), ['integer', 'string', 'array', 'object'])
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '$.c[*]'), ['boolean', 'null', 'number'])
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '$..a'), ['integer', 'object'])
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '$.b'), ['string'])
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '$.nope'), [])
+    r.assertEqual(r.execute_command('JSON.TYPE', 'missing', '$.*'), None)
+
+    # legacy paths still reply a single type
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '.b'), 'string')
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '.nope'), None)
+
+def testTypeNumeric(env):
+    """Test JSON.TYPE NUMERIC"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"n":null,"b":true,"i":1,"f":1.5,"s":"s","o":{},"a":[]}'))
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '$.*', 'NUMERIC'), [0, 1, 2, 3, 4, 5, 6])
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '.f', 'numeric'), 3)
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', 'NUMERIC'), 5)
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test'), 'object')
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '.nope', 'NUMERIC'), None)
+    r.assertEqual(r.execute_command('JSON.TYPE', 'test', '$.nope', 'NUMERIC'), [])
+    r.expect('JSON.TYPE', 'test', '.a', 'NUMERIC', 'NUMERIC').error().contains('syntax error')
+    r.expect('JSON.TYPE', 'test', '.a', '.b').error().contains('syntax error')
+
 def testLenCommands(env):
     """Test the JSON.ARRLEN, JSON.OBJLEN and JSON.STRLEN commands"""
     r = env
@@ -711,6 +1463,82 @@ def testLenCommands(env):
     # test an infinite index
     r.expect('JSON.LEN', 'test', '.arr[-inf]').raiseError()
 
+def testLenCommandsMultiPath(env):
+    """Test the length commands with paths that match several values"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":{"items":[1,2],"s":"ab"},"b":{"items":"xyz","s":{"k":1}},"c":{"items":[]}}'))
+    r.assertEqual(r.execute_command('JSON.ARRLEN', 'test', '$..items'), [2, None, 0])
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '$..items'), [None, 3, None])
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '$.*.s'), [2, None])
+    r.assertEqual(r.execute_command('JSON.OBJLEN', 'test', '$.*'), [2, 2, 1])
+    r.assertEqual(r.execute_command('JSON.OBJLEN', 'test', '$.*.s'), [None, 1])
+
+    # a single match is still replied as an integer
+    r.assertEqual(r.execute_command('JSON.ARRLEN', 'test', '$.a.items'), 2)
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '$.a.s'), 2)
+    r.expect('JSON.ARRLEN', 'test', '$.b.items').error().contains("wrong type")
+    r.expect('JSON.ARRLEN', 'test', '$..nope').error().contains("does not exist")
+
+def resp_reply_end(data, pos=0):
+    """The position after the RESP2 or RESP3 reply starting at `pos` in `data`, or None when
+    `data` does not hold all of it yet"""
+    line_end = data.find(b'\r\n', pos)
+    if line_end < 0:
+        return None
+    kind, line, pos = data[pos:pos + 1], data[pos + 1:line_end], line_end + 2
+    if kind in (b'$', b'!', b'='):
+        n = int(line)
+        end = pos + n + 2
+        return pos if n < 0 else end if len(data) >= end else None
+    if kind in (b'*', b'~', b'>'):
+        count = max(int(line), 0)
+    elif kind in (b'%', b'|'):
+        count = 2 * int(line)
+    else:
+        return pos
+    for _ in range(count):
+        pos = resp_reply_end(data, pos)
+        if pos is None:
+            return None
+    # attributes are followed by the reply they describe
+    return resp_reply_end(data, pos) if kind == b'|' else pos
+
+def raw_connection(env):
+    """A connection to the server that is not bound to a client, to read the bytes of replies
+    that the client can not decode or would not tell apart"""
+    kwargs = env.getConnection().connection_pool.connection_kwargs
+    sock = socket.create_connection((kwargs.get('host', 'localhost'), kwargs['port']))
+    sock.settimeout(10)
+    def reply(*args):
+        args = [a if isinstance(a, bytes) else str(a).encode() for a in args]
+        sock.sendall(b'*%d\r\n' % len(args) + b''.join(b'$%d\r\n%s\r\n' % (len(a), a) for a in args))
+        data = b''
+        while resp_reply_end(data) is None:
+            chunk = sock.recv(65536)
+            if not chunk:
+                raise ConnectionError('connection closed before the reply was complete')
+            data += chunk
+        return data
+    return sock, reply
+
+def testLenCommandsResp3(env):
+    """Test that the length commands reply native RESP3 types"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":{"items":[1,2]},"b":{"items":"xyz"}}'))
+    # the client does not speak RESP3, so the replies are read from a raw connection
+    sock, reply = raw_connection(r)
+    r.assertTrue(reply('HELLO', '3').startswith(b'%'))
+    r.assertEqual(reply('JSON.ARRLEN', 'test', '$.a.items'), b':2\r\n')
+    r.assertEqual(reply('JSON.ARRLEN', 'test', '$..items'), b'*2\r\n:2\r\n_\r\n')
+    r.assertEqual(reply('JSON.OBJLEN', 'test', '$.*'), b'*2\r\n:1\r\n:1\r\n')
+    r.assertEqual(reply('JSON.ARRLEN', 'missing', '.'), b'_\r\n')
+    sock.close()
+
+    # RESP2 replies are unchanged
+    r.assertEqual(r.execute_command('JSON.ARRLEN', 'test', '$..items'), [2, None])
+
 def testObjKeysCommand(env):
     """Test JSON.OBJKEYS command"""
     r = env
@@ -724,6 +1552,89 @@ def testObjKeysCommand(env):
     # test a wrong type
     r.expect('JSON.OBJKEYS', 'test', '.null').raiseError()
 
+def testObjKeysMultiPath(env):
+    """Test JSON.OBJKEYS with a path matching several values"""
+    r = env
+
+    r.expect('JSON.SET', 'test', '$', json.dumps({
+        'home': {'address': {'city': 'NYC', 'zip': '10001'}},
+        'work': {'address': {'street': 'Main', 'city': 'SF'}},
+        'other': {'address': 'unknown'},
+    })).ok()
+    r.assertEqual(r.execute_command('JSON.OBJKEYS', 'test', '$..address'),
+                  [['city', 'zip'], ['street', 'city'], None])
+    r.assertEqual(r.execute_command('JSON.OBJKEYS', 'test', '$.*'),
+                  [['address'], ['address'], ['address']])
+
+    # a single match replies its keys, as a legacy path does
+    r.assertEqual(r.execute_command('JSON.OBJKEYS', 'test', '$.home.address'), ['city', 'zip'])
+    r.assertEqual(r.execute_command('JSON.OBJKEYS', 'test', '.home.address'), ['city', 'zip'])
+    r.expect('JSON.OBJKEYS', 'test', '$.other.address').raiseError()
+    r.expect('JSON.OBJKEYS', 'test', '$..nope').error().contains("does not exist")
+    r.expect('JSON.OBJKEYS', 'test', '$', 'extra').raiseError()
+
+def testObjValuesCommand(env):
+    """Test JSON.OBJVALUES command"""
+    r = env
+
+    r.expect('JSON.SET', 'test', '.', json.dumps(docs['types'])).ok()
+    keys = r.execute_command('JSON.OBJKEYS', 'test', '.')
+    values = r.execute_command('JSON.OBJVALUES', 'test')
+    r.assertEqual([json.loads(v) for v in values], [docs['types'][k] for k in keys])
+    r.assertEqual(r.execute_command('JSON.OBJVALUES', 'test', '$.object'), [])
+
+    # test a wrong type and missing key
+    r.expect('JSON.OBJVALUES', 'test', '.null').raiseError()
+    r.expect('JSON.OBJVALUES', 'test', '.array').raiseError()
+    r.expect('JSON.OBJVALUES', 'test', '.', 'extra').raiseError()
+    r.assertEqual(r.execute_command('JSON.OBJVALUES', 'missing'), None)
+
+def testObjEntriesCommand(env):
+    """Test JSON.OBJENTRIES command"""
+    r = env
+
+    r.expect('JSON.SET', 'test', '.', json.dumps(docs['types'])).ok()
+    keys = r.execute_command('JSON.OBJKEYS', 'test', '.')
+    values = r.execute_command('JSON.OBJVALUES', 'test', '.')
+    entries = r.execute_command('JSON.OBJENTRIES', 'test')
+    r.assertEqual(entries, [[k, v] for k, v in zip(keys, values)])
+    r.assertEqual(r.execute_command('JSON.OBJENTRIES', 'test', '$.object'), [])
+
+    # test a wrong type and missing key
+    r.expect('JSON.OBJENTRIES', 'test', '.null').raiseError()
+    r.expect('JSON.OBJENTRIES', 'test', '.array').raiseError()
+    r.assertEqual(r.execute_command('JSON.OBJENTRIES', 'missing'), None)
+
+def testEntriesCommand(env):
+    """Test JSON.ENTRIES command"""
+    r = env
+
+    obj = {'k{}'.format(i): i for i in range(25)}
+    r.expect('JSON.SET', 'test', '.', json.dumps({'obj': obj, 'empty': {}, 'arr': []})).ok()
+    entries = r.execute_command('JSON.OBJENTRIES', 'test', '.obj')
+
+    # a full scan visits each member once, in the same order as JSON.OBJENTRIES
+    scanned = []
+    cursor, batch = r.execute_command('JSON.ENTRIES', 'test', '.obj', 0)
+    r.assertEqual(len(batch), 10)
+    scanned += batch
+    while cursor != '0':
+        cursor, batch = r.execute_command('JSON.ENTRIES', 'test', '.obj', cursor, 'COUNT', 7)
+        scanned += batch
+    r.assertEqual(scanned, entries)
+
+    r.assertEqual(r.execute_command('JSON.ENTRIES', 'test', '$.obj', 0, 'count', 100), ['0', entries])
+    r.assertEqual(r.execute_command('JSON.ENTRIES', 'test', '.obj', 24, 'COUNT', 1), ['0', [['k24', '24']]])
+    r.assertEqual(r.execute_command('JSON.ENTRIES', 'test', '.obj', 100), ['0', []])
+    r.assertEqual(r.execute_command('JSON.ENTRIES', 'test', '.empty', 0), ['0', []])
+    r.assertEqual(r.execute_command('JSON.ENTRIES', 'missing', '.', 0), None)
+
+    r.expect('JSON.ENTRIES', 'test', '.arr', 0).raiseError()
+    r.expect('JSON.ENTRIES', 'test', '.obj').raiseError()
+    r.expect('JSON.ENTRIES', 'test', '.obj', -1).raiseError()
+    r.expect('JSON.ENTRIES', 'test', '.obj', 0, 'COUNT', 0).error().contains('positive')
+    r.expect('JSON.ENTRIES', 'test', '.obj', 0, 'MATCH', '*').error().contains('syntax error')
+
 def testNumIncrCommand(env):
     """Test JSON.NUMINCRBY command"""
     r = env
@@ -755,6 +1666,98 @@ def testNumIncrCommand(env):
     r.assertEqual(1, res['foo'])
     r.assertEqual(84, res['bar'])
 
+def testNumFloatText(env):
+    """Test that float results are replied and stored as the same shortest text"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":0.2,"b":1e20,"c":0,"d":2}'))
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '.a', 0.1), '0.30000000000000004')
+    r.assertEqual(r.execute_command('JSON.NUMMULTBY', 'test', '.b', 10), '1e21')
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '.c', '1.5e-7'), '1.5e-7')
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '.d', 0.5), '2.5')
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"a":0.30000000000000004,"b":1e21,"c":1.5e-7,"d":2.5}')
+    r.assertEqual(r.execute_command('JSON.RESP', 'test', '.a'), '0.30000000000000004')
+    r.assertEqual(r.execute_command('JSON.MULTIOP', 'test', '[{"op":"numincrby","path":"$.d","args":[0.25]}]'), ['2.75'])
+
+def testNumDivModCommands(env):
+    """Test JSON.NUMDIVBY and JSON.NUMMODBY commands"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{ "foo": 7, "bar": 7.5, "baz": "qux" }'))
+    r.assertEqual('3', r.execute_command('JSON.NUMDIVBY', 'test', '.foo', 2))
+    r.assertEqual('1.5', r.execute_command('JSON.NUMDIVBY', 'test', '.foo', 2.0))
+    r.assertEqual('2.5', r.execute_command('JSON.NUMDIVBY', 'test', '.bar', 3))
+    r.assertEqual('2.5', r.execute_command('JSON.NUMMODBY', 'test', '.bar', 5))
+    r.assertEqual('{"foo":1.5,"bar":2.5,"baz":"qux"}', r.execute_command('JSON.GET', 'test', '.'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.foo', '-7'))
+    r.assertEqual('-1', r.execute_command('JSON.NUMMODBY', 'test', '.foo', 3))
+
+    # division by zero leaves the value untouched
+    r.expect('JSON.NUMDIVBY', 'test', '.foo', 0).error().contains('division by zero')
+    r.expect('JSON.NUMDIVBY', 'test', '.bar', '0.0').error().contains('division by zero')
+    r.expect('JSON.NUMMODBY', 'test', '.foo', 0).error().contains('division by zero')
+    r.assertEqual('-1', r.execute_command('JSON.GET', 'test', '.foo'))
+
+    # test a wrong type
+    r.expect('JSON.NUMDIVBY', 'test', '.baz', 1).raiseError()
+    r.expect('JSON.NUMMODBY', 'test', '.baz', 1).raiseError()
+
+def testNumOpNotFiniteResult(env):
+    """Test numeric operations with no JSON representable result"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{ "big": 1e308, "neg": -8 }'))
+    r.expect('JSON.NUMMULTBY', 'test', '.big', 10).error().contains('not a valid JSON number')
+    r.expect('JSON.NUMINCRBY', 'test', '.big', '1.7976931348623157e308').error().contains('not a valid JSON number')
+    r.expect('JSON.NUMPOWBY', 'test', '.neg', 0.5).error().contains('not a valid JSON number')
+    r.assertEqual('{"big":1e308,"neg":-8}', r.execute_command('JSON.GET', 'test', '.'))
+
+    # the root is left untouched as well
+    r.assertOk(r.execute_command('JSON.SET', 'num', '.', '-8'))
+    r.expect('JSON.NUMPOWBY', 'num', '.', 0.5).error().contains('not a valid JSON number')
+    r.assertEqual('-8', r.execute_command('JSON.GET', 'num', '.'))
+
+    # the matches changed before the one that fails are kept, and notified and replicated
+    r.assertOk(r.execute_command('JSON.SET', 'multi', '$', '{"a":{"n":2},"b":{"n":3},"c":{"n":1e308}}'))
+    r.assertOk(r.execute_command('CONFIG', 'SET', 'notify-keyspace-events', 'Kd'))
+    p = env.getConnection().pubsub()
+    p.subscribe('__keyspace@0__:multi')
+    r.expect('JSON.NUMMULTBY', 'multi', '$..n', 10).error().contains('not a valid JSON number')
+    message = p.get_message(timeout=10)
+    while message is not None and message['type'] != 'message':
+        message = p.get_message(timeout=10)
+    r.assertIsNotNone(message)
+    r.assertEqual(message['data'], 'json.nummultby')
+    p.close()
+    r.assertOk(r.execute_command('CONFIG', 'SET', 'notify-keyspace-events', ''))
+    r.assertEqual('{"a":{"n":20},"b":{"n":30},"c":{"n":1e308}}', r.execute_command('JSON.GET', 'multi'))
+
+def testNumMinMaxCommands(env):
+    """Test JSON.NUMMINBY and JSON.NUMMAXBY commands"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{ "foo": 5, "bar": "baz" }'))
+    r.assertEqual('5', r.execute_command('JSON.NUMMINBY', 'test', '.foo', 10))
+    r.assertEqual('3', r.execute_command('JSON.NUMMINBY', 'test', '.foo', 3))
+    r.assertEqual('3', r.execute_command('JSON.GET', 'test', '.foo'))
+    r.assertEqual('8', r.execute_command('JSON.NUMMAXBY', 'test', '.foo', 8))
+    r.assertEqual('8', r.execute_command('JSON.NUMMAXBY', 'test', '.foo', -1))
+
+    # an integer replaced by a float, or a float by an integer, results in a float
+    r.assertEqual('7.5', r.execute_command('JSON.NUMMINBY', 'test', '.foo', 7.5))
+    r.assertEqual('9.0', r.execute_command('JSON.NUMMAXBY', 'test', '.foo', 9))
+    r.assertEqual('9.0', r.execute_command('JSON.NUMMINBY', 'test', '.foo', 10))
+
+    # a value that is kept keeps its type
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.foo', '5'))
+    r.assertEqual('5', r.execute_command('JSON.NUMMAXBY', 'test', '.foo', 1.5))
+    r.assertEqual('5', r.execute_command('JSON.NUMMINBY', 'test', '.foo', 5.0))
+    r.assertEqual('integer', r.execute_command('JSON.TYPE', 'test', '.foo'))
+    r.assertEqual('5', r.execute_command('JSON.GET', 'test', '.foo'))
+
+    # test a wrong type
+    r.expect('JSON.NUMMINBY', 'test', '.bar', 1).raiseError()
+    r.expect('JSON.NUMMAXBY', 'test', '.bar', 1).raiseError()
 
 def testStrCommands(env):
     """Test JSON.STRAPPEND and JSON.STRLEN commands"""
@@ -765,11 +1768,493 @@ def testStrCommands(env):
     r.assertEqual(3, r.execute_command('JSON.STRLEN', 'test', '.'))
     r.assertEqual(6, r.execute_command('JSON.STRAPPEND', 'test', '.', '"bar"'))
     r.assertEqual('"foobar"', r.execute_command('JSON.GET', 'test', '.'))
+    r.assertEqual(9, r.execute_command('JSON.STRAPPEND', 'test', '"baz"'))
+    r.assertEqual(12, r.execute_command('JSON.STRAPPEND', 'test', '.', '"a"', '"bc"'))
+    r.assertEqual('"foobarbazabc"', r.execute_command('JSON.GET', 'test', '.'))
+    r.expect('JSON.STRAPPEND', 'test', '.', '"a"', '1').error().contains("expected string")
+    r.expect('JSON.STRAPPEND', 'test', '.', '"a"', '"b').error().contains("argument 4 for path '.'")
+    r.assertEqual(12, r.execute_command('JSON.STRLEN', 'test', '.'))
+
+def testStrLenUnicode(env):
+    """Test that string lengths are counted in characters, or in bytes with BYTES"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":"caf\u00e9","b":"\\uD83D\\uDE00","c":"abc"}'))
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '.a'), 4)
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '.a', 'BYTES'), 5)
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', 'bytes', '.b'), 4)
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '$.*'), [4, 1, 3])
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '$.*', 'BYTES'), [5, 4, 3])
+    r.expect('JSON.STRLEN', 'test', '.a', '.b').raiseError()
+
+    r.assertEqual(r.execute_command('JSON.STRAPPEND', 'test', '.a', '"\u00e9"'), 5)
+    r.assertEqual(r.execute_command('JSON.STRAPPEND', 'test', '.a', '"!"', 'BYTES'), 8)
+    r.assertEqual(r.execute_command('JSON.STRAPPEND', 'test', '$.*', '"\u00e9"', 'BYTES'), [10, 6, 5])
+    r.assertEqual(r.execute_command('JSON.STRAPPEND', 'test', '$.*', '"\u00e9"'), [8, 3, 5])
+    r.assertEqual(r.execute_command('JSON.STRREPLACE', 'test', '.c', 'abc', '\u00e9'), 3)
+    r.assertOk(r.execute_command('JSON.SET', 's', '.', '"\u00e9"'))
+    r.assertEqual(r.execute_command('JSON.STRAPPEND', 's', '"\u00e9"', 'BYTES'), 4)
+    r.assertEqual(r.execute_command('JSON.STRLEN', 's'), 2)
+
+def testStrAppendMultiPath(env):
+    """Test JSON.STRAPPEND with paths that match several values"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":"x","b":{"a":"yy"},"c":{"a":1}}'))
+    r.assertEqual(r.execute_command('JSON.STRAPPEND', 'test', '$..a', '"!"'), [2, 3, None])
+    r.assertEqual(r.execute_command('JSON.STRAPPEND', 'test', '$..a', '"1"', '"2"'), [4, 5, None])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test')), {'a': 'x!12', 'b': {'a': 'yy!12'}, 'c': {'a': 1}})
+    r.assertEqual(r.execute_command('JSON.STRAPPEND', 'test', '$.b.*', '"z"'), 6)
+    r.assertEqual(r.execute_command('JSON.STRAPPEND', 'test', '$.*', '"z"'), [5, None, None])
+    r.expect('JSON.STRAPPEND', 'test', '$.c', '"z"').error().contains("not a string")
+
+def testPointerCommand(env):
+    """Test JSON.POINTER command"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"store":{"book":[{"title":"a"},{"title":"b"}]},"a/b":1,"m~n":2,"":3}'))
+    r.assertEqual(r.execute_command('JSON.POINTER', 'test', '/store/book/1/title'), '"b"')
+    r.assertEqual(r.execute_command('JSON.POINTER', 'test', '/store/book/0'), '{"title":"a"}')
+    r.assertEqual(json.loads(r.execute_command('JSON.POINTER', 'test', ''))['m~n'], 2)
+    r.assertEqual(r.execute_command('JSON.POINTER', 'test', '/a~1b'), '1')
+    r.assertEqual(r.execute_command('JSON.POINTER', 'test', '/m~0n'), '2')
+    r.assertEqual(r.execute_command('JSON.POINTER', 'test', '/'), '3')
+
+    # non-matching pointers
+    for p in ['/foo', '/store/book/2', '/store/book/01', '/store/book/-', '/store/book/x', '/a~1b/c']:
+        r.assertEqual(r.execute_command('JSON.POINTER', 'test', p), None)
+    r.assertEqual(r.execute_command('JSON.POINTER', 'missing', '/store'), None)
+
+    # malformed pointers
+    r.expect('JSON.POINTER', 'test', 'store').error().contains('invalid JSON Pointer')
+    r.expect('JSON.POINTER', 'test', '/m~2n').error().contains('invalid JSON Pointer')
+    r.expect('JSON.POINTER', 'test').raiseError()
+
+def testKeysCommand(env):
+    """Test JSON.KEYS"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"b":{"c":[1,{"d":null}],"e":{}},"a b":"x","it\'s":[],"0":true}'))
+    r.assertEqual(r.execute_command('JSON.KEYS', 'test'),
+                  ['$.b.c[0]', '$.b.c[1].d', '$.b.e', "$['a b']", "$['it\\'s']", "$['0']"])
+    r.assertEqual(r.execute_command('JSON.KEYS', 'test', 'NOTATION', 'POINTER'),
+                  ['/b/c/0', '/b/c/1/d', '/b/e', '/a b', "/it's", '/0'])
+    r.assertEqual(r.execute_command('JSON.KEYS', 'test', '$.b', 'notation', 'pointer'), ['/c/0', '/c/1/d', '/e'])
+    r.assertEqual(r.execute_command('JSON.KEYS', 'test', '.b.c[0]'), ['$'])
+    r.assertEqual(r.execute_command('JSON.KEYS', 'test', '.b.c[0]', 'NOTATION', 'POINTER'), [''])
+    # every returned path leads to a leaf
+    for path in r.execute_command('JSON.KEYS', 'test'):
+        r.assertNotEqual(r.execute_command('JSON.GET', 'test', path), '[]')
+    r.assertEqual(r.execute_command('JSON.KEYS', 'missing'), None)
+
+    r.expect('JSON.KEYS', 'test', 'NOTATION', 'DOTS').error().contains('JSONPATH or POINTER')
+    r.expect('JSON.KEYS', 'test', '.b', '.a').error().contains('syntax error')
+    r.expect('JSON.KEYS', 'test', '.x').raiseError()
+
+def testFlattenCommand(env):
+    """Test JSON.FLATTEN command"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":{"b":[1,{"c":null}],"d":"e"},"f":[],"g":{}}'))
+    r.assertEqual(json.loads(r.execute_command('JSON.FLATTEN', 'test')),
+                  {'a.b.0': 1, 'a.b.1.c': None, 'a.d': 'e', 'f': [], 'g': {}})
+    r.assertEqual(json.loads(r.execute_command('JSON.FLATTEN', 'test', '$.a', 'SEPARATOR', '/')),
+                  {'b/0': 1, 'b/1/c': None, 'd': 'e'})
+    r.assertEqual(json.loads(r.execute_command('JSON.FLATTEN', 'test', 'separator', '_')),
+                  {'a_b_0': 1, 'a_b_1_c': None, 'a_d': 'e', 'f': [], 'g': {}})
+    r.assertEqual(json.loads(r.execute_command('JSON.FLATTEN', 'test', '.a.d')), {'': 'e'})
+    r.assertEqual(r.execute_command('JSON.FLATTEN', 'missing'), None)
+
+    # Deep documents do not exhaust the stack
+    r.assertOk(r.execute_command('JSON.SET', 'deep', '.', '[' * 100 + ']' * 100))
+    r.assertEqual(json.loads(r.execute_command('JSON.FLATTEN', 'deep')), {'.'.join(['0'] * 99): []})
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a.b":1,"a":{"b":2}}'))
+    r.expect('JSON.FLATTEN', 'test').error().contains('ambiguous')
+    r.expect('JSON.FLATTEN', 'test', 'SEPARATOR', '').raiseError()
+    r.expect('JSON.FLATTEN', 'test', '.', 'SEPARATOR').raiseError()
+    r.expect('JSON.FLATTEN', 'test', '.', '.a').raiseError()
+
+def testUnflattenCommand(env):
+    """Test JSON.UNFLATTEN command"""
+    r = env
+
+    doc = {'a': {'b': [1, {'c': None}], 'd': 'e'}, 'f': [], 'g': {}, 'h': [[0, 1]] * 11}
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', json.dumps(doc)))
+    flat = r.execute_command('JSON.FLATTEN', 'test')
+    r.assertOk(r.execute_command('JSON.UNFLATTEN', 'copy', '.', flat))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'copy')), doc)
+
+    flat = r.execute_command('JSON.FLATTEN', 'test', '.a', 'SEPARATOR', '/')
+    r.assertOk(r.execute_command('JSON.UNFLATTEN', 'copy', '$.a2', flat, 'SEPARATOR', '/'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'copy', '.a2')), doc['a'])
+    r.assertOk(r.execute_command('JSON.UNFLATTEN', 'copy', '.s', '{"":"scalar"}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'copy', '.s'), '"scalar"')
+    # elements without a path are null
+    r.assertOk(r.execute_command('JSON.UNFLATTEN', 'copy', '.sparse', '{"x.1":1,"y":2}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'copy', '.sparse'), '{"x":[null,1],"y":2}')
+
+    r.expect('JSON.UNFLATTEN', 'copy', '.', '{"a.0":1,"a.b":2}').error().contains('both as an object key and as an array index')
+    r.expect('JSON.UNFLATTEN', 'copy', '.', '{"a":1,"a.b":2}').error().contains('conflicts')
+    r.expect('JSON.UNFLATTEN', 'copy', '.', '{"":1,"a":2}').error().contains('conflicts')
+    r.expect('JSON.UNFLATTEN', 'copy', '.', '{"a.1000000000":1}').error().contains('out of range')
+    r.expect('JSON.UNFLATTEN', 'copy', '.', '[1]').error().contains('must be an object')
+    r.expect('JSON.UNFLATTEN', 'copy', '.', '{"a":1}', 'SEPARATOR', '').raiseError()
+    r.expect('JSON.UNFLATTEN', 'copy', '.', '{"a":1}', 'FOO', '/').raiseError()
+    r.expect('JSON.UNFLATTEN', 'missing', '.a', '{"a":1}').error().contains('at the root')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'copy', '.a')), doc['a'])
+
+def testCopyPathCommand(env):
+    """Test JSON.COPYPATH command"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":{"b":[1,2]},"c":{}}'))
+    r.assertOk(r.execute_command('JSON.COPYPATH', 'test', '.a', '.c.d'))
+    r.assertOk(r.execute_command('JSON.COPYPATH', 'test', '$.a.b[1]', '$.a.b[0]'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test')), {'a': {'b': [2, 2]}, 'c': {'d': {'b': [1, 2]}}})
+
+    # the copy is independent of the original
+    r.assertEqual('3', r.execute_command('JSON.NUMINCRBY', 'test', '.c.d.b[0]', 2))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.a.b'), '[2,2]')
+
+    # missing parents are only created with CREATE
+    r.expect('JSON.COPYPATH', 'test', '.a', '.x.y.z').error().contains("does not exist")
+    r.assertOk(r.execute_command('JSON.COPYPATH', 'test', '.a', '.x.y.z', 'CREATE'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '.x')), {'y': {'z': {'b': [2, 2]}}})
+    r.expect('JSON.COPYPATH', 'test', '.a', '.a.b[5].c', 'CREATE').error().contains("cannot create")
+    r.expect('JSON.COPYPATH', 'test', '.a', '$..b', 'CREATE').error().contains("static path")
+
+    r.expect('JSON.COPYPATH', 'test', '.nope', '.c.e').error().contains("does not exist")
+    r.expect('JSON.COPYPATH', 'test', '.a', '.c.e', 'FOO').raiseError()
+    r.expect('JSON.COPYPATH', 'missing', '.a', '.b').raiseError()
+
+    r.assertOk(r.execute_command('JSON.COPYPATH', 'test', '.a.b', '.'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '[2,2]')
+
+def testMovePathCommand(env):
+    """Test JSON.MOVEPATH command"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":{"b":[1,2,3]},"c":{}}'))
+    r.assertOk(r.execute_command('JSON.MOVEPATH', 'test', '.a.b', '.c.d'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test')), {'a': {}, 'c': {'d': [1, 2, 3]}})
+
+    # the element is removed before the target is looked up, like RFC 6902 does
+    r.assertOk(r.execute_command('JSON.MOVEPATH', 'test', '$.c.d[0]', '$.c.d[1]'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.c.d'), '[2,1]')
+    r.assertOk(r.execute_command('JSON.MOVEPATH', 'test', '.c.d', '.c.d'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.c.d'), '[2,1]')
+
+    r.expect('JSON.MOVEPATH', 'test', '.c', '.c.d.e').error().contains("descendants")
+    r.expect('JSON.MOVEPATH', 'test', '.c', '.c.e').error().contains("descendants")
+    r.expect('JSON.MOVEPATH', 'test', '.', '.x').error().contains("descendants")
+    r.expect('JSON.MOVEPATH', 'test', '.nope', '.x').error().contains("does not exist")
+    r.expect('JSON.MOVEPATH', 'missing', '.a', '.b').raiseError()
+    r.expect('JSON.MOVEPATH', 'test', '.a', '.b', 'FOO').raiseError()
+
+    # a failed move leaves the document unchanged
+    r.expect('JSON.MOVEPATH', 'test', '.c.d[0]', '.x.y').error().contains("does not exist")
+    r.expect('JSON.MOVEPATH', 'test', '.c', '.x.y').error().contains("does not exist")
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test')), {'a': {}, 'c': {'d': [2, 1]}})
+    # the member that failed to move keeps its place
+    r.expect('JSON.MOVEPATH', 'test', '.a', '.x.y').error().contains("does not exist")
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"a":{},"c":{"d":[2,1]}}')
+
+    r.assertOk(r.execute_command('JSON.MOVEPATH', 'test', '.c.d', '.'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '[2,1]')
+
+def testValidateCommand(env):
+    """Test JSON.VALIDATE command"""
+    r = env
+
+    schema = json.dumps({
+        'type': 'object',
+        'required': ['name', 'tags'],
+        'properties': {
+            'name': {'type': 'string', 'minLength': 1},
+            'age': {'type': 'integer', 'minimum': 0},
+            'tags': {'type': 'array', 'items': {'type': 'string'}, 'uniqueItems': True},
+        },
+        'additionalProperties': False,
+    })
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"name":"a","age":3,"tags":["x","y"]}'))
+    r.assertEqual(1, r.execute_command('JSON.VALIDATE', 'test', 'SCHEMA', schema))
+    r.assertEqual([], r.execute_command('JSON.VALIDATE', 'test', 'SCHEMA', schema, 'ERRORS'))
+    r.assertEqual(1, r.execute_command('JSON.VALIDATE', 'test', '.tags', 'SCHEMA', '{"maxItems":2}'))
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"name":"","age":-1.5,"tags":["x",1,"x"],"a/b":0}'))
+    r.assertEqual(0, r.execute_command('JSON.VALIDATE', 'test', 'SCHEMA', schema))
+    r.assertEqual(r.execute_command('JSON.VALIDATE', 'test', 'SCHEMA', schema, 'ERRORS'), [
+        ['/name', 'string is shorter than 1 characters'],
+        ['/age', 'expected integer, got number'],
+        ['/age', '-1.5 is less than the minimum of 0'],
+        ['/tags', 'item 2 is a duplicate'],
+        ['/tags/1', 'expected string, got integer'],
+        ['/a~1b', 'no value is allowed'],
+    ])
+    r.assertEqual(r.execute_command('JSON.VALIDATE', 'test', '$.tags', 'SCHEMA', '{"contains":{"type":"integer"}}', 'ERRORS'), [])
+
+    r.assertEqual(1, r.execute_command('JSON.VALIDATE', 'test', 'SCHEMA', 'true'))
+    r.assertEqual(0, r.execute_command('JSON.VALIDATE', 'test', 'SCHEMA', 'false'))
+    r.assertEqual(None, r.execute_command('JSON.VALIDATE', 'missing', 'SCHEMA', schema))
+
+    r.expect('JSON.VALIDATE', 'test', 'SCHEMA', '{"type":"nope"}').error().contains("invalid schema")
+    r.expect('JSON.VALIDATE', 'test', 'SCHEMA', '{"minItems"').error().contains("invalid schema")
+    r.expect('JSON.VALIDATE', 'test', 'SCHEMA', '{"$ref":"#"}').error().contains("not supported")
+    r.expect('JSON.VALIDATE', 'test', '.nope', 'SCHEMA', 'true').raiseError()
+    r.expect('JSON.VALIDATE', 'test', 'SCHEMA').raiseError()
+    r.expect('JSON.VALIDATE', 'test').raiseError()
+    r.expect('JSON.VALIDATE', 'test', 'SCHEMA', 'true', '.name').raiseError()
+
+def testGetRequire(env):
+    """Test JSON.GET REQUIRE"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":null,"b":[1]}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.nope'), '[]')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '.a', '.nope')), {'.a': None, '.nope': None})
+
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'REQUIRE', '$.a'), '[null]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.b[0]', 'require'), '[1]')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', 'REQUIRE', '.a', '$.b')), {'.a': None, '$.b': [1]})
+    r.expect('JSON.GET', 'test', 'REQUIRE', '$.nope').error().contains("path '$.nope' does not exist")
+    r.expect('JSON.GET', 'test', 'REQUIRE', '.a', '.nope').error().contains("path '.nope' does not exist")
+    r.expect('JSON.GET', 'test', 'REQUIRE', '.a', '$[').error().contains("JSON Path error")
+    r.expect('JSON.GET', 'test', 'REQUIRE', '.nope').error().contains("does not exist")
+    r.assertEqual(r.execute_command('JSON.GET', 'missing', 'REQUIRE', '$.a'), None)
+
+def testGetPrecision(env):
+    """Test JSON.GET PRECISION"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"f":0.30000000000000004,"pi":3.14159,"big":1234.5,"i":12345}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.f'), '0.30000000000000004')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'PRECISION', 15, '.f'), '0.3')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', 'precision', 3)), {'f': 0.3, 'pi': 3.14, 'big': 1230.0, 'i': 12345})
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'PRECISION', 2, '$.big'), '[1200.0]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'PRECISION', 1, '.i'), '12345')
+
+    # the stored value is untouched
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.pi'), '3.14159')
+
+    r.expect('JSON.GET', 'test', 'PRECISION', 0).error().contains("between 1 and 17")
+    r.expect('JSON.GET', 'test', 'PRECISION', 18).error().contains("between 1 and 17")
+    r.expect('JSON.GET', 'test', 'PRECISION', 'x').raiseError()
+
+def testGetNumFormat(env):
+    """Test JSON.GET NUMFORMAT"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"big":1e21,"small":0.00001,"f":1234.5,"i":5}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.'), '{"big":1e21,"small":1e-5,"f":1234.5,"i":5}')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'NUMFORMAT', 'AUTO', '.'), '{"big":1e21,"small":1e-5,"f":1234.5,"i":5}')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'NUMFORMAT', 'FIXED', '.'), '{"big":1000000000000000000000.0,"small":0.00001,"f":1234.5,"i":5}')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'numformat', 'sci', '.'), '{"big":1e21,"small":1e-5,"f":1.2345e3,"i":5}')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'NUMFORMAT', 'SCI', 'PRECISION', 2, '$.f'), '[1.2e3]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.f'), '1234.5')
+    r.expect('JSON.GET', 'test', 'NUMFORMAT', 'HEX').error().contains("FIXED, SCI or AUTO")
+
+def testWrongTypeKey(env):
+    """Test that commands on a non-JSON key reply WRONGTYPE"""
+    r = env
+
+    r.assertOk(r.execute_command('SET', 'str', 'foo'))
+    r.execute_command('LPUSH', 'list', 'foo')
+    for cmd in [['JSON.GET', 'str'], ['JSON.GET', 'list', '.'],
+                ['JSON.SET', 'str', '.', '1'], ['JSON.SET', 'str', '.a', '1'],
+                ['JSON.ARRAPPEND', 'list', '.', '1'], ['JSON.DEL', 'str'],
+                ['JSON.TYPE', 'str'], ['JSON.NUMINCRBY', 'str', '.', '1'],
+                ['JSON.STRLEN', 'str', '.'], ['JSON.RESP', 'str']]:
+        r.expect(*cmd).error().contains('WRONGTYPE Operation against a key holding the wrong kind of value')
+    r.assertEqual(r.execute_command('GET', 'str'), 'foo')
+
+def testParseErrorContext(env):
+    """Test that parse errors tell which argument is invalid"""
+    r = env
+
+    r.expect('JSON.SET', 'test', '.', '{"a":').error().contains("argument 3 for path '.'").contains("line 1 column 5")
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":[],"n":1}'))
+    r.expect('JSON.SET', 'test', '$.b', '{\n"x":}').error().contains("argument 3 for path '$.b'").contains("line 2")
+    r.expect('JSON.ARRAPPEND', 'test', '.a', '1', '"x"', 'nope').error().contains("argument 5 for path '.a'")
+    r.expect('JSON.ARRINSERT', 'test', '.a', '0', '[', '1').error().contains("argument 4 for path '.a'")
+    r.expect('JSON.NUMINCRBY', 'test', '.n', '1x').error().contains("argument 3 for path '.n'")
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"a":[],"n":1}')
+
+def testErrorKinds(env):
+    """Test the message replied for each kind of error"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":[1],"n":1,"s":"x"}'))
+    # wrong type
+    r.expect('JSON.STRLEN', 'test', '.n').error().contains('ERR wrong type of path value')
+    r.expect('JSON.ARRLEN', 'test', '.s').error().contains('ERR wrong type of path value')
+    # path not found
+    r.expect('JSON.STRLEN', 'test', '.missing').error().contains('ERR path does not exist')
+    r.expect('JSON.GET', 'test', 'REQUIRE', '.missing').error().contains("ERR path '.missing' does not exist")
+    # parse error
+    r.expect('JSON.NUMINCRBY', 'test', '.n', '[').error().contains("ERR failed to parse argument 3 for path '.n'")
+    r.expect('JSON.GET', 'test', '$[').error().contains('JSON Path error')
+    # index out of bounds
+    r.expect('JSON.ARRINSERT', 'test', '.a', 5, '0').error().contains('ERR index out of bounds')
+    r.expect('JSON.ARRPOP', 'test', '.a', 3).error().contains('ERR index out of range')
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"a":[1],"n":1,"s":"x"}')
+
+def testValidatePathCommand(env):
+    """Test JSON.VALIDATEPATH command"""
+    r = env
+
+    for path in ['.', '$', '.a.b[0]', 'a["b"]', '$..a', '$.a[*]', '$.a[1:3]', '$.a[?(@.b>1)]']:
+        r.assertOk(r.execute_command('JSON.VALIDATEPATH', path))
+    for path in ['$[', '.a[', '$.a[?(@.b>)]']:
+        r.expect('JSON.VALIDATEPATH', path).error().contains("JSON Path error")
+    r.expect('JSON.VALIDATEPATH').raiseError()
+    r.expect('JSON.VALIDATEPATH', '.', '.').raiseError()
+
+def testMultiOpCommand(env):
+    """Test JSON.MULTIOP command"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"x":[1],"n":1,"s":"a","b":true,"o":{"k":1}}'))
+    ops = [
+        {'op': 'arrappend', 'path': '$.x', 'args': [2, 3]},
+        {'op': 'arrinsert', 'path': '$.x', 'args': [0, 0]},
+        {'op': 'arrpop', 'path': '$.x'},
+        {'op': 'arrtrim', 'path': '$.x', 'args': [1, 2]},
+        {'op': 'numincrby', 'path': '$.n', 'args': [2]},
+        {'op': 'strappend', 'path': '$.s', 'args': ['bc']},
+        {'op': 'TOGGLE', 'path': '$.b'},
+        {'op': 'set', 'path': '$.o.j', 'args': [{'a': None}]},
+        {'op': 'del', 'path': '$.o.k'},
+        {'op': 'clear', 'path': '$.o.j'},
+    ]
+    r.assertEqual(r.execute_command('JSON.MULTIOP', 'test', json.dumps(ops)),
+                  [3, 4, '3', 2, '3', 3, 'false', 'OK', 1, 1])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test')),
+                  {'x': [1, 2], 'n': 3, 's': 'abc', 'b': False, 'o': {'j': {}}})
+
+    # clearing a match clears the matches nested in it
+    r.assertOk(r.execute_command('JSON.SET', 'nested', '$', '{"a":[[1,[2]],3],"b":{"c":[4]},"n":5}'))
+    r.assertEqual(r.execute_command('JSON.MULTIOP', 'nested', json.dumps([{'op': 'clear', 'path': '$..*'}])), [3])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'nested')), {'a': [], 'b': {}, 'n': 0})
+
+    # a failed operation leaves the document as it was
+    ops = [
+        {'op': 'arrappend', 'path': '$.x', 'args': [3]},
+        {'op': 'numincrby', 'path': '$.s', 'args': [1]},
+    ]
+    r.expect('JSON.MULTIOP', 'test', json.dumps(ops)).error().contains('(operation 1)')
+    r.expect('JSON.MULTIOP', 'test', json.dumps([{'op': 'arrinsert', 'path': '$', 'args': [9, 1]}])).raiseError()
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test')),
+                  {'x': [1, 2], 'n': 3, 's': 'abc', 'b': False, 'o': {'j': {}}})
+
+    # a new key can be created, and is not kept when an operation fails
+    r.assertEqual(r.execute_command('JSON.MULTIOP', 'new', json.dumps([{'op': 'set', 'args': [[]]}, {'op': 'arrappend', 'args': [1]}])), ['OK', 1])
+    r.assertEqual(r.execute_command('JSON.GET', 'new'), '[1]')
+    r.expect('JSON.MULTIOP', 'other', json.dumps([{'op': 'set', 'args': [[]]}, {'op': 'toggle'}])).raiseError()
+    r.assertEqual(r.execute_command('EXISTS', 'other'), 0)
+
+    # the document is put back as it was, even when it would no longer be accepted as is
+    r.assertOk(r.execute_command('JSON.SET', 'deep', '$', '{"a":{"b":{"c":[1]}},"d":1}'))
+    r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'MAX_DEPTH', '2'))
+    try:
+        r.expect('JSON.MULTIOP', 'deep', json.dumps([{'op': 'del', 'path': '$.d'}, {'op': 'toggle', 'path': '$.d'}])).error().contains('(operation 1)')
+        r.assertEqual(r.execute_command('JSON.GET', 'deep'), '{"a":{"b":{"c":[1]}},"d":1}')
+    finally:
+        r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'MAX_DEPTH', '127'))
+
+    r.expect('JSON.MULTIOP', 'test', '{}').raiseError()
+    r.expect('JSON.MULTIOP', 'test', '[{"op":"nope"}]').error().contains('unknown operation')
+    r.expect('JSON.MULTIOP', 'test', '[{"op":"set"}]').error().contains('wrong number of arguments')
+    r.expect('JSON.MULTIOP', 'test', '[{"op":"del","path":"$"}]').raiseError()
+    r.expect('JSON.MULTIOP', 'test', '[{"op":"del","extra":1}]').raiseError()
+    r.assertEqual(r.execute_command('JSON.MULTIOP', 'test', '[]'), [])
+
+def testDiffCommand(env):
+    """Test JSON.DIFF command"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'a', '.', '{"x":1,"y":{"z":[1,2],"w":"s"},"d":true}'))
+    r.assertOk(r.execute_command('JSON.SET', 'b', '.', '{"x":2,"y":{"z":[1],"w":"s"},"n":null}'))
+    r.assertEqual(json.loads(r.execute_command('JSON.DIFF', 'a', 'b')),
+                  {'d': None, 'n': None, 'x': 2, 'y': {'z': [1]}})
+    r.assertEqual(json.loads(r.execute_command('JSON.DIFF', 'a', 'b', '.y')), {'z': [1]})
+    r.assertEqual(json.loads(r.execute_command('JSON.DIFF', 'a', 'a')), {})
+    r.assertEqual(json.loads(r.execute_command('JSON.DIFF', 'a', 'b', '.x')), 2)
+
+    # operations are ordered by path
+    r.assertEqual(json.loads(r.execute_command('JSON.DIFF', 'a', 'b', 'PATCH')), [
+        {'op': 'remove', 'path': '/d'},
+        {'op': 'add', 'path': '/n', 'value': None},
+        {'op': 'replace', 'path': '/x', 'value': 2},
+        {'op': 'remove', 'path': '/y/z/1'},
+    ])
+    r.assertEqual(json.loads(r.execute_command('JSON.DIFF', 'b', 'a', '$.y', 'patch')),
+                  [{'op': 'add', 'path': '/z/1', 'value': 2}])
+    r.assertEqual(json.loads(r.execute_command('JSON.DIFF', 'a', 'a', 'PATCH')), [])
+
+    r.assertOk(r.execute_command('JSON.SET', 'c', '.', '{"a/b":[1,2,3],"m~":0}'))
+    r.assertOk(r.execute_command('JSON.SET', 'd', '.', '{"a/b":"s"}'))
+    r.assertEqual(json.loads(r.execute_command('JSON.DIFF', 'c', 'd', 'PATCH')), [
+        {'op': 'replace', 'path': '/a~1b', 'value': 's'},
+        {'op': 'remove', 'path': '/m~0'},
+    ])
+
+    r.assertEqual(r.execute_command('JSON.DIFF', 'a', 'missing'), None)
+    r.assertEqual(r.execute_command('JSON.DIFF', 'missing', 'a'), None)
+    r.expect('JSON.DIFF', 'a', 'b', '.nope').raiseError()
+    r.expect('JSON.DIFF', 'a', 'b', 'PATCH', '.x').raiseError()
+    r.expect('JSON.DIFF', 'a').raiseError()
+
+def testStrReplaceCommand(env):
+    """Test JSON.STRREPLACE command"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"s":"aaaa ab","n":1}'))
+    r.assertEqual(5, r.execute_command('JSON.STRREPLACE', 'test', '.s', 'aa', 'b'))
+    r.assertEqual('"bb ab"', r.execute_command('JSON.GET', 'test', '.s'))
+    r.assertEqual(7, r.execute_command('JSON.STRREPLACE', 'test', '.s', 'b', 'cc', 'COUNT', '2'))
+    r.assertEqual('"cccc ab"', r.execute_command('JSON.GET', 'test', '.s'))
+    r.assertEqual(7, r.execute_command('JSON.STRREPLACE', 'test', '$.s', 'x', 'y'))
+    r.assertEqual(7, r.execute_command('JSON.STRREPLACE', 'test', '.s', 'c', 'd', 'count', '0'))
+    r.assertEqual(3, r.execute_command('JSON.STRREPLACE', 'test', '.s', 'cccc', ''))
+    r.assertEqual('" ab"', r.execute_command('JSON.GET', 'test', '.s'))
+
+    r.expect('JSON.STRREPLACE', 'test', '.n', 'a', 'b').error().contains("not a string")
+    r.expect('JSON.STRREPLACE', 'test', '.s', '', 'b').raiseError()
+    r.expect('JSON.STRREPLACE', 'test', '.s', 'a', 'b', 'COUNT', '-1').raiseError()
+    r.expect('JSON.STRREPLACE', 'test', '.s', 'a', 'b', 'FOO').raiseError()
+    r.expect('JSON.STRREPLACE', 'missing', '.s', 'a', 'b').raiseError()
+
+def testStrReplaceNotifications(env):
+    """Test JSON.STRREPLACE only notifies when a string changed"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '"abc"'))
+    r.assertOk(r.execute_command('CONFIG', 'SET', 'notify-keyspace-events', 'Kd'))
+    p = env.getConnection().pubsub()
+    p.subscribe('__keyspace@0__:test')
+    r.assertEqual(3, r.execute_command('JSON.STRREPLACE', 'test', '.', 'x', 'y'))
+    r.assertEqual(3, r.execute_command('JSON.STRREPLACE', 'test', '.', 'a', 'z', 'COUNT', '0'))
+    r.assertEqual(4, r.execute_command('JSON.STRAPPEND', 'test', '"d"'))
+    r.assertEqual(4, r.execute_command('JSON.STRREPLACE', 'test', '.', 'a', 'z'))
+    events = []
+    while len(events) < 2:
+        message = p.get_message(timeout=10)
+        r.assertIsNotNone(message)
+        if message['type'] == 'message':
+            events.append(message['data'])
+    r.assertEqual(events, ['json.strappend', 'json.strreplace'])
+    r.assertEqual('"zbcd"', r.execute_command('JSON.GET', 'test'))
+    p.close()
+    r.assertOk(r.execute_command('CONFIG', 'SET', 'notify-keyspace-events', ''))
 
 def testRespCommand(env):
     """Test JSON.RESP command"""
     r = env
 
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '[1, 2.0, 2.5, -3.0]'))
+    r.assertEqual(['[', 1, '2.0', '2.5', '-3.0'], r.execute_command('JSON.RESP', 'test'))
+
     r.assertOk(r.execute_command('JSON.SET', 'test', '.', 'null'))
 #   r.assertIsNone(r.execute_command('JSON.RESP', 'test'))
 #   r.assertOk(r.execute_command('JSON.SET', 'test', '.', 'true'))
@@ -831,6 +2316,196 @@ def testSetGetComparePassJSONCaseFiles(env):
                     d2 = json.loads(raw)
                     r.assertEqual(d1, d2, message=path)
 
+def testDebugMemory(env):
+    """Test that JSON.DEBUG MEMORY counts what the values allocate"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"s":"a","l":"%s","arr":[],"big":[%s],"obj":{"k":1}}' % ('x' * 1000, ','.join(['1'] * 100))))
+    # the size of a value that allocates nothing
+    value_size = r.execute_command('JSON.DEBUG', 'MEMORY', 'test', '.obj.k')
+    r.assertGreater(value_size, 0)
+    r.assertEqual(r.execute_command('JSON.DEBUG', 'MEMORY', 'test', '.arr'), value_size)
+    r.assertGreaterEqual(r.execute_command('JSON.DEBUG', 'MEMORY', 'test', '.s'), value_size + 1)
+    r.assertGreaterEqual(r.execute_command('JSON.DEBUG', 'MEMORY', 'test', '.l'), value_size + 1000)
+    r.assertGreaterEqual(r.execute_command('JSON.DEBUG', 'MEMORY', 'test', '.big'), value_size * 101)
+    r.assertGreater(r.execute_command('JSON.DEBUG', 'MEMORY', 'test', '.obj'), value_size * 2)
+
+    root_size = r.execute_command('JSON.DEBUG', 'MEMORY', 'test')
+    r.assertGreater(root_size, 1000 + value_size * 101)
+    r.assertGreaterEqual(r.execute_command('MEMORY', 'USAGE', 'test'), root_size)
+
+    # several paths reply the size of each one, 0 when it matches nothing
+    sizes = [r.execute_command('JSON.DEBUG', 'MEMORY', 'test', p) for p in ['.s', '.l', '$.obj']]
+    r.assertEqual(r.execute_command('JSON.DEBUG', 'MEMORY', 'test', '.s', '.l', '$.obj'), sizes)
+    r.assertEqual(r.execute_command('JSON.DEBUG', 'MEMORY', 'test', '.nope', '.arr'), [0, value_size])
+    r.assertEqual(r.execute_command('JSON.DEBUG', 'MEMORY', 'missing', '.s', '.l'), [0, 0])
+    r.expect('JSON.DEBUG', 'MEMORY', 'test', '.s', '$.a[').raiseError()
+
+def testMemoryCommand(env):
+    """Test that JSON.MEMORY reports the sizes JSON.DEBUG MEMORY does"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"s":"%s","n":1}' % ('x' * 100)))
+    r.assertEqual(r.execute_command('JSON.MEMORY', 'test'), r.execute_command('JSON.DEBUG', 'MEMORY', 'test'))
+    r.assertEqual(r.execute_command('JSON.MEMORY', 'test', '$.s'), r.execute_command('JSON.DEBUG', 'MEMORY', 'test', '$.s'))
+    r.assertGreater(r.execute_command('JSON.MEMORY', 'test', '.s'), 100)
+    r.assertEqual(r.execute_command('JSON.MEMORY', 'test', '.s', '.n', '.nope'),
+                  r.execute_command('JSON.DEBUG', 'MEMORY', 'test', '.s', '.n', '.nope'))
+    r.assertEqual(r.execute_command('JSON.MEMORY', 'missing'), 0)
+    r.expect('JSON.MEMORY', 'test', '.nope').raiseError()
+    r.expect('JSON.MEMORY').raiseError()
+
+def testDebugKeyspace(env):
+    """Test that JSON.DEBUG KEYSPACE counts the values of each type"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$',
+                                 '{"a": [1, 2.5, "s", {"b": null}], "c": true, "d": {}, "e": []}'))
+    shape = r.execute_command('JSON.DEBUG', 'KEYSPACE', 'test')
+    r.assertEqual(dict(zip(shape[::2], shape[1::2])), {
+        'objects': 3, 'arrays': 2, 'strings': 1, 'numbers': 2, 'booleans': 1, 'nulls': 1,
+        'max_depth': 3, 'leaves': 7,
+    })
+    shape = r.execute_command('JSON.DEBUG', 'KEYSPACE', 'test', '$.a[0]')
+    r.assertEqual(dict(zip(shape[::2], shape[1::2])), {
+        'objects': 0, 'arrays': 0, 'strings': 0, 'numbers': 1, 'booleans': 0, 'nulls': 0,
+        'max_depth': 0, 'leaves': 1,
+    })
+    r.assertEqual(shape[-1], len(json.loads(r.execute_command('JSON.FLATTEN', 'test', '$.a[0]'))))
+
+    r.assertEqual(r.execute_command('JSON.DEBUG', 'KEYSPACE', 'missing'), None)
+    r.expect('JSON.DEBUG', 'KEYSPACE', 'test', '$.nope').raiseError()
+    r.expect('JSON.DEBUG', 'KEYSPACE', 'test', '$', 'extra').raiseError()
+    r.assertTrue(any('KEYSPACE' in l for l in r.execute_command('JSON.DEBUG', 'HELP')))
+
+def testDebugVersion(env):
+    """Test JSON.DEBUG VERSION"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":1}'))
+    r.assertEqual(r.execute_command('JSON.DEBUG', 'VERSION', 'test'), 4)
+    keys = ['test']
+    version = r.execute_command('INFO', 'server')['redis_version']
+    if tuple(int(v) for v in version.split('.')[:2]) >= (6, 2):
+        # COPY keeps the version of the copied document
+        r.assertEqual(r.execute_command('COPY', 'test', 'copy'), 1)
+        keys.append('copy')
+    for _ in r.retry_with_rdb_reload():
+        for key in keys:
+            r.assertEqual(r.execute_command('JSON.DEBUG', 'VERSION', key), 4)
+    r.assertEqual(r.execute_command('JSON.DEBUG', 'VERSION', 'missing'), None)
+    r.expect('JSON.DEBUG', 'VERSION').raiseError()
+    r.expect('JSON.DEBUG', 'VERSION', 'test', 'extra').raiseError()
+    r.execute_command('SET', 'str', 'x')
+    r.expect('JSON.DEBUG', 'VERSION', 'str').error().contains('WRONGTYPE')
+    r.assertTrue(any('VERSION' in l for l in r.execute_command('JSON.DEBUG', 'HELP')))
+
+def testAclCategory(env):
+    """Test that the commands are in the json ACL category"""
+    r = env
+    version = r.execute_command('INFO', 'server')['redis_version']
+    if tuple(int(n) for n in version.split('.')[:2]) < (7, 2):
+        env.skip()
+
+    commands = r.execute_command('ACL', 'CAT', 'json')
+    for command in ['json.get', 'json.set', 'json.del', 'json.forget', 'json.arrappend', 'json.multiop', 'json.config']:
+        r.assertIn(command, commands)
+    r.assertTrue(all(c.startswith('json.') for c in commands))
+    # the categories of the command flags are kept
+    r.assertIn('json.set', r.execute_command('ACL', 'CAT', 'write'))
+    r.assertIn('json.get', r.execute_command('ACL', 'CAT', 'read'))
+    r.assertIn('json.config', r.execute_command('ACL', 'CAT', 'dangerous'))
+
+    r.assertOk(r.execute_command('ACL', 'SETUSER', 'jsonuser', 'on', '>pass', '~*', '+@json', '-@dangerous'))
+    try:
+        kwargs = env.getConnection().connection_pool.connection_kwargs
+        conn = redis.Redis(host=kwargs.get('host', 'localhost'), port=kwargs['port'],
+                           username='jsonuser', password='pass', decode_responses=True)
+        r.assertEqual(conn.execute_command('JSON.SET', 'test', '$', '{"a":1}'), 'OK')
+        r.assertEqual(conn.execute_command('JSON.GET', 'test', '$.a'), '[1]')
+        for cmd in [['JSON.CONFIG', 'GET', 'MAX_DEPTH'], ['SET', 'str', 'x']]:
+            try:
+                conn.execute_command(*cmd)
+                r.assertTrue(False, cmd)
+            except redis.exceptions.ResponseError as e:
+                r.assertIn('permission', str(e))
+        conn.close()
+    finally:
+        r.execute_command('ACL', 'DELUSER', 'jsonuser')
+
+def testAuxDataSavedInRdb(env):
+    """Test that the auxiliary data saved after the keys is loaded back from the RDB"""
+    r = env
+
+    # saved and checked even when there are no JSON keys
+    r.assertOk(r.execute_command('DEBUG', 'RELOAD'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":[1,"b"]}'))
+    r.assertOk(r.execute_command('SET', 'str', 'x'))
+    for _ in r.retry_with_rdb_reload():
+        r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"a":[1,"b"]}')
+        r.assertEqual(r.execute_command('JSON.DEBUG', 'VERSION', 'test'), 4)
+        r.assertEqual(r.execute_command('GET', 'str'), 'x')
+    r.assertOk(r.execute_command('DEBUG', 'RELOAD'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"a":[1,"b"]}')
+
+def testSharedDocuments(env):
+    """Test that keys set to the same document share it until one of them is modified"""
+    r = env
+
+    doc = '{"l":"%s","arr":[%s]}' % ('x' * 10000, ','.join(['1'] * 1000))
+    r.assertOk(r.execute_command('JSON.SET', 'a', '.', doc))
+    alone = r.execute_command('MEMORY', 'USAGE', 'a')
+    # documents are only shared with SHARE_DOCS
+    r.assertOk(r.execute_command('JSON.SET', 'b', '.', doc))
+    r.assertEqual(r.execute_command('MEMORY', 'USAGE', 'a'), alone)
+    r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'SHARE_DOCS', 'YES'))
+    r.assertOk(r.execute_command('JSON.SET', 'a', '.', doc))
+    r.assertOk(r.execute_command('JSON.SET', 'b', '.', doc))
+    r.assertLess(r.execute_command('MEMORY', 'USAGE', 'a'), alone)
+    # the same members in another order are a different document
+    r.assertOk(r.execute_command('JSON.SET', 'c', '.', json.dumps({'arr': [1] * 1000, 'l': 'x' * 10000})))
+    r.assertEqual(r.execute_command('JSON.OBJKEYS', 'c'), ['arr', 'l'])
+    r.assertEqual(r.execute_command('JSON.OBJKEYS', 'b'), ['l', 'arr'])
+
+    # modifying a key copies the document first
+    r.assertEqual(r.execute_command('JSON.ARRAPPEND', 'b', '.arr', 2), 1001)
+    r.assertEqual(r.execute_command('JSON.ARRLEN', 'a', '.arr'), 1000)
+    r.assertEqual(r.execute_command('JSON.GET', 'a'), doc)
+    r.assertEqual(r.execute_command('MEMORY', 'USAGE', 'a'), alone)
+    r.assertEqual(r.execute_command('JSON.GET', 'b', '.arr[-1]'), '2')
+
+    version = r.execute_command('INFO', 'server')['redis_version']
+    if tuple(int(v) for v in version.split('.')[:2]) >= (6, 2):
+        # COPY shares the document too
+        r.assertEqual(r.execute_command('COPY', 'a', 'd'), 1)
+        r.assertOk(r.execute_command('JSON.SET', 'd', '.l', '"y"'))
+        r.assertEqual(r.execute_command('JSON.GET', 'd', '.l'), '"y"')
+        r.assertEqual(r.execute_command('JSON.GET', 'a'), doc)
+
+    # the documents are saved and loaded separately
+    for _ in r.retry_with_rdb_reload():
+        r.assertEqual(r.execute_command('JSON.ARRLEN', 'a', '.arr'), 1000)
+        r.assertEqual(r.execute_command('JSON.ARRLEN', 'b', '.arr'), 1001)
+    r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'SHARE_DOCS', 'NO'))
+
+def testRdbReloadUnaccessed(env):
+    """Test documents that are saved again, or changed, before being accessed after a load"""
+    r = env
+
+    doc = {'a': [1, 2.5, {'b': None}], 'c': '\u00e9', 'd': {'e': True}}
+    r.assertOk(r.execute_command('JSON.SET', 'doc1', '.', json.dumps(doc)))
+    r.assertOk(r.execute_command('JSON.SET', 'doc2', '.', json.dumps(doc)))
+    for _ in r.retry_with_rdb_reload():
+        pass
+    for _ in r.retry_with_rdb_reload():
+        pass
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'doc1')), doc)
+    r.assertOk(r.execute_command('JSON.SET', 'doc2', '$.d.e', 'false'))
+    for _ in r.retry_with_rdb_reload():
+        r.assertEqual(json.loads(r.execute_command('JSON.GET', 'doc1')), doc)
+        r.assertEqual(r.execute_command('JSON.GET', 'doc2', '$.d.e'), '[false]')
+        r.assertEqual(r.execute_command('JSON.TYPE', 'doc2', '.a[1]'), 'number')
+
 def testIssue_13(env):
     """https://github.com/RedisJSON/RedisJSON/issues/13"""
     r = env
@@ -873,6 +2548,361 @@ def testMultiPathResults(env):
     # make sure legacy json path returns single result
     env.expect("JSON.GET", "k", '.*[0,2]').equal('1')
 
+def testMaxDepth(env):
+    r = env
+    # This shouldn't crash Redis
+    depth = 100000
+    r.expect('JSON.SET', 'test', '.', '[' * depth + ']' * depth).raiseError()
+    r.expect('JSON.SET', 'test', '.', '{"a":' * depth + '1' + '}' * depth).raiseError()
+
+    # nesting a value deep in the document is limited as well
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":' * 100 + '1' + '}' * 100))
+    path = '$' + '.a' * 99
+    r.expect('JSON.SET', 'test', path, '[' * 50 + ']' * 50).error().contains('maximum nesting depth')
+    r.expect('JSON.SET', 'test', path + '.b', '[' * 50 + ']' * 50).error().contains('maximum nesting depth')
+    r.assertOk(r.execute_command('JSON.SET', 'test', path, '[' * 28 + ']' * 28))
+    r.assertEqual(r.execute_command('JSON.ARRAPPEND', 'test', path + '[0]' * 27, '[1]'), 1)
+    r.expect('JSON.ARRAPPEND', 'test', path + '[0]' * 27, '[[1]]').error().contains('maximum nesting depth')
+
+def testDeepInput(env):
+    """Test that megabytes of nested arrays and objects are an error for every command that parses a value"""
+    r = env
+
+    depth = 1000000
+    nested = '[' * depth + ']' * depth
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '[]'))
+    r.expect('JSON.SET', 'test', '.', nested).error().contains('recursion limit exceeded')
+    r.expect('JSON.SET', 'test', '.', '{"a":' * depth + '1' + '}' * depth).error().contains('recursion limit exceeded')
+    r.expect('JSON.ARRAPPEND', 'test', '.', nested).error().contains('recursion limit exceeded')
+    r.expect('JSON.ARRINSERT', 'test', '.', 0, nested).error().contains('recursion limit exceeded')
+    r.expect('JSON.ARRINDEX', 'test', '.', nested).raiseError()
+    r.expect('JSON.GET', 'test', 'DEFAULT', nested, '.a').raiseError()
+    r.expect('JSON.MULTIOP', 'test', nested).raiseError()
+    r.expect('JSON.SET', 'test', '.', '*2\r\n+[\r\n' * depth + ':1\r\n', 'FORMAT', 'RESP').error().contains('maximum nesting depth')
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '[]')
+
+    r.execute_command('JSON.CONFIG', 'SET', 'DUPLICATE_KEYS', 'ERROR')
+    try:
+        r.expect('JSON.SET', 'test', '.', '{"a":' * depth + '1' + '}' * depth).error().contains('recursion limit exceeded')
+    finally:
+        r.execute_command('JSON.CONFIG', 'SET', 'DUPLICATE_KEYS', 'LAST')
+    r.assertEqual(r.execute_command('PING'), True)
+
+def testRespCanonical(env):
+    r = env
+    r.assertOk(r.execute_command('JSON.SET', 'test1', '.', '{"b":1,"a":{"d":[{"f":1,"e":2}],"c":null}}'))
+    r.assertOk(r.execute_command('JSON.SET', 'test2', '.', '{"a":{"c":null,"d":[{"e":2,"f":1}]},"b":1}'))
+    r.assertEqual(r.execute_command('JSON.RESP', 'test1'),
+                  ['{', 'b', 1, 'a', ['{', 'd', ['[', ['{', 'f', 1, 'e', 2]], 'c', None]])
+    r.assertEqual(r.execute_command('JSON.RESP', 'test1', 'CANONICAL'),
+                  ['{', 'a', ['{', 'c', None, 'd', ['[', ['{', 'e', 2, 'f', 1]]], 'b', 1])
+    r.assertEqual(r.execute_command('JSON.RESP', 'test1', 'canonical'), r.execute_command('JSON.RESP', 'test2', 'CANONICAL'))
+    r.assertEqual(r.execute_command('JSON.RESP', 'test1', '.a.d[0]', 'CANONICAL'), ['{', 'e', 2, 'f', 1])
+    r.assertEqual(r.execute_command('JSON.RESP', 'test1', 'CANONICAL', '.a.d[0]'), ['{', 'e', 2, 'f', 1])
+    r.expect('JSON.RESP', 'test1', '.a', '.b').raiseError()
+
+def testSetKeepTtl(env):
+    """Test that JSON.SET keeps the TTL of an existing key, with or without KEEPTTL"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":1}'))
+    r.assertEqual(r.execute_command('PEXPIRE', 'test', 100000), 1)
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"b":2}'))
+    r.assertGreater(r.execute_command('PTTL', 'test'), 0)
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '[1]', 'KEEPTTL'))
+    r.assertGreater(r.execute_command('PTTL', 'test'), 0)
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$[0]', '2', 'keepttl', 'XX'))
+    r.assertGreater(r.execute_command('PTTL', 'test'), 0)
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '[2]')
+
+    r.assertOk(r.execute_command('JSON.SET', 'new', '$', '1', 'KEEPTTL'))
+    r.assertEqual(r.execute_command('PTTL', 'new'), -1)
+
+def testRespCommandResp3(env):
+    """Test that JSON.RESP replies objects as RESP3 maps and arrays without their marker to RESP3 clients"""
+    r = env
+    # RedisModule_ReplyWithMap is only available since Redis 7.0
+    version = r.execute_command('INFO', 'server')['redis_version']
+    if tuple(int(n) for n in version.split('.')[:2]) < (7, 0):
+        env.skip()
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":[1,true],"b":{}}'))
+    sock, reply = raw_connection(r)
+    r.assertTrue(reply('HELLO', '3').startswith(b'%'))
+    r.assertEqual(reply('JSON.RESP', 'test'),
+                  b'%2\r\n$1\r\na\r\n*2\r\n:1\r\n#t\r\n$1\r\nb\r\n%0\r\n')
+    r.assertEqual(reply('JSON.RESP', 'test', '.a'), b'*2\r\n:1\r\n#t\r\n')
+    sock.close()
+
+    # RESP2 replies are unchanged
+    r.assertEqual(r.execute_command('JSON.RESP', 'test'), ['{', 'a', ['[', 1, 'true'], 'b', ['{']])
+
+def testSetFormatResp(env):
+    """Test that JSON.SET FORMAT RESP sets the value replied by JSON.RESP"""
+    r = env
+
+    doc = {'a': [1, 2.0, -3.5e-7, 'x', '2.0', 'true', True, False, None], 'b': {'c': {}, 'd': []}, 'e': ''}
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', json.dumps(doc)))
+    sock, reply = raw_connection(r)
+    for protocol in ['2', '3']:
+        reply('HELLO', protocol)
+        resp = reply('JSON.RESP', 'test')
+        r.assertOk(r.execute_command('JSON.SET', 'copy', '$', resp, 'FORMAT', 'RESP'))
+        r.assertEqual(r.execute_command('JSON.GET', 'copy'), r.execute_command('JSON.GET', 'test'), message=protocol)
+        resp = reply('JSON.RESP', 'test', '$.a[1]')
+        r.assertOk(r.execute_command('JSON.SET', 'copy', '$.f', resp, 'FORMAT', 'RESP'))
+        r.assertEqual(r.execute_command('JSON.GET', 'copy', '$.f'), '[2.0]', message=protocol)
+    sock.close()
+
+    r.assertOk(r.execute_command('JSON.SET', 'copy', '$', '*3\r\n+[\r\n:1\r\n$1\r\na\r\n', 'FORMAT', 'RESP'))
+    r.assertEqual(r.execute_command('JSON.GET', 'copy'), '[1,"a"]')
+    r.assertEqual(r.execute_command('JSON.SET', 'copy', '$[1]', '$-1\r\n', 'FORMAT', 'RESP', 'GET'), '["a"]')
+    r.assertEqual(r.execute_command('JSON.GET', 'copy'), '[1,null]')
+
+    # Arrays without the marker are RESP3 arrays
+    r.assertOk(r.execute_command('JSON.SET', 'copy', '$', '*2\r\n:1\r\n*0\r\n', 'FORMAT', 'RESP'))
+    r.assertEqual(r.execute_command('JSON.GET', 'copy'), '[1,[]]')
+    r.assertOk(r.execute_command('JSON.SET', 'copy', '$', '*2\r\n$1\r\n[\r\n%1\r\n$1\r\na\r\n*1\r\n#t\r\n', 'FORMAT', 'RESP'))
+    r.assertEqual(r.execute_command('JSON.GET', 'copy'), '["[",{"a":[true]}]')
+
+    r.expect('JSON.SET', 'copy', '$', '*2\r\n:1\r\n:2\r\n:3\r\n', 'FORMAT', 'RESP').raiseError()
+    r.expect('JSON.SET', 'copy', '$', '*2\r\n+{\r\n$1\r\na\r\n', 'FORMAT', 'RESP').raiseError()
+    r.expect('JSON.SET', 'copy', '$', '*3\r\n+[\r\n:1\r\n', 'FORMAT', 'RESP').raiseError()
+    r.expect('JSON.SET', 'copy', '$', '$5\r\nab\r\n', 'FORMAT', 'RESP').raiseError()
+    r.expect('JSON.SET', 'copy', '$', ':1\r\n:2\r\n', 'FORMAT', 'RESP').raiseError()
+    r.expect('JSON.SET', 'copy', '$', '+foo\r\n', 'FORMAT', 'RESP').raiseError()
+    r.assertEqual(r.execute_command('JSON.GET', 'copy', 'FORMAT', 'RESP'), '*3\r\n+[\r\n:1\r\n$-1\r\n')
+    r.assertEqual(r.execute_command('JSON.GET', 'copy'), '[1,null]')
+
+def testRespMaxDepth():
+    env = Env()
+    r = env
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '[' * 127 + ']' * 127))
+    res = r.execute_command('JSON.RESP', 'test')
+    for _ in range(126):
+        res = res[1]
+    r.assertEqual(res, ['['])
+    # one more level could not be parsed back, so no write may build it
+    r.expect('JSON.SET', 'deep', '.', '[' * 128 + ']' * 128).raiseError()
+    r.expect('JSON.ARRAPPEND', 'test', '$' + '[0]' * 126, '[]').error().contains('maximum nesting depth of 127 exceeded')
+    r.expect('JSON.SET', 'test', '$' + '[0]' * 125, '[[[]]]').error().contains('maximum nesting depth of 127 exceeded')
+
+    # documents stored before the limit was lowered are not replied past it
+    r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'MAX_DEPTH', '10'))
+    r.expect('JSON.RESP', 'test').error().contains('maximum nesting depth of 10 exceeded')
+    r.assertEqual(len(r.execute_command('JSON.RESP', 'test', '$' + '[0]' * 117)), 2)
+    # the limit can not exceed the depth the parser accepts
+    r.expect('JSON.CONFIG', 'SET', 'MAX_DEPTH', '128').error().contains('expected an integer between 1 and 127')
+
+def testMaxDepthConfig():
+    env = Env(moduleArgs='MAX_DEPTH 3')
+    r = env
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '[[[1]]]'))
+    r.expect('JSON.SET', 'test', '.', '[[[[1]]]]').error().contains('maximum nesting depth of 3 exceeded')
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$[0]', '[[2]]'))
+    r.expect('JSON.SET', 'test', '$[0][0]', '[[1]]').error().contains('maximum nesting depth of 3 exceeded')
+    r.assertEqual(r.execute_command('JSON.RESP', 'test'), ['[', ['[', ['[', 2]]])
+
+def testPathCache(env):
+    r = env
+    info = getCacheInfo(r)
+    r.assertEqual(info['items'], 0)
+    r.assertEqual(info['max_items'], 1000)
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"foo": "bar", "baz": 1}'))
+    r.assertOk(r.execute_command('JSON._CACHEINIT', '2'))
+    r.assertEqual(getCacheInfo(r), {'hits': 0, 'misses': 0, 'items': 0, 'bytes': 0, 'max_items': 2})
+
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.foo'), '["bar"]')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.foo'), '["bar"]')
+    info = getCacheInfo(r)
+    r.assertEqual((info['hits'], info['misses'], info['items']), (1, 1, 1))
+    r.assertGreater(info['bytes'], 0)
+
+    # Least recently used paths are evicted
+    r.execute_command('JSON.GET', 'test', '$.baz')
+    r.execute_command('JSON.GET', 'test', '$.foo')
+    r.execute_command('JSON.GET', 'test', '$.qux')
+    r.execute_command('JSON.GET', 'test', '$.foo')
+    info = getCacheInfo(r)
+    r.assertEqual((info['hits'], info['misses'], info['items']), (3, 3, 2))
+
+    # Invalid paths are not cached
+    r.expect('JSON.GET', 'test', '$..[').raiseError()
+    r.assertEqual(getCacheInfo(r)['items'], 2)
+
+    r.expect('JSON._CACHEINIT', '-1').raiseError()
+    r.expect('JSON._CACHEINIT', '1', '2').raiseError()
+    r.assertOk(r.execute_command('JSON._CACHEINIT', '0'))
+    r.execute_command('JSON.GET', 'test', '$.foo')
+    r.assertEqual(getCacheInfo(r), {'hits': 0, 'misses': 0, 'items': 0, 'bytes': 0, 'max_items': 0})
+    r.assertOk(r.execute_command('JSON._CACHEINIT'))
+    r.assertEqual(getCacheInfo(r)['max_items'], 1000)
+
+def testPathCacheSharedByCommands(env):
+    r = env
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"foo": "bar", "baz": [1]}'))
+    r.assertOk(r.execute_command('JSON._CACHEINIT'))
+    r.execute_command('JSON.GET', 'test', '$.foo', '$.baz')
+    r.execute_command('JSON.GET', 'test', '$.foo', '$.baz')
+    r.execute_command('JSON.ARRAPPEND', 'test', '$.baz', '2')
+    r.execute_command('JSON.SET', 'test', '$.baz', '[3]')
+    info = getCacheInfo(r)
+    r.assertEqual((info['hits'], info['misses'], info['items']), (4, 2, 2))
+
+def testPathCacheConfig():
+    env = Env(moduleArgs='PATH_CACHE_SIZE 0')
+    r = env
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"foo": "bar"}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.foo'), '["bar"]')
+    r.assertEqual(getCacheInfo(r)['items'], 0)
+    r.assertEqual(getCacheInfo(r)['max_items'], 0)
+
+def testDuplicateKeys(env):
+    """Test that the last value of a duplicate key wins by default"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":1,"b":{"c":2,"c":3},"a":4}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"a":4,"b":{"c":3}}')
+
+def testDuplicateKeysConfig():
+    env = Env(moduleArgs='DUPLICATE_KEYS ERROR')
+    r = env
+    r.expect('JSON.SET', 'test', '.', '{"a":1,"a":2}').error().contains("duplicate key 'a'")
+    r.expect('JSON.SET', 'test', '.', '[{"b":{"c":1,"c":1}}]').error().contains("duplicate key 'c'")
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":{"a":1},"b":[{"a":2},{"a":3}]}'))
+    r.expect('JSON.ARRAPPEND', 'test', '.b', '{"x":1,"x":2}').error().contains("argument 3").contains("duplicate key 'x'")
+    r.expect('JSON.UNFLATTEN', 'test', '.c', '{"x":1,"x":2}').error().contains("duplicate key 'x'")
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"a":{"a":1},"b":[{"a":2},{"a":3}]}')
+
+def testFormattingConfig():
+    env = Env(moduleArgs='DEFAULT_INDENT_SIZE 2')
+    r = env
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"b":[1],"a":2}'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{\n  "b": [\n    1\n  ],\n  "a": 2\n}')
+    r.assertEqual(r.execute_command('JSON.MGET', 'test', '.a'), ['2'])
+    # any formatting option replaces the default one
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'INDENT', ''), '{"b":[1],"a":2}')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'NEWLINE', ''), '{"b":[1],"a":2}')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', 'INDENTSIZE', '0'), '{"b":[1],"a":2}')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '.b', 'FORMAT', 'NDJSON'), '1\n')
+
+    r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'DEFAULT_INDENT_SIZE', '0'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"b":[1],"a":2}')
+    r.expect('JSON.CONFIG', 'SET', 'DEFAULT_INDENT_SIZE', '11').error().contains('expected an integer between 0 and 10')
+
+    r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'PRESERVE_ORDER', 'NO'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"a":2,"b":[1]}')
+    r.assertEqual(r.execute_command('JSON.MGET', 'test', '.'), ['{"a":2,"b":[1]}'])
+    r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'PRESERVE_ORDER', 'YES'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"b":[1],"a":2}')
+
+def testModuleConfigParameters():
+    env = Env(moduleArgs='MAX_DEPTH 3')
+    r = env
+    # The module configuration API is only available since Redis 7.0
+    version = r.execute_command('INFO', 'server')['redis_version']
+    if tuple(int(n) for n in version.split('.')[:2]) < (7, 0):
+        env.skip()
+
+    # the parameters are named after the module, which is ReJSON
+    def config_get(name):
+        res = r.execute_command('CONFIG', 'GET', name)
+        r.assertEqual(res[0].lower(), name)
+        return res[1]
+
+    r.assertEqual(config_get('rejson.max-depth'), '3')
+    r.assertOk(r.execute_command('CONFIG', 'SET', 'rejson.max-depth', '4'))
+    r.assertEqual(r.execute_command('JSON.CONFIG', 'GET', 'MAX_DEPTH'), ['MAX_DEPTH', '4'])
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '[[[[1]]]]'))
+    r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'MAX_DEPTH', '2'))
+    r.assertEqual(config_get('rejson.max-depth'), '2')
+    r.expect('CONFIG', 'SET', 'rejson.max-depth', '0').error().contains('expected an integer between 1 and 127')
+    r.expect('CONFIG', 'SET', 'rejson.notify-paths', 'maybe').error().contains('expected YES or NO')
+
+    r.assertOk(r.execute_command('CONFIG', 'SET', 'rejson.path-cache-size', '2'))
+    r.assertEqual(getCacheInfo(r)['max_items'], 2)
+    r.assertEqual(config_get('rejson.preserve-order'), 'YES')
+
+def testMaxInputSizeConfig():
+    env = Env(moduleArgs='MAX_INPUT_SIZE 10')
+    r = env
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '[1,2,3,4]'))
+    r.expect('JSON.SET', 'test', '.', '[1,2,3,4,5,6]').error().contains('input size of 13 bytes exceeds the limit of 10 bytes')
+    r.expect('JSON.ARRAPPEND', 'test', '.', '"0123456789"').error().contains('input size of 12 bytes')
+    # the smaller of MAX_INPUT_SIZE and MAX_DOC_SIZE applies
+    r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'MAX_DOC_SIZE', '5'))
+    r.expect('JSON.SET', 'test', '.', '[1,22]').error().contains('input size of 6 bytes exceeds the limit of 5 bytes')
+    r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'MAX_DOC_SIZE', '0'))
+    r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'MAX_INPUT_SIZE', '0'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '[1,2,3,4,5,6]'))
+    r.expect('JSON.CONFIG', 'SET', 'MAX_INPUT_SIZE', '-1').error().contains('expected a non-negative integer')
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '[1,2,3,4,5,6]')
+
+def testMaxDocSizeConfig():
+    env = Env(moduleArgs='MAX_DOC_SIZE 20')
+    r = env
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '{"a":"0123456789"}'))
+    r.expect('JSON.SET', 'test', '.', '{"a":"0123456789abcd"}').error().contains('input size of 22 bytes exceeds the limit of 20 bytes')
+    r.expect('JSON.SET', 'big', '.', '[' + '1,' * 1000000 + '1]').error().contains('exceeds the limit of 20 bytes')
+    r.assertEqual(r.execute_command('EXISTS', 'big'), 0)
+    # compressed values are limited by their decompressed size
+    r.expect('JSON.SET', 'big', '.', gzip.compress(b'[' + b'1,' * 1000000 + b'1]'), 'COMPRESS', 'GZIP').error().contains('exceeds the limit of 20 bytes')
+    r.assertEqual(r.execute_command('EXISTS', 'big'), 0)
+
+    # Sizes count what a path replaces, and what it adds otherwise
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$.a', '"01234567890"'))
+    r.expect('JSON.SET', 'test', '$.a', '"01234567890123"').error().contains('exceeds the limit')
+    r.expect('JSON.SET', 'test', '$.b', '"0123"').error().contains('exceeds the limit')
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$.a', '1'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$.b', '2'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test'), '{"a":1,"b":2}')
+
+    # the values added by other commands are checked before they are parsed
+    r.assertOk(r.execute_command('JSON.SET', 'arr', '.', '[]'))
+    big = '[' + '1,' * 1000000 + '1]'
+    r.expect('JSON.ARRAPPEND', 'arr', '.', big).error().contains('input size of 2000003 bytes exceeds the limit of 20 bytes')
+    r.expect('JSON.ARRINSERT', 'arr', '.', 0, big).error().contains('exceeds the limit of 20 bytes')
+    r.expect('JSON.ARRAPPENDUNIQUE', 'arr', '.', big).error().contains('exceeds the limit of 20 bytes')
+    r.expect('JSON.MULTIOP', 'arr', json.dumps([{'op': 'arrappend', 'path': '$', 'args': [1] * 100}])).error().contains('exceeds the limit of 20 bytes')
+    r.assertEqual(r.execute_command('JSON.ARRAPPEND', 'arr', '.', '[1,2,3]'), 1)
+    r.assertEqual(r.execute_command('JSON.GET', 'arr'), '[[1,2,3]]')
+
+    # every write that grows the document is checked, new members counting their key
+    r.assertOk(r.execute_command('JSON.SET', 'doc', '.', '{"a":"0123456789"}'))
+    r.expect('JSON.SET', 'doc', '$.bb', '1').error().contains('document size of 25 bytes exceeds the limit of 20 bytes')
+    r.expect('JSON.STRAPPEND', 'doc', '$.a', '"abc"').error().contains('document size of 21 bytes')
+    r.expect('JSON.COPYPATH', 'doc', '$.a', '$.c').error().contains('document size of 35 bytes')
+    r.expect('JSON.ARRAPPEND', 'arr', '.', '"0123456789a"').error().contains('document size of 23 bytes')
+    r.assertEqual(r.execute_command('JSON.GET', 'doc'), '{"a":"0123456789"}')
+    r.assertEqual(r.execute_command('JSON.GET', 'arr'), '[[1,2,3]]')
+
+def testConfigCommand():
+    env = Env(moduleArgs='MAX_DEPTH 3')
+    r = env
+    r.assertEqual(r.execute_command('JSON.CONFIG', 'GET', '*'),
+                  ['MAX_DEPTH', '3', 'PATH_CACHE_SIZE', '1000', 'DUPLICATE_KEYS', 'LAST', 'NOTIFY_PATHS', 'NO', 'MAX_DOC_SIZE', '0',
+                   'SHARE_DOCS', 'NO', 'MAX_INPUT_SIZE', '67108864', 'DEFAULT_INDENT_SIZE', '0',
+                   'PRESERVE_ORDER', 'YES'])
+    r.assertEqual(r.execute_command('JSON.CONFIG', 'GET', 'unknown'), [])
+
+    r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'max_depth', '4'))
+    r.assertEqual(r.execute_command('JSON.CONFIG', 'GET', 'MAX_DEPTH'), ['MAX_DEPTH', '4'])
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.', '[[[[1]]]]'))
+
+    r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'DUPLICATE_KEYS', 'ERROR'))
+    r.expect('JSON.SET', 'test', '.', '{"a":1,"a":2}').error().contains("duplicate key 'a'")
+
+    r.assertOk(r.execute_command('JSON.CONFIG', 'SET', 'PATH_CACHE_SIZE', '2'))
+    r.assertEqual(getCacheInfo(r)['max_items'], 2)
+
+    r.expect('JSON.CONFIG', 'SET', 'MAX_DEPTH', '0').error().contains('expected an integer between 1 and 127')
+    r.expect('JSON.CONFIG', 'SET', 'NOTIFY_PATHS', 'maybe').error().contains('expected YES or NO')
+    r.expect('JSON.CONFIG', 'SET', 'unknown', '1').error().contains('unknown configuration')
+    r.expect('JSON.CONFIG', 'SET', 'MAX_DEPTH').raiseError()
+    r.expect('JSON.CONFIG', 'RESET').error().contains('unknown subcommand')
+    r.assertEqual(r.execute_command('JSON.CONFIG', 'GET', 'MAX_DEPTH'), ['MAX_DEPTH', '4'])
+
 # class CacheTestCase(BaseReJSONTest):
 #     @property
 #     def module_args(env):
@@ -19,13 +19,18 @@ def test_keyspace_set(env):
         env.assertEqual('psubscribe', pubsub.get_message()['type']) 
 
         r.execute_command('JSON.SET', 'test_key', '$', '{"foo": "bar"}')
-        assert_msg(env, pubsub.get_message(), 'pmessage', 'json.set')
+        assert_msg(env, pubsub.get_message(), 'pmessage', 'json.create')
         assert_msg(env, pubsub.get_message(), 'pmessage', 'test_key')
 
         env.assertEqual('OK', r.execute_command('JSON.SET', 'test_key', '$.foo', '"gogo"'))
         assert_msg(env, pubsub.get_message(), 'pmessage', 'json.set')
         assert_msg(env, pubsub.get_message(), 'pmessage', 'test_key')
 
+        # Replacing the root of an existing key is an update
+        env.assertEqual('OK', r.execute_command('JSON.SET', 'test_key', '$', '{"foo": "gogo"}'))
+        assert_msg(env, pubsub.get_message(), 'pmessage', 'json.set')
+        assert_msg(env, pubsub.get_message(), 'pmessage', 'test_key')
+
         env.assertEqual(8, r.execute_command('JSON.STRAPPEND', 'test_key', '$.foo', '"toto"'))
         assert_msg(env, pubsub.get_message(), 'pmessage', 'json.strappend')
         assert_msg(env, pubsub.get_message(), 'pmessage', 'test_key')
@@ -60,7 +65,7 @@ def test_keyspace_arr(env):
         env.assertEqual('psubscribe', pubsub.get_message()['type']) 
 
         r.execute_command('JSON.SET', 'test_key_arr', '$', '{"foo": []}')
-        assert_msg(env, pubsub.get_message(), 'pmessage', 'json.set')
+        assert_msg(env, pubsub.get_message(), 'pmessage', 'json.create')
         assert_msg(env, pubsub.get_message(), 'pmessage', 'test_key_arr')
 
         env.assertEqual(2, r.execute_command('JSON.ARRAPPEND', 'test_key_arr', '$.foo', '"gogo1"', '"gogo2"'))
@@ -99,7 +104,7 @@ def test_keyspace_del(env):
         env.assertEqual('psubscribe', pubsub.get_message()['type']) 
 
         r.execute_command('JSON.SET', 'test_key', '$', '{"foo": "bar", "foo2":"bar2", "foo3":"bar3"}')
-        assert_msg(env, pubsub.get_message(), 'pmessage', 'json.set')
+        assert_msg(env, pubsub.get_message(), 'pmessage', 'json.create')
         assert_msg(env, pubsub.get_message(), 'pmessage', 'test_key')
 
         env.assertEqual(1, r.execute_command('JSON.DEL', 'test_key', '$.foo'))
@@ -124,7 +129,7 @@ def test_keyspace_num(env):
         env.assertEqual('psubscribe', pubsub.get_message()['type']) 
 
         r.execute_command('JSON.SET', 'test_key', '$', '{"foo": 1}')
-        assert_msg(env, pubsub.get_message(), 'pmessage', 'json.set')
+        assert_msg(env, pubsub.get_message(), 'pmessage', 'json.create')
         assert_msg(env, pubsub.get_message(), 'pmessage', 'test_key')
 
         env.assertEqual('4', r.execute_command('JSON.NUMINCRBY', 'test_key', '$.foo', 3))
@@ -136,3 +141,30 @@ def test_keyspace_num(env):
         assert_msg(env, pubsub.get_message(), 'pmessage', 'test_key')
 
         # TODO add negative test for number
+
+def test_keyspace_notify_paths():
+    env = Env(moduleArgs='NOTIFY_PATHS YES')
+    with env.getClusterConnectionIfNeeded() as r:
+        r.execute_command('config', 'set', 'notify-keyspace-events', 'KEA')
+
+        pubsub = r.pubsub()
+        pubsub.psubscribe('__key*')
+
+        time.sleep(1)
+        env.assertEqual('psubscribe', pubsub.get_message()['type'])
+
+        r.execute_command('JSON.SET', 'test_key', '$', '{"foo": 1, "bar": "baz"}')
+        assert_msg(env, pubsub.get_message(), 'pmessage', 'json.create:$')
+        assert_msg(env, pubsub.get_message(), 'pmessage', 'test_key')
+
+        r.execute_command('JSON.SET', 'test_key', '$.foo', '2')
+        assert_msg(env, pubsub.get_message(), 'pmessage', 'json.set:$.foo')
+        assert_msg(env, pubsub.get_message(), 'pmessage', 'test_key')
+
+        env.assertEqual('5', r.execute_command('JSON.NUMINCRBY', 'test_key', '$.foo', 3))
+        assert_msg(env, pubsub.get_message(), 'pmessage', 'json.numincrby:$.foo')
+        assert_msg(env, pubsub.get_message(), 'pmessage', 'test_key')
+
+        env.assertEqual(1, r.execute_command('JSON.DEL', 'test_key', '$.bar'))
+        assert_msg(env, pubsub.get_message(), 'pmessage', 'json.del:$.bar')
+        assert_msg(env, pubsub.get_message(), 'pmessage', 'test_key')